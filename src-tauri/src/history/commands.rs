@@ -0,0 +1,66 @@
+// History Tauri commands
+// Ranges are half-open, `[from, to)`, and take RFC 3339 times.
+
+use chrono::{DateTime, Utc};
+use tauri::State;
+
+use super::models::{EventSource, HistoryEvent, HistorySummary};
+use super::{HistoryResult, HistoryStore};
+use crate::kimai::models::KimaiTimeSheet;
+
+#[tauri::command]
+pub fn history_upsert_timesheets(
+    history: State<'_, HistoryStore>,
+    profile_id: String,
+    timesheets: Vec<KimaiTimeSheet>,
+) -> HistoryResult<usize> {
+    history.upsert_timesheets(&profile_id, &timesheets)
+}
+
+#[tauri::command]
+pub fn history_delete_timesheets(
+    history: State<'_, HistoryStore>,
+    profile_id: String,
+    ids: Vec<u64>,
+) -> HistoryResult<usize> {
+    history.delete_timesheets(&profile_id, &ids)
+}
+
+/// Timesheets of a profile beginning in the range, newest first
+#[tauri::command]
+pub fn history_timesheets(
+    history: State<'_, HistoryStore>,
+    profile_id: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: Option<u32>,
+) -> HistoryResult<Vec<KimaiTimeSheet>> {
+    history.timesheets(&profile_id, from, to, limit)
+}
+
+#[tauri::command]
+pub fn history_summary(
+    history: State<'_, HistoryStore>,
+    profile_id: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> HistoryResult<HistorySummary> {
+    history.summary(&profile_id, from, to)
+}
+
+/// Stores a timer, session or task event and returns its id
+#[tauri::command]
+pub fn history_record_event(history: State<'_, HistoryStore>, event: HistoryEvent) -> HistoryResult<i64> {
+    history.record_event(&event)
+}
+
+#[tauri::command]
+pub fn history_events(
+    history: State<'_, HistoryStore>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    source: Option<EventSource>,
+    profile_id: Option<String>,
+) -> HistoryResult<Vec<HistoryEvent>> {
+    history.events(from, to, source, profile_id.as_deref())
+}
@@ -0,0 +1,440 @@
+// History
+// An embedded SQLite database for timesheets, timer/session/task events and
+// sync bookkeeping. Times are stored as fixed-width UTC strings so range
+// queries can use the indexes.
+
+pub mod commands;
+pub mod models;
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Serialize, Serializer};
+
+use crate::kimai::cache::parse_kimai_datetime;
+use crate::kimai::models::KimaiTimeSheet;
+use models::{EventSource, HistoryEvent, HistorySummary};
+
+const DATABASE_FILE: &str = "history.sqlite3";
+
+/// Applied in order; `PRAGMA user_version` is the number already applied
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE timesheets (
+        profile_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        begin_utc TEXT NOT NULL,
+        end_utc TEXT,
+        duration INTEGER NOT NULL DEFAULT 0,
+        billable INTEGER NOT NULL DEFAULT 0,
+        customer INTEGER,
+        project INTEGER NOT NULL,
+        activity INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        stored_at TEXT NOT NULL,
+        PRIMARY KEY (profile_id, id)
+    );
+    CREATE INDEX timesheets_profile_begin ON timesheets (profile_id, begin_utc);
+
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        source TEXT NOT NULL,
+        type TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        profile_id TEXT,
+        task_id INTEGER,
+        details TEXT
+    );
+    CREATE INDEX events_timestamp ON events (timestamp);
+    CREATE INDEX events_source_timestamp ON events (source, timestamp);
+
+    CREATE TABLE sync_state (
+        profile_id TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (profile_id, key)
+    );
+"#, r#"
+    CREATE INDEX events_profile_timestamp ON events (profile_id, timestamp);
+"#];
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryError {
+    #[error("History database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Invalid history data: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("Could not create the history database: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl HistoryError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HistoryError::Database(_) => "database",
+            HistoryError::Invalid(_) => "invalid",
+            HistoryError::Io(_) => "io",
+        }
+    }
+}
+
+impl Serialize for HistoryError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorPayload {
+            kind: &'static str,
+            message: String,
+        }
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type HistoryResult<T> = Result<T, HistoryError>;
+
+pub struct HistoryStore {
+    conn: Mutex<Connection>,
+}
+
+impl HistoryStore {
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(DATABASE_FILE)
+    }
+
+    pub fn open(path: &Path) -> HistoryResult<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::with_connection(conn)
+    }
+
+    fn with_connection(mut conn: Connection) -> HistoryResult<Self> {
+        // Deleted rows are overwritten with zeros, so a purged profile leaves nothing behind
+        conn.pragma_update(None, "secure_delete", "ON")?;
+        migrate(&mut conn)?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    // Timesheets
+    /// Inserts or replaces timesheets by id; returns how many were written
+    pub fn upsert_timesheets(&self, profile_id: &str, timesheets: &[KimaiTimeSheet]) -> HistoryResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let stored_at = format_utc(Utc::now());
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO timesheets
+                 (profile_id, id, begin_utc, end_utc, duration, billable, customer, project, activity, data, stored_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+            for timesheet in timesheets {
+                insert.execute(params![
+                    profile_id,
+                    timesheet.id as i64,
+                    normalize_time(&timesheet.begin),
+                    timesheet.end.as_deref().map(normalize_time),
+                    timesheet.duration.unwrap_or(0),
+                    timesheet.billable,
+                    timesheet.customer.map(|id| id as i64),
+                    timesheet.project.id() as i64,
+                    timesheet.activity.id() as i64,
+                    serde_json::to_string(timesheet)?,
+                    stored_at,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(timesheets.len())
+    }
+
+    pub fn delete_timesheets(&self, profile_id: &str, ids: &[u64]) -> HistoryResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let mut delete = conn.prepare_cached("DELETE FROM timesheets WHERE profile_id = ?1 AND id = ?2")?;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += delete.execute(params![profile_id, *id as i64])?;
+        }
+        Ok(deleted)
+    }
+
+    /// Timesheets beginning in `[from, to)`, newest first
+    pub fn timesheets(
+        &self,
+        profile_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: Option<u32>,
+    ) -> HistoryResult<Vec<KimaiTimeSheet>> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare_cached(
+            "SELECT data FROM timesheets
+             WHERE profile_id = ?1 AND begin_utc >= ?2 AND begin_utc < ?3
+             ORDER BY begin_utc DESC LIMIT ?4",
+        )?;
+        let limit = limit.map_or(-1, i64::from);
+        let rows = query.query_map(params![profile_id, format_utc(from), format_utc(to), limit], |row| {
+            row.get::<_, String>(0)
+        })?;
+
+        let mut timesheets = Vec::new();
+        for data in rows {
+            timesheets.push(serde_json::from_str(&data?)?);
+        }
+        Ok(timesheets)
+    }
+
+    pub fn summary(&self, profile_id: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> HistoryResult<HistorySummary> {
+        let conn = self.conn.lock().unwrap();
+        let summary = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(duration), 0), COALESCE(SUM(CASE WHEN billable THEN duration ELSE 0 END), 0)
+             FROM timesheets
+             WHERE profile_id = ?1 AND begin_utc >= ?2 AND begin_utc < ?3",
+            params![profile_id, format_utc(from), format_utc(to)],
+            |row| {
+                Ok(HistorySummary {
+                    count: row.get::<_, i64>(0)? as u64,
+                    total_time: row.get(1)?,
+                    billable_time: row.get(2)?,
+                })
+            },
+        )?;
+        Ok(summary)
+    }
+
+    // Events
+    pub fn record_event(&self, event: &HistoryEvent) -> HistoryResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let details = event.details.as_ref().map(serde_json::to_string).transpose()?;
+        conn.prepare_cached(
+            "INSERT INTO events (source, type, timestamp, profile_id, task_id, details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            event.source.as_str(),
+            event.kind,
+            format_utc(event.timestamp),
+            event.profile_id,
+            event.task_id.map(|id| id as i64),
+            details,
+        ])?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Events in `[from, to)`, oldest first, optionally of one source or
+    /// profile only
+    pub fn events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        source: Option<EventSource>,
+        profile_id: Option<&str>,
+    ) -> HistoryResult<Vec<HistoryEvent>> {
+        let conn = self.conn.lock().unwrap();
+        let mut query = conn.prepare_cached(
+            "SELECT id, source, type, timestamp, profile_id, task_id, details FROM events
+             WHERE timestamp >= ?1 AND timestamp < ?2 AND (?3 IS NULL OR source = ?3)
+               AND (?4 IS NULL OR profile_id = ?4)
+             ORDER BY timestamp, id",
+        )?;
+        let rows = query.query_map(
+            params![format_utc(from), format_utc(to), source.map(|source| source.as_str()), profile_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            },
+        )?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (id, source, kind, timestamp, profile_id, task_id, details) = row?;
+            // Rows written by a newer version with an unknown source are skipped
+            let Some(source) = EventSource::parse(&source) else {
+                continue;
+            };
+            let Ok(timestamp) = DateTime::parse_from_rfc3339(&timestamp) else {
+                continue;
+            };
+            events.push(HistoryEvent {
+                id: Some(id),
+                source,
+                kind,
+                timestamp: timestamp.with_timezone(&Utc),
+                profile_id,
+                task_id: task_id.map(|id| id as u64),
+                details: details.as_deref().map(serde_json::from_str).transpose()?,
+            });
+        }
+        Ok(events)
+    }
+
+    /// Deletes every row of a profile and truncates the write-ahead log, so
+    /// no copy of them is left in either file. Returns how many were deleted.
+    pub fn purge_profile(&self, profile_id: &str) -> HistoryResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+        for table in ["timesheets", "events", "sync_state"] {
+            deleted += tx.execute(&format!("DELETE FROM {table} WHERE profile_id = ?1"), params![profile_id])?;
+        }
+        tx.commit()?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(deleted)
+    }
+
+    // Sync metadata
+    pub fn sync_value(&self, profile_id: &str, key: &str) -> HistoryResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let value = conn
+            .query_row(
+                "SELECT value FROM sync_state WHERE profile_id = ?1 AND key = ?2",
+                params![profile_id, key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value)
+    }
+
+    pub fn set_sync_value(&self, profile_id: &str, key: &str, value: &str) -> HistoryResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_state (profile_id, key, value, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![profile_id, key, value, format_utc(Utc::now())],
+        )?;
+        Ok(())
+    }
+}
+
+fn migrate(conn: &mut Connection) -> HistoryResult<()> {
+    let applied: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let tx = conn.transaction()?;
+    for migration in MIGRATIONS.iter().skip(applied) {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Fixed-width UTC, so string order is time order
+fn format_utc(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Kimai's offsets (`+0200`) and RFC 3339 both end up as UTC; anything else
+/// is kept verbatim.
+fn normalize_time(value: &str) -> String {
+    parse_kimai_datetime(value)
+        .or_else(|| DateTime::parse_from_rfc3339(value).ok().map(|time| time.with_timezone(&Utc)))
+        .map(format_utc)
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+
+    fn store() -> HistoryStore {
+        HistoryStore::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn timesheet(id: u64, begin: &str, duration: i64, billable: bool) -> KimaiTimeSheet {
+        serde_json::from_value(json!({
+            "id": id, "begin": begin, "duration": duration, "billable": billable,
+            "user": 1, "activity": 2, "project": 3, "customer": 4
+        }))
+        .unwrap()
+    }
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, day, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn timesheets_are_queried_by_utc_range() {
+        let store = store();
+        store
+            .upsert_timesheets(
+                "work",
+                &[
+                    timesheet(1, "2024-03-01T23:30:00+0200", 600, true),
+                    timesheet(2, "2024-03-02T09:00:00+0200", 1200, false),
+                    timesheet(3, "2024-03-03T09:00:00+0200", 1800, true),
+                ],
+            )
+            .unwrap();
+        store.upsert_timesheets("home", &[timesheet(4, "2024-03-02T10:00:00+0200", 60, true)]).unwrap();
+
+        // 23:30+02:00 on the 1st is 21:30 UTC, so it falls on the 1st
+        let ids: Vec<_> = store.timesheets("work", day(1), day(3), None).unwrap().iter().map(|ts| ts.id).collect();
+        assert_eq!(ids, [2, 1]);
+
+        let summary = store.summary("work", day(1), day(4)).unwrap();
+        assert_eq!((summary.count, summary.total_time, summary.billable_time), (3, 3600, 2400));
+
+        store.delete_timesheets("work", &[3]).unwrap();
+        assert_eq!(store.summary("work", day(1), day(4)).unwrap().count, 2);
+    }
+
+    #[test]
+    fn events_and_sync_state_round_trip() {
+        let store = store();
+        let event = HistoryEvent {
+            id: None,
+            source: EventSource::Task,
+            kind: "started".into(),
+            timestamp: day(2),
+            profile_id: Some("work".into()),
+            task_id: Some(7),
+            details: Some(json!({"title": "Review"})),
+        };
+        let id = store.record_event(&event).unwrap();
+        store
+            .record_event(&HistoryEvent {
+                source: EventSource::Timer,
+                kind: "start".into(),
+                ..event.clone()
+            })
+            .unwrap();
+
+        let events = store.events(day(1), day(3), Some(EventSource::Task), None).unwrap();
+        assert_eq!(events, [HistoryEvent { id: Some(id), ..event }]);
+        assert_eq!(store.events(day(1), day(3), None, Some("work")).unwrap().len(), 2);
+        assert!(store.events(day(1), day(3), None, Some("home")).unwrap().is_empty());
+
+        assert_eq!(store.sync_value("work", "timesheets").unwrap(), None);
+        store.set_sync_value("work", "timesheets", "2024-03-02").unwrap();
+        assert_eq!(store.sync_value("work", "timesheets").unwrap().as_deref(), Some("2024-03-02"));
+    }
+
+    #[test]
+    fn purging_a_profile_leaves_the_others_alone() {
+        let store = store();
+        store.upsert_timesheets("work", &[timesheet(1, "2024-03-02T09:00:00+0200", 600, true)]).unwrap();
+        store.upsert_timesheets("home", &[timesheet(1, "2024-03-02T09:00:00+0200", 60, true)]).unwrap();
+        store.set_sync_value("work", "timesheets", "2024-03-02").unwrap();
+
+        assert_eq!(store.purge_profile("work").unwrap(), 2);
+        assert!(store.timesheets("work", day(1), day(3), None).unwrap().is_empty());
+        assert_eq!(store.sync_value("work", "timesheets").unwrap(), None);
+        assert_eq!(store.summary("home", day(1), day(3)).unwrap().count, 1);
+    }
+}
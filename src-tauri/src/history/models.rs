@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSource {
+    Timer,
+    Session,
+    Task,
+}
+
+impl EventSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventSource::Timer => "timer",
+            EventSource::Session => "session",
+            EventSource::Task => "task",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "timer" => Some(EventSource::Timer),
+            "session" => Some(EventSource::Session),
+            "task" => Some(EventSource::Task),
+            _ => None,
+        }
+    }
+}
+
+/// A `TimerEvent`, `SessionEvent` or `TaskEvent` from the stores
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEvent {
+    /// Assigned when stored
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub source: EventSource,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    #[serde(default)]
+    pub task_id: Option<u64>,
+    #[serde(default)]
+    pub details: Option<Value>,
+}
+
+/// Totals over a date range, in seconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistorySummary {
+    pub count: u64,
+    pub total_time: i64,
+    pub billable_time: i64,
+}
@@ -0,0 +1,372 @@
+// Entity cache
+// Persisted per profile so the UI has data before the server answers.
+// Timesheets refresh through `modified_after`. Kimai offers no such filter
+// for customers, projects or activities, so their lists are reloaded whole
+// only once a day or after the app changed them; in between, what changed
+// timesheets refer to and the cache lacks is fetched one by one. Tasks are
+// reloaded whole once their last fetch is older than TASK_MAX_AGE.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::capabilities::KimaiFeature;
+use super::client::KimaiClient;
+use super::error::{KimaiError, KimaiResult};
+use super::models::*;
+use super::pagination;
+use super::retry;
+
+/// Bumped whenever the cached shapes change; older files are discarded
+pub const CACHE_VERSION: &str = "1";
+
+pub const CUSTOMERS: &str = "customers";
+pub const PROJECTS: &str = "projects";
+pub const ACTIVITIES: &str = "activities";
+const TIMESHEETS: &str = "timeSheets";
+const TIMESHEETS_FULL: &str = "timeSheetsFull";
+const TASKS: &str = "tasks";
+
+/// How long a fetched task list is used as is
+const TASK_MAX_AGE: Duration = Duration::minutes(15);
+/// Customers, projects and activities are reloaded whole this often, to
+/// pick up renames and deletions the fill-ins can't see
+const ENTITY_FULL_MAX_AGE: Duration = Duration::hours(24);
+/// More missing entities of a list than this reload the list instead
+const MAX_FILL_INS: usize = 10;
+/// Incremental syncs can't see deletions, so timesheets are reloaded fully this often
+const TIMESHEETS_FULL_MAX_AGE: Duration = Duration::hours(24);
+const TIMESHEET_WINDOW_DAYS: i64 = 31;
+/// Kimai reads `modified_after` in the user's timezone, which may differ from
+/// ours; reaching back by the widest gap between two UTC offsets means no
+/// change is missed. Overlapping entries are merged by id.
+const MODIFIED_AFTER_MARGIN: Duration = Duration::hours(26);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiCache {
+    pub customers: Vec<KimaiCustomer>,
+    pub projects: Vec<KimaiProject>,
+    pub activities: Vec<KimaiActivity>,
+    pub time_sheets: Vec<KimaiTimeSheet>,
+    pub tasks: Vec<KimaiTask>,
+    /// RFC 3339 time of the last fetch, keyed by entity list
+    pub last_updated: BTreeMap<String, String>,
+    pub version: String,
+}
+
+impl Default for KimaiCache {
+    fn default() -> Self {
+        Self {
+            customers: Vec::new(),
+            projects: Vec::new(),
+            activities: Vec::new(),
+            time_sheets: Vec::new(),
+            tasks: Vec::new(),
+            last_updated: BTreeMap::new(),
+            version: CACHE_VERSION.to_string(),
+        }
+    }
+}
+
+impl KimaiCache {
+    pub fn path(cache_dir: &Path, profile_id: &str) -> KimaiResult<PathBuf> {
+        if !KimaiProfile::is_valid_id(profile_id) {
+            return Err(KimaiError::Storage(format!("Invalid profile id {profile_id:?}")));
+        }
+        Ok(cache_dir.join("kimai").join(format!("{profile_id}.json")))
+    }
+
+    /// Where the latest finished timesheet ends
+    pub fn last_end(&self) -> Option<DateTime<Utc>> {
+        self.time_sheets
+            .iter()
+            .filter_map(|timesheet| timesheet.end.as_deref().and_then(parse_kimai_datetime))
+            .max()
+    }
+
+    /// Reads a saved cache. Missing, unreadable or outdated files yield `None`.
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let cache: Self = serde_json::from_slice(&data).ok()?;
+        (cache.version == CACHE_VERSION).then_some(cache)
+    }
+
+    /// Has the next refresh reload `list` whole, as the app changed it
+    pub fn expire(path: &Path, list: &str) -> KimaiResult<()> {
+        let Some(mut cache) = Self::load(path) else {
+            return Ok(());
+        };
+        cache.last_updated.remove(list);
+        cache.save(path)
+    }
+
+    /// Writes to a temporary file first so a crash never leaves half a cache behind.
+    pub fn save(&self, path: &Path) -> KimaiResult<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_vec(self).map_err(|err| KimaiError::Storage(err.to_string()))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn last_updated(&self, key: &str) -> Option<DateTime<Utc>> {
+        let value = self.last_updated.get(key)?;
+        DateTime::parse_from_rfc3339(value).ok().map(|time| time.with_timezone(&Utc))
+    }
+
+    fn is_stale(&self, key: &str, max_age: Duration, now: DateTime<Utc>) -> bool {
+        self.last_updated(key).is_none_or(|updated| now - updated > max_age)
+    }
+
+    fn touch(&mut self, key: &str, now: DateTime<Utc>) {
+        self.last_updated.insert(key.to_string(), now.to_rfc3339());
+    }
+
+    /// Brings the cache up to date, fetching only what is stale or changed.
+    /// `force` reloads everything.
+    pub async fn refresh(&mut self, client: &KimaiClient, force: bool) -> KimaiResult<()> {
+        let now = Utc::now();
+
+        // Cached entities resolve names for timesheets fetched below
+        self.customers = client.normalize(std::mem::take(&mut self.customers));
+        self.projects = client.normalize(std::mem::take(&mut self.projects));
+        self.activities = client.normalize(std::mem::take(&mut self.activities));
+
+        if force || self.is_stale(CUSTOMERS, ENTITY_FULL_MAX_AGE, now) {
+            self.customers = client.get_customers().await?;
+            self.touch(CUSTOMERS, now);
+        }
+        if force || self.is_stale(PROJECTS, ENTITY_FULL_MAX_AGE, now) {
+            self.projects = client.get_projects(None).await?;
+            self.touch(PROJECTS, now);
+        }
+        if force || self.is_stale(ACTIVITIES, ENTITY_FULL_MAX_AGE, now) {
+            self.activities = client.get_activities(None).await?;
+            self.touch(ACTIVITIES, now);
+        }
+        self.refresh_timesheets(client, force, now).await?;
+        self.fill_in(client, now).await?;
+
+        let supports_tasks = client.capabilities().is_some_and(|caps| caps.supports(KimaiFeature::Tasks));
+        if supports_tasks && (force || self.is_stale(TASKS, TASK_MAX_AGE, now)) {
+            self.tasks = client.get_tasks(&KimaiTaskQuery::default()).await?;
+            self.touch(TASKS, now);
+        } else if !supports_tasks {
+            self.tasks.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Adds the projects and activities timesheets refer to that the lists
+    /// lack, e.g. created since their last reload, and the customers of
+    /// those projects
+    async fn fill_in(&mut self, client: &KimaiClient, now: DateTime<Utc>) -> KimaiResult<()> {
+        let projects = missing(&self.projects, self.time_sheets.iter().map(|timesheet| timesheet.project.id()));
+        if fill_in(&mut self.projects, projects, |id| client.get_project(id), || client.get_projects(None)).await? {
+            self.touch(PROJECTS, now);
+        }
+        let activities = missing(&self.activities, self.time_sheets.iter().map(|timesheet| timesheet.activity.id()));
+        if fill_in(&mut self.activities, activities, |id| client.get_activity(id), || client.get_activities(None)).await? {
+            self.touch(ACTIVITIES, now);
+        }
+        let customers = missing(&self.customers, self.projects.iter().map(|project| project.customer.id()));
+        if fill_in(&mut self.customers, customers, |id| client.get_customer(id), || client.get_customers()).await? {
+            self.touch(CUSTOMERS, now);
+        }
+        // What refers to the new entities gets their names now
+        self.projects = client.normalize(std::mem::take(&mut self.projects));
+        self.time_sheets = client.normalize(std::mem::take(&mut self.time_sheets));
+        Ok(())
+    }
+
+    async fn refresh_timesheets(&mut self, client: &KimaiClient, force: bool, now: DateTime<Utc>) -> KimaiResult<()> {
+        let window_start = now - Duration::days(TIMESHEET_WINDOW_DAYS);
+        let mut query = KimaiTimeSheetQuery {
+            begin: Some(kimai_datetime(window_start)),
+            ..Default::default()
+        };
+
+        let supports_incremental = client
+            .capabilities()
+            .is_some_and(|caps| caps.supports(KimaiFeature::ModifiedAfter));
+        let since = self.last_updated(TIMESHEETS);
+        let incremental = !force && supports_incremental && !self.is_stale(TIMESHEETS_FULL, TIMESHEETS_FULL_MAX_AGE, now);
+
+        match since.filter(|_| incremental) {
+            Some(since) => {
+                query.modified_after = Some(kimai_datetime(since - MODIFIED_AFTER_MARGIN));
+                let changed = fetch_timesheets(client, query).await?;
+                self.time_sheets = merge_by_id(std::mem::take(&mut self.time_sheets), changed, |timesheet| timesheet.id);
+            }
+            None => {
+                self.time_sheets = fetch_timesheets(client, query).await?;
+                self.touch(TIMESHEETS_FULL, now);
+            }
+        }
+
+        self.time_sheets.retain(|timesheet| {
+            parse_kimai_datetime(&timesheet.begin).is_none_or(|begin| begin >= window_start)
+        });
+        self.time_sheets.sort_by(|a, b| b.begin.cmp(&a.begin));
+        self.touch(TIMESHEETS, now);
+        Ok(())
+    }
+}
+
+/// The query for timesheets begun since `since` (all of them for `None`) that
+/// fall outside the window the cache holds at `now`, oldest first so a walk
+/// can be resumed where it stopped; `None` when the window covers them.
+pub fn timesheets_before_window(since: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Option<KimaiTimeSheetQuery> {
+    let window_start = now - Duration::days(TIMESHEET_WINDOW_DAYS);
+    if since.is_some_and(|since| since >= window_start) {
+        return None;
+    }
+    Some(KimaiTimeSheetQuery {
+        begin: since.map(kimai_datetime),
+        end: Some(kimai_datetime(window_start)),
+        order_by: Some("begin".into()),
+        order: Some("ASC".into()),
+        ..Default::default()
+    })
+}
+
+async fn fetch_timesheets(client: &KimaiClient, query: KimaiTimeSheetQuery) -> KimaiResult<Vec<KimaiTimeSheet>> {
+    let result = pagination::walk_timesheets(client, "cache", query, &CancellationToken::new(), |_| {}).await?;
+    Ok(result.timesheets)
+}
+
+/// Ids among `ids` that `known` lacks, each once
+fn missing<T: KimaiEntity>(known: &[T], ids: impl Iterator<Item = u64>) -> BTreeSet<u64> {
+    let known: HashSet<u64> = known.iter().map(KimaiEntity::id).collect();
+    ids.filter(|id| !known.contains(id)).collect()
+}
+
+/// Fetches the `ids` into `list` one by one, or reloads it through `all`
+/// when there are more than MAX_FILL_INS; returns whether it reloaded.
+/// Entities the user may not see are left out.
+async fn fill_in<T, One, All>(
+    list: &mut Vec<T>,
+    ids: BTreeSet<u64>,
+    one: impl Fn(u64) -> One,
+    all: impl FnOnce() -> All,
+) -> KimaiResult<bool>
+where
+    One: Future<Output = KimaiResult<T>>,
+    All: Future<Output = KimaiResult<Vec<T>>>,
+{
+    if ids.len() > MAX_FILL_INS {
+        *list = all().await?;
+        return Ok(true);
+    }
+    for id in ids {
+        match one(id).await {
+            Ok(entity) => list.push(entity),
+            Err(err) if retry::is_retryable(&err) => return Err(err),
+            Err(_) => {}
+        }
+    }
+    Ok(false)
+}
+
+/// Replaces entries of `existing` with the `changed` ones sharing their id
+fn merge_by_id<T>(existing: Vec<T>, changed: Vec<T>, id: impl Fn(&T) -> u64) -> Vec<T> {
+    let mut merged: HashMap<u64, T> = existing.into_iter().map(|item| (id(&item), item)).collect();
+    merged.extend(changed.into_iter().map(|item| (id(&item), item)));
+    merged.into_values().collect()
+}
+
+/// Kimai expects local date-times without an offset, in query parameters
+/// and forms alike
+pub fn kimai_datetime(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Parses Kimai's `2019-08-28T13:20:00+0200` timestamps
+pub fn parse_kimai_datetime(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%z")
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_replaces_changed_entries_and_keeps_the_rest() {
+        let mut merged = merge_by_id(vec![(1, "old"), (2, "kept")], vec![(1, "new"), (3, "added")], |item| item.0);
+        merged.sort();
+        assert_eq!(merged, vec![(1, "new"), (2, "kept"), (3, "added")]);
+    }
+
+    #[test]
+    fn only_history_older_than_the_window_is_walked_oldest_first() {
+        let now = Utc::now();
+        assert!(timesheets_before_window(Some(now - Duration::days(2)), now).is_none());
+
+        let query = timesheets_before_window(None, now).unwrap();
+        assert_eq!(query.begin, None);
+        assert_eq!(query.end, Some(kimai_datetime(now - Duration::days(TIMESHEET_WINDOW_DAYS))));
+        assert_eq!((query.order_by.as_deref(), query.order.as_deref()), (Some("begin"), Some("ASC")));
+
+        let since = now - Duration::days(90);
+        assert_eq!(timesheets_before_window(Some(since), now).unwrap().begin, Some(kimai_datetime(since)));
+    }
+
+    #[tokio::test]
+    async fn fill_ins_fetch_by_id_until_too_many_are_missing() {
+        let mut list = vec![1];
+        let reloaded = fill_in(
+            &mut list,
+            BTreeSet::from([2, 3]),
+            |id| async move { if id == 3 { Err(KimaiError::NotFound("hidden".into())) } else { Ok(id) } },
+            || async { Ok(vec![]) },
+        )
+        .await
+        .unwrap();
+        assert!(!reloaded);
+        assert_eq!(list, vec![1, 2]);
+
+        let offline = fill_in(&mut list, BTreeSet::from([4]), |_| async { Err(KimaiError::Timeout) }, || async { Ok(vec![]) });
+        assert!(offline.await.is_err());
+
+        let ids: BTreeSet<u64> = (10..=10 + MAX_FILL_INS as u64).collect();
+        let reloaded = fill_in(&mut list, ids, |_| async { unreachable!() }, || async { Ok(vec![1, 2, 3]) }).await.unwrap();
+        assert!(reloaded);
+        assert_eq!(list, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn parses_kimai_offsets_without_colon() {
+        let time = parse_kimai_datetime("2019-08-28T13:20:00+0200").unwrap();
+        assert_eq!(time.to_rfc3339(), "2019-08-28T11:20:00+00:00");
+    }
+
+    #[test]
+    fn outdated_cache_versions_are_discarded() {
+        let dir = std::env::temp_dir().join(format!("tikker-cache-test-{}", std::process::id()));
+        let path = KimaiCache::path(&dir, "profile").unwrap();
+        assert!(KimaiCache::path(&dir, "../profile").is_err());
+
+        let mut cache = KimaiCache::default();
+        cache.touch(CUSTOMERS, Utc::now());
+        cache.save(&path).unwrap();
+        assert!(KimaiCache::load(&path).is_some_and(|loaded| !loaded.is_stale(CUSTOMERS, ENTITY_FULL_MAX_AGE, Utc::now())));
+
+        cache.version = "0".into();
+        cache.save(&path).unwrap();
+        assert!(KimaiCache::load(&path).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
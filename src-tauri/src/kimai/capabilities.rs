@@ -0,0 +1,155 @@
+// Server capabilities
+// Parsed from /api/version plus probes for optional plugin endpoints
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::models::KimaiVersion;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct KimaiServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl KimaiServerVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// Parses `2.0.33` as well as Kimai 1.x semver strings like `1.30.11-prod`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let core = version.trim().split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|part| part.parse::<u32>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        Some(Self { major, minor, patch })
+    }
+
+    pub fn from_version(version: &KimaiVersion) -> Option<Self> {
+        version
+            .semantic_version
+            .as_deref()
+            .and_then(Self::parse)
+            .or_else(|| Self::parse(&version.version))
+    }
+}
+
+impl fmt::Display for KimaiServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KimaiFeature {
+    /// /api/tasks/*, provided by the TaskManagement plugin
+    Tasks,
+    /// Personal access tokens sent as `Authorization: Bearer`
+    BearerAuth,
+    /// `modified_after` filter on /api/timesheets
+    ModifiedAfter,
+    /// /api/metafields, provided by the custom-fields plugin
+    MetaFields,
+}
+
+impl KimaiFeature {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KimaiFeature::Tasks => "task management",
+            KimaiFeature::BearerAuth => "API token authentication",
+            KimaiFeature::ModifiedAfter => "incremental timesheet sync",
+            KimaiFeature::MetaFields => "custom fields",
+        }
+    }
+
+    /// Minimum server version for features that ship with Kimai itself
+    fn min_version(&self) -> Option<KimaiServerVersion> {
+        match self {
+            KimaiFeature::Tasks | KimaiFeature::MetaFields => None,
+            KimaiFeature::BearerAuth => Some(KimaiServerVersion::new(2, 14, 0)),
+            KimaiFeature::ModifiedAfter => Some(KimaiServerVersion::new(1, 15, 0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiCapabilities {
+    pub version: Option<KimaiServerVersion>,
+    pub features: BTreeSet<KimaiFeature>,
+}
+
+impl KimaiCapabilities {
+    /// Capabilities implied by the server version alone; plugin features are
+    /// added by the caller after probing their endpoints.
+    pub fn from_version(version: &KimaiVersion) -> Self {
+        let version = KimaiServerVersion::from_version(version);
+        let features = [KimaiFeature::BearerAuth, KimaiFeature::ModifiedAfter]
+            .into_iter()
+            .filter(|feature| match (feature.min_version(), version) {
+                (Some(min), Some(version)) => version >= min,
+                _ => false,
+            })
+            .collect();
+
+        Self { version, features }
+    }
+
+    pub fn supports(&self, feature: KimaiFeature) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(json: &str) -> KimaiVersion {
+        serde_json::from_str(json).expect("fixture should deserialize")
+    }
+
+    #[test]
+    fn versions_parse_with_suffixes_and_missing_parts() {
+        assert_eq!(KimaiServerVersion::parse("2.0.33"), Some(KimaiServerVersion::new(2, 0, 33)));
+        assert_eq!(KimaiServerVersion::parse(" 1.30.11-prod"), Some(KimaiServerVersion::new(1, 30, 11)));
+        assert_eq!(KimaiServerVersion::parse("2.14+build.5"), Some(KimaiServerVersion::new(2, 14, 0)));
+        assert_eq!(KimaiServerVersion::parse("2"), Some(KimaiServerVersion::new(2, 0, 0)));
+        assert_eq!(KimaiServerVersion::parse("2.x.1"), None);
+        assert_eq!(KimaiServerVersion::parse(""), None);
+    }
+
+    #[test]
+    fn kimai1_prefers_the_semantic_version() {
+        let version = fixture(include_str!("../../tests/fixtures/kimai/v1/version.json"));
+        let capabilities = KimaiCapabilities::from_version(&version);
+        assert_eq!(capabilities.version, Some(KimaiServerVersion::new(1, 30, 11)));
+        assert!(capabilities.supports(KimaiFeature::ModifiedAfter));
+        assert!(!capabilities.supports(KimaiFeature::BearerAuth));
+        assert!(!capabilities.supports(KimaiFeature::Tasks));
+    }
+
+    #[test]
+    fn features_follow_their_minimum_version() {
+        let mut version = fixture(include_str!("../../tests/fixtures/kimai/v2/version.json"));
+        let capabilities = KimaiCapabilities::from_version(&version);
+        assert_eq!(capabilities.version, Some(KimaiServerVersion::new(2, 0, 33)));
+        assert!(capabilities.supports(KimaiFeature::ModifiedAfter));
+        assert!(!capabilities.supports(KimaiFeature::BearerAuth));
+
+        version.version = "2.14.0".into();
+        assert!(KimaiCapabilities::from_version(&version).supports(KimaiFeature::BearerAuth));
+
+        version.version = "1.14.3".into();
+        assert!(KimaiCapabilities::from_version(&version).features.is_empty());
+
+        version.version = "unknown".into();
+        let capabilities = KimaiCapabilities::from_version(&version);
+        assert_eq!((capabilities.version, capabilities.features.len()), (None, 0));
+    }
+}
@@ -0,0 +1,683 @@
+// Kimai API Client
+// Handles all API operations to Kimai server instances
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use reqwest::{Method, Request, RequestBuilder, Response};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::capabilities::{KimaiCapabilities, KimaiFeature};
+use super::error::{KimaiError, KimaiResult};
+use super::metafields::{self, MetaFieldDefinition, MetaFieldEntity, MetaFieldValues};
+use super::models::*;
+use super::normalize::{EntityIndex, Normalize};
+use super::pagination::KimaiPage;
+use super::policy::TimesheetConfig;
+use super::retry::{self, AttemptListener, RequestAttempt, RetryPolicy};
+use super::tls::{self, CertificateCapture, PeerCertificate};
+
+pub struct KimaiClient {
+    profile_id: String,
+    http: reqwest::Client,
+    base_url: String,
+    auth: KimaiAuthConfig,
+    scheme: KimaiAuthScheme,
+    certificates: Arc<CertificateCapture>,
+    capabilities: RwLock<Option<KimaiCapabilities>>,
+    index: RwLock<EntityIndex>,
+    meta_fields: RwLock<Option<Vec<MetaFieldDefinition>>>,
+    timesheet_config: RwLock<Option<TimesheetConfig>>,
+    calendar_config: RwLock<Option<KimaiCalendarConfig>>,
+    retry: RetryPolicy,
+    on_attempt: Option<AttemptListener>,
+}
+
+impl KimaiClient {
+    pub fn new(profile: &KimaiProfile) -> KimaiResult<Self> {
+        let auth = profile.auth.clone();
+        validate_auth_config(&auth)?;
+
+        let certificates = Arc::new(CertificateCapture::default());
+        let tls_config = tls::build_tls_config(
+            &profile.ssl,
+            profile.client_certificate.as_ref(),
+            certificates.clone(),
+        )?;
+
+        let http = reqwest::Client::builder()
+            .user_agent(concat!("tikker/", env!("CARGO_PKG_VERSION")))
+            .use_preconfigured_tls(tls_config)
+            .connect_timeout(Duration::from_secs(profile.requests.connect_timeout_secs))
+            .timeout(Duration::from_secs(profile.requests.timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            profile_id: profile.id.clone(),
+            http,
+            base_url: auth.base_url.trim_end_matches('/').to_string(),
+            scheme: auth.scheme.unwrap_or(match auth.kind {
+                KimaiAuthType::ApiToken => KimaiAuthScheme::Bearer,
+                KimaiAuthType::Legacy => KimaiAuthScheme::XAuth,
+            }),
+            auth,
+            certificates,
+            capabilities: RwLock::new(None),
+            index: RwLock::default(),
+            meta_fields: RwLock::new(None),
+            timesheet_config: RwLock::new(None),
+            calendar_config: RwLock::new(None),
+            retry: RetryPolicy::new(&profile.requests),
+            on_attempt: None,
+        })
+    }
+
+    /// Reports every request attempt, including retries, to `listener`.
+    pub fn on_attempt(mut self, listener: impl Fn(&RequestAttempt) + Send + Sync + 'static) -> Self {
+        self.on_attempt = Some(Arc::new(listener));
+        self
+    }
+
+    pub fn auth_scheme(&self) -> KimaiAuthScheme {
+        self.scheme
+    }
+
+    /// Finds the authentication scheme the server accepts, starting with the
+    /// stored or preferred one, and returns the authenticated user.
+    pub async fn detect_auth_scheme(&mut self) -> KimaiResult<KimaiUser> {
+        let fallback = match self.scheme {
+            KimaiAuthScheme::Bearer => KimaiAuthScheme::XAuth,
+            KimaiAuthScheme::XAuth => KimaiAuthScheme::Bearer,
+        };
+        // X-AUTH headers need a username, so don't fall back to them without one
+        let can_fall_back = fallback == KimaiAuthScheme::Bearer || !self.username().is_empty();
+
+        match self.get_current_user().await {
+            Err(KimaiError::Unauthorized(_) | KimaiError::Forbidden(_)) if can_fall_back => {
+                let preferred = self.scheme;
+                self.scheme = fallback;
+                let result = self.get_current_user().await;
+                if result.is_err() {
+                    self.scheme = preferred;
+                }
+                result
+            }
+            result => result,
+        }
+    }
+
+    // Version and Configuration
+    pub async fn get_version(&self) -> KimaiResult<KimaiVersion> {
+        self.get_raw("/api/version", &[]).await
+    }
+
+    /// Works out what the server supports from its version and by probing
+    /// optional plugin endpoints. The result gates the matching client calls.
+    pub async fn probe_capabilities(&self, version: &KimaiVersion) -> KimaiResult<KimaiCapabilities> {
+        let mut capabilities = KimaiCapabilities::from_version(version);
+
+        let probes = [
+            (KimaiFeature::Tasks, "/api/tasks"),
+            (KimaiFeature::MetaFields, "/api/metafields"),
+        ];
+        for (feature, endpoint) in probes {
+            if self.probe(endpoint).await? {
+                capabilities.features.insert(feature);
+            }
+        }
+
+        *self.capabilities.write().unwrap() = Some(capabilities.clone());
+        Ok(capabilities)
+    }
+
+    async fn probe(&self, endpoint: &str) -> KimaiResult<bool> {
+        let query = [("page", "1".to_string()), ("size", "1".to_string())];
+        match self.get_raw::<serde_json::Value>(endpoint, &query).await {
+            Ok(_) => Ok(true),
+            // Missing plugin, or the user may not use it
+            Err(KimaiError::NotFound(_) | KimaiError::Forbidden(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn capabilities(&self) -> Option<KimaiCapabilities> {
+        self.capabilities.read().unwrap().clone()
+    }
+
+    /// Fails fast for features the server is known not to support.
+    /// Before the first probe everything is allowed.
+    pub fn require(&self, feature: KimaiFeature) -> KimaiResult<()> {
+        match &*self.capabilities.read().unwrap() {
+            Some(capabilities) if !capabilities.supports(feature) => Err(KimaiError::Unsupported(feature)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fetches the certificate the server presents, without sending credentials,
+    /// so the user can decide whether to trust it.
+    pub async fn inspect_certificate(&self) -> KimaiResult<PeerCertificate> {
+        let result = self.http.get(format!("{}/api/version", self.base_url)).send().await;
+        match (self.certificates.last(), result) {
+            (Some(certificate), _) => Ok(certificate),
+            (None, Err(err)) => Err(err.into()),
+            (None, Ok(_)) => Err(KimaiError::Certificate("server did not present a certificate".into())),
+        }
+    }
+
+    // Authentication
+    pub async fn get_current_user(&self) -> KimaiResult<KimaiUser> {
+        self.get("/api/users/me", &[]).await
+    }
+
+    // Meta Fields
+    /// Custom field definitions for all entities, fetched once per connection.
+    /// Servers without the custom-fields plugin have none.
+    pub async fn get_meta_field_definitions(&self) -> KimaiResult<Vec<MetaFieldDefinition>> {
+        if let Some(definitions) = self.meta_fields.read().unwrap().clone() {
+            return Ok(definitions);
+        }
+
+        let definitions = match self.require(KimaiFeature::MetaFields) {
+            Err(_) => Vec::new(),
+            Ok(()) => match self.get_raw("/api/metafields", &[]).await {
+                Ok(definitions) => definitions,
+                Err(KimaiError::NotFound(_) | KimaiError::Forbidden(_)) => Vec::new(),
+                Err(err) => return Err(err),
+            },
+        };
+        *self.meta_fields.write().unwrap() = Some(definitions.clone());
+        Ok(definitions)
+    }
+
+    /// The server's timesheet rules, fetched once per connection. Servers
+    /// that don't expose them get Kimai's permissive defaults.
+    pub async fn get_timesheet_config(&self) -> KimaiResult<TimesheetConfig> {
+        if let Some(config) = self.timesheet_config.read().unwrap().clone() {
+            return Ok(config);
+        }
+
+        let config = match self.get_raw("/api/config/timesheet", &[]).await {
+            Ok(config) => config,
+            Err(KimaiError::NotFound(_) | KimaiError::Forbidden(_)) => TimesheetConfig::default(),
+            Err(err) => return Err(err),
+        };
+        *self.timesheet_config.write().unwrap() = Some(config.clone());
+        Ok(config)
+    }
+
+    /// Business hours and day limit, fetched once per connection. Servers
+    /// that don't expose them get an empty config.
+    pub async fn get_calendar_config(&self) -> KimaiResult<KimaiCalendarConfig> {
+        if let Some(config) = self.calendar_config() {
+            return Ok(config);
+        }
+
+        let config = match self.get_raw("/api/config/calendar", &[]).await {
+            Ok(config) => config,
+            Err(KimaiError::NotFound(_) | KimaiError::Forbidden(_)) => KimaiCalendarConfig::default(),
+            Err(err) => return Err(err),
+        };
+        *self.calendar_config.write().unwrap() = Some(config.clone());
+        Ok(config)
+    }
+
+    /// The calendar config if already fetched, for callers that can't wait
+    pub fn calendar_config(&self) -> Option<KimaiCalendarConfig> {
+        self.calendar_config.read().unwrap().clone()
+    }
+
+    /// Validates meta field values without saving anything.
+    pub async fn validate_meta_fields(
+        &self,
+        entity: MetaFieldEntity,
+        values: Option<&MetaFieldValues>,
+        creating: bool,
+    ) -> KimaiResult<()> {
+        let definitions = self.get_meta_field_definitions().await?;
+        // Without definitions the server is the only judge
+        if definitions.is_empty() {
+            return Ok(());
+        }
+
+        let field_errors = metafields::validate(&definitions, entity, values.unwrap_or(&MetaFieldValues::new()), creating);
+        if field_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(KimaiError::Validation {
+                message: "Some custom fields are missing or invalid".into(),
+                field_errors,
+            })
+        }
+    }
+
+    // Customer Management
+    pub async fn get_customers(&self) -> KimaiResult<Vec<KimaiCustomer>> {
+        self.get("/api/customers", &[]).await
+    }
+
+    pub async fn get_customer(&self, id: u64) -> KimaiResult<KimaiCustomer> {
+        self.get(&format!("/api/customers/{id}"), &[]).await
+    }
+
+    pub async fn create_customer(&self, form: &KimaiCustomerForm) -> KimaiResult<KimaiCustomer> {
+        self.save_with_meta(MetaFieldEntity::Customer, Method::POST, "/api/customers", form, form.meta_fields.as_ref())
+            .await
+    }
+
+    pub async fn update_customer(&self, id: u64, form: &KimaiCustomerForm) -> KimaiResult<KimaiCustomer> {
+        self.save_with_meta(
+            MetaFieldEntity::Customer,
+            Method::PATCH,
+            &format!("/api/customers/{id}"),
+            form,
+            form.meta_fields.as_ref(),
+        )
+        .await
+    }
+
+    pub async fn delete_customer(&self, id: u64) -> KimaiResult<()> {
+        self.delete(&format!("/api/customers/{id}")).await
+    }
+
+    // Project Management
+    pub async fn get_projects(&self, customer: Option<u64>) -> KimaiResult<Vec<KimaiProject>> {
+        let query: Vec<_> = customer.map(|id| ("customer", id.to_string())).into_iter().collect();
+        self.get("/api/projects", &query).await
+    }
+
+    pub async fn get_project(&self, id: u64) -> KimaiResult<KimaiProject> {
+        self.get(&format!("/api/projects/{id}"), &[]).await
+    }
+
+    pub async fn create_project(&self, form: &KimaiProjectForm) -> KimaiResult<KimaiProject> {
+        self.save_with_meta(MetaFieldEntity::Project, Method::POST, "/api/projects", form, form.meta_fields.as_ref())
+            .await
+    }
+
+    pub async fn update_project(&self, id: u64, form: &KimaiProjectForm) -> KimaiResult<KimaiProject> {
+        self.save_with_meta(
+            MetaFieldEntity::Project,
+            Method::PATCH,
+            &format!("/api/projects/{id}"),
+            form,
+            form.meta_fields.as_ref(),
+        )
+        .await
+    }
+
+    pub async fn delete_project(&self, id: u64) -> KimaiResult<()> {
+        self.delete(&format!("/api/projects/{id}")).await
+    }
+
+    // Activity Management
+    pub async fn get_activities(&self, project: Option<u64>) -> KimaiResult<Vec<KimaiActivity>> {
+        let query: Vec<_> = project.map(|id| ("project", id.to_string())).into_iter().collect();
+        self.get("/api/activities", &query).await
+    }
+
+    pub async fn get_activity(&self, id: u64) -> KimaiResult<KimaiActivity> {
+        self.get(&format!("/api/activities/{id}"), &[]).await
+    }
+
+    pub async fn create_activity(&self, form: &KimaiActivityForm) -> KimaiResult<KimaiActivity> {
+        self.save_with_meta(MetaFieldEntity::Activity, Method::POST, "/api/activities", form, form.meta_fields.as_ref())
+            .await
+    }
+
+    pub async fn update_activity(&self, id: u64, form: &KimaiActivityForm) -> KimaiResult<KimaiActivity> {
+        self.save_with_meta(
+            MetaFieldEntity::Activity,
+            Method::PATCH,
+            &format!("/api/activities/{id}"),
+            form,
+            form.meta_fields.as_ref(),
+        )
+        .await
+    }
+
+    pub async fn delete_activity(&self, id: u64) -> KimaiResult<()> {
+        self.delete(&format!("/api/activities/{id}")).await
+    }
+
+    // Time Sheet Management
+    pub async fn get_timesheets(&self, query: &KimaiTimeSheetQuery) -> KimaiResult<Vec<KimaiTimeSheet>> {
+        self.get("/api/timesheets", &query.to_query()).await
+    }
+
+    pub async fn get_timesheets_page(&self, query: &KimaiTimeSheetQuery) -> KimaiResult<KimaiPage<KimaiTimeSheet>> {
+        let response = self
+            .send(self.request(Method::GET, "/api/timesheets").query(&query.to_query()))
+            .await?;
+        let headers = response.headers().clone();
+        let items = self.normalize(response.json().await?);
+        Ok(KimaiPage::from_headers(items, &headers, query.page.unwrap_or(1)))
+    }
+
+    pub async fn get_active_timesheets(&self) -> KimaiResult<Vec<KimaiTimeSheet>> {
+        self.get("/api/timesheets/active", &[]).await
+    }
+
+    pub async fn get_timesheet(&self, id: u64) -> KimaiResult<KimaiTimeSheet> {
+        self.get(&format!("/api/timesheets/{id}"), &[]).await
+    }
+
+    pub async fn create_timesheet(&self, form: &KimaiTimeSheetForm) -> KimaiResult<KimaiTimeSheet> {
+        self.save_with_meta(MetaFieldEntity::Timesheet, Method::POST, "/api/timesheets", form, form.meta_fields.as_ref())
+            .await
+    }
+
+    pub async fn update_timesheet(&self, id: u64, form: &KimaiTimeSheetForm) -> KimaiResult<KimaiTimeSheet> {
+        self.save_with_meta(
+            MetaFieldEntity::Timesheet,
+            Method::PATCH,
+            &format!("/api/timesheets/{id}"),
+            form,
+            form.meta_fields.as_ref(),
+        )
+        .await
+    }
+
+    /// Clears the end of a stopped timesheet, so it runs again. The form
+    /// can't express this, as it leaves out empty fields.
+    pub async fn reopen_timesheet(&self, id: u64) -> KimaiResult<KimaiTimeSheet> {
+        self.send_json(Method::PATCH, &format!("/api/timesheets/{id}"), &serde_json::json!({ "end": null }))
+            .await
+    }
+
+    pub async fn delete_timesheet(&self, id: u64) -> KimaiResult<()> {
+        self.delete(&format!("/api/timesheets/{id}")).await
+    }
+
+    // Task Management
+    pub async fn get_tasks(&self, query: &KimaiTaskQuery) -> KimaiResult<Vec<KimaiTask>> {
+        self.require(KimaiFeature::Tasks)?;
+        self.get("/api/tasks", &query.to_query()).await
+    }
+
+    pub async fn get_task(&self, id: u64) -> KimaiResult<KimaiTask> {
+        self.require(KimaiFeature::Tasks)?;
+        self.get(&format!("/api/tasks/{id}"), &[]).await
+    }
+
+    pub async fn create_task(&self, form: &KimaiTaskForm) -> KimaiResult<KimaiTask> {
+        self.require(KimaiFeature::Tasks)?;
+        self.send_json(Method::POST, "/api/tasks", form).await
+    }
+
+    pub async fn update_task(&self, id: u64, form: &KimaiTaskForm) -> KimaiResult<KimaiTask> {
+        self.require(KimaiFeature::Tasks)?;
+        self.send_json(Method::PATCH, &format!("/api/tasks/{id}"), form).await
+    }
+
+    pub async fn delete_task(&self, id: u64) -> KimaiResult<()> {
+        self.require(KimaiFeature::Tasks)?;
+        self.delete(&format!("/api/tasks/{id}")).await
+    }
+
+    pub async fn task_action(&self, id: u64, action: KimaiTaskAction) -> KimaiResult<KimaiTask> {
+        self.require(KimaiFeature::Tasks)?;
+        let endpoint = format!("/api/tasks/{id}/{}", action.path());
+        let response = self.send(self.request(Method::PATCH, &endpoint)).await?;
+        Ok(self.normalize(response.json().await?))
+    }
+
+    // Core HTTP Request Methods
+    async fn get<T: DeserializeOwned + Normalize>(&self, endpoint: &str, query: &[(&str, String)]) -> KimaiResult<T> {
+        self.get_raw(endpoint, query).await.map(|value| self.normalize(value))
+    }
+
+    /// Like `get`, without resolving entity references
+    async fn get_raw<T: DeserializeOwned>(&self, endpoint: &str, query: &[(&str, String)]) -> KimaiResult<T> {
+        let response = self.send(self.request(Method::GET, endpoint).query(query)).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn send_json<B: Serialize, T: DeserializeOwned + Normalize>(
+        &self,
+        method: Method,
+        endpoint: &str,
+        body: &B,
+    ) -> KimaiResult<T> {
+        let response = self.send(self.request(method, endpoint).json(body)).await?;
+        Ok(self.normalize(response.json().await?))
+    }
+
+    /// Creates (POST) or updates (PATCH) an entity, then stores its meta
+    /// fields one by one, which is the only way the API accepts them. A meta
+    /// field that fails after the entity was saved yields `PartialSave`,
+    /// carrying the saved entity so it isn't created twice.
+    async fn save_with_meta<B: Serialize, T: DeserializeOwned + Serialize + Normalize + KimaiEntity>(
+        &self,
+        entity: MetaFieldEntity,
+        method: Method,
+        endpoint: &str,
+        form: &B,
+        meta_fields: Option<&MetaFieldValues>,
+    ) -> KimaiResult<T> {
+        self.validate_meta_fields(entity, meta_fields, method == Method::POST).await?;
+
+        let mut saved: T = self.send_json(method, endpoint, form).await?;
+        for (name, value) in meta_fields.into_iter().flatten() {
+            let body = serde_json::json!({ "name": name, "value": metafields::to_meta_value(value) });
+            let endpoint = format!("{}/{}/meta", entity.endpoint(), saved.id());
+            saved = match self.send_json(Method::PATCH, &endpoint, &body).await {
+                Ok(updated) => updated,
+                Err(error) => {
+                    return Err(KimaiError::PartialSave {
+                        saved: Box::new(serde_json::to_value(&saved).unwrap_or_default()),
+                        field: name.clone(),
+                        error: Box::new(error),
+                    })
+                }
+            };
+        }
+        Ok(saved)
+    }
+
+    async fn delete(&self, endpoint: &str) -> KimaiResult<()> {
+        self.send(self.request(Method::DELETE, endpoint)).await?;
+        Ok(())
+    }
+
+    /// Resolves ids and names against the entities this client has seen so far
+    pub(crate) fn normalize<T: Normalize>(&self, mut value: T) -> T {
+        value.normalize(&mut self.index.write().unwrap());
+        value
+    }
+
+    fn request(&self, method: Method, endpoint: &str) -> RequestBuilder {
+        let builder = self
+            .http
+            .request(method, format!("{}{}", self.base_url, endpoint))
+            .header(reqwest::header::ACCEPT, "application/json");
+
+        match self.scheme {
+            KimaiAuthScheme::Bearer => builder.bearer_auth(self.token()),
+            KimaiAuthScheme::XAuth => builder
+                .header("X-AUTH-USER", self.username())
+                .header("X-AUTH-TOKEN", self.token()),
+        }
+    }
+
+    fn username(&self) -> &str {
+        self.auth.username.as_deref().unwrap_or_default()
+    }
+
+    /// API token for token profiles, the API password for legacy ones
+    fn token(&self) -> &str {
+        self.auth.credential().unwrap_or_default()
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> KimaiResult<Response> {
+        let mut request = builder.build()?;
+        // Only reads are retried: a repeated POST could book the same time twice
+        let max_retries = if request.method() == Method::GET {
+            self.retry.max_retries
+        } else {
+            0
+        };
+
+        let mut attempt = 1;
+        loop {
+            // Bodies that can't be cloned (streams) are never retried
+            let retry = (attempt <= max_retries).then(|| request.try_clone()).flatten();
+            let (method, path) = (request.method().to_string(), request.url().path().to_string());
+            let result = self.execute(request).await;
+
+            let retry = match (&result, retry) {
+                (Err(err), Some(next)) if retry::is_retryable(err) => Some((next, self.retry.delay(attempt))),
+                _ => None,
+            };
+            self.report(RequestAttempt {
+                profile_id: self.profile_id.clone(),
+                method,
+                path,
+                attempt,
+                max_attempts: max_retries + 1,
+                succeeded: result.is_ok(),
+                error_kind: result.as_ref().err().map(KimaiError::kind),
+                error: result.as_ref().err().map(ToString::to_string),
+                retry_in_ms: retry.as_ref().map(|(_, delay)| delay.as_millis() as u64),
+            });
+
+            let Some((next, delay)) = retry else {
+                return result;
+            };
+            tokio::time::sleep(delay).await;
+            request = next;
+            attempt += 1;
+        }
+    }
+
+    async fn execute(&self, request: Request) -> KimaiResult<Response> {
+        let response = self.http.execute(request).await.map_err(|err| match self.certificates.take_rejected() {
+            Some(certificate) => KimaiError::Tls {
+                message: certificate.error.unwrap_or_else(|| err.to_string()),
+                fingerprint: certificate.fingerprint,
+            },
+            None => err.into(),
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(KimaiError::from_response(status, &body))
+    }
+
+    fn report(&self, attempt: RequestAttempt) {
+        if let Some(listener) = &self.on_attempt {
+            listener(&attempt);
+        }
+    }
+}
+
+// Utility functions
+pub fn validate_auth_config(config: &KimaiAuthConfig) -> KimaiResult<()> {
+    if config.base_url.trim().is_empty() {
+        return Err(KimaiError::InvalidConfig("base URL is required".into()));
+    }
+
+    match config.kind {
+        KimaiAuthType::ApiToken if config.api_token.as_deref().unwrap_or_default().is_empty() => {
+            Err(KimaiError::InvalidConfig("API token is required".into()))
+        }
+        KimaiAuthType::Legacy
+            if config.username.as_deref().unwrap_or_default().is_empty()
+                || config.password.as_deref().unwrap_or_default().is_empty() =>
+        {
+            Err(KimaiError::InvalidConfig("username and password are required".into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::kimai::test_server::{self, Response};
+
+    /// Serves `/api/users/me` to X-AUTH requests only, refusing all others
+    /// with `refusal`
+    fn serve_x_auth_only(refusal: &'static str) -> String {
+        test_server::serve(move |request| match request.header("X-AUTH-TOKEN") {
+            Some(_) => Response::json("200 OK", include_str!("../../tests/fixtures/kimai/v2/user_me.json")),
+            None => Response::json(refusal, r#"{"code": 401, "message": "Invalid credentials"}"#),
+        })
+    }
+
+    fn token_profile(base_url: &str, username: Option<&str>) -> KimaiProfile {
+        let mut profile = test_server::profile(base_url);
+        profile.auth.username = username.map(str::to_string);
+        profile
+    }
+
+    #[test]
+    fn zero_timeouts_are_raised_to_a_second() {
+        let mut value = serde_json::to_value(test_server::profile("https://kimai.test")).unwrap();
+        value["requests"] = json!({"connectTimeoutSecs": 0, "timeoutSecs": 0, "maxRetries": 0});
+        let profile: KimaiProfile = serde_json::from_value(value).unwrap();
+
+        assert_eq!(profile.requests.connect_timeout_secs, 1);
+        assert_eq!(profile.requests.timeout_secs, 1);
+        assert_eq!(profile.requests.max_retries, 0);
+        assert!(KimaiClient::new(&profile).is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_refused_bearer_token_falls_back_to_x_auth() {
+        for refusal in ["401 Unauthorized", "403 Forbidden"] {
+            let profile = token_profile(&serve_x_auth_only(refusal), Some("anna"));
+            let mut client = KimaiClient::new(&profile).unwrap();
+            assert_eq!(client.auth_scheme(), KimaiAuthScheme::Bearer);
+
+            let user = client.detect_auth_scheme().await.unwrap();
+            assert_eq!(user.username, "anna");
+            assert_eq!(client.auth_scheme(), KimaiAuthScheme::XAuth);
+        }
+    }
+
+    #[tokio::test]
+    async fn there_is_no_x_auth_fallback_without_a_username() {
+        let profile = token_profile(&serve_x_auth_only("401 Unauthorized"), None);
+        let mut client = KimaiClient::new(&profile).unwrap();
+
+        let result = client.detect_auth_scheme().await;
+        assert!(matches!(result, Err(KimaiError::Unauthorized(_))));
+        assert_eq!(client.auth_scheme(), KimaiAuthScheme::Bearer);
+    }
+
+    #[tokio::test]
+    async fn a_failed_meta_field_still_returns_the_saved_timesheet() {
+        let url = test_server::serve(|request| match (request.method.as_str(), request.target.as_str()) {
+            ("GET", "/api/metafields") => Response::json("200 OK", "[]"),
+            ("POST", "/api/timesheets") => Response::json(
+                "200 OK",
+                r#"{"id": 42, "begin": "2024-05-06T09:00:00+0200", "user": 1, "activity": 3, "project": 2}"#,
+            ),
+            _ => Response::json(
+                "400 Bad Request",
+                r#"{"code": 400, "message": "Validation Failed", "errors": {"children": {"value": {"errors": ["Too long."]}}}}"#,
+            ),
+        });
+        let client = test_server::client(&url);
+        let form = KimaiTimeSheetForm {
+            begin: Some("2024-05-06T09:00:00".into()),
+            project: Some(2),
+            activity: Some(3),
+            meta_fields: Some([("ticket".to_string(), json!("TIK-1234567890"))].into()),
+            ..Default::default()
+        };
+
+        let err = client.create_timesheet(&form).await.unwrap_err();
+        assert_eq!(err.kind(), "partial_save");
+        assert_eq!(err.field_errors().unwrap()["value"], ["Too long."]);
+
+        let payload = serde_json::to_value(&err).unwrap();
+        assert_eq!(payload["saved"]["id"], 42);
+        assert!(payload["message"].as_str().unwrap().contains("ticket"));
+    }
+}
@@ -0,0 +1,638 @@
+// Kimai Tauri commands
+// Every command takes the id of the profile whose connection it uses
+
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+use super::cache::{parse_kimai_datetime, timesheets_before_window, KimaiCache, ACTIVITIES, CUSTOMERS, PROJECTS};
+use super::capabilities::KimaiCapabilities;
+use super::metafields::{MetaFieldDefinition, MetaFieldEntity, MetaFieldValues};
+use super::models::*;
+use super::pagination::{self, TimesheetWalkResult, TimesheetWalks, TIMESHEETS_PAGE_EVENT};
+use super::policy::{self, PolicyViolation, TimesheetConfig};
+use super::retry::REQUEST_ATTEMPT_EVENT;
+use super::switch::{self, QueuedStartResult, QueuedStarts, SwitchOutcome, TIMESHEET_SWITCH_EVENT};
+use super::tls::PeerCertificate;
+use super::{KimaiClient, KimaiError, KimaiResult, KimaiState};
+use crate::history::HistoryStore;
+use crate::secrets::SecretStore;
+use crate::settings::{SettingsState, SETTINGS_CHANGED_EVENT};
+
+// Connection Management
+#[tauri::command]
+pub async fn kimai_connect<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    secrets: State<'_, SecretStore>,
+    mut profile: KimaiProfile,
+) -> KimaiResult<KimaiConnectionState> {
+    secrets.resolve(&mut profile.auth)?;
+    let emitter = app.clone();
+    let mut client = KimaiClient::new(&profile)?.on_attempt(move |attempt| {
+        let _ = emitter.emit(REQUEST_ATTEMPT_EVENT, attempt);
+    });
+
+    // Check version and test authentication before keeping the client
+    let version = client.get_version().await?;
+    let user = client.detect_auth_scheme().await?;
+    let auth_scheme = client.auth_scheme();
+    if profile.auth.scheme != Some(auth_scheme) {
+        remember_auth_scheme(&app, &profile.id, auth_scheme);
+    }
+    let capabilities = client.probe_capabilities(&version).await?;
+    // Only the timer's automatic stop needs it, so it can't fail the connection
+    if let Err(err) = client.get_calendar_config().await {
+        eprintln!("Could not load the calendar config of profile {}: {err}", profile.id);
+    }
+
+    state.insert(profile.id, client);
+
+    Ok(KimaiConnectionState {
+        is_connected: true,
+        is_connecting: false,
+        last_connected: Some(chrono::Utc::now().to_rfc3339()),
+        error: None,
+        version: Some(version),
+        user: Some(user),
+        auth_scheme: Some(auth_scheme),
+        capabilities: Some(capabilities),
+    })
+}
+
+/// Saves the scheme the server accepted so later connections try it first.
+/// The connection works either way, so a failed save is only logged.
+fn remember_auth_scheme<R: Runtime>(app: &AppHandle<R>, profile_id: &str, scheme: KimaiAuthScheme) {
+    let result = app.state::<SettingsState>().update(|settings| {
+        if let Some(profile) = settings.profiles.iter_mut().find(|profile| profile.id == profile_id) {
+            profile.auth.scheme = Some(scheme);
+        }
+        Ok(())
+    });
+    match result {
+        Ok((settings, ())) => {
+            let _ = app.emit(SETTINGS_CHANGED_EVENT, &settings);
+        }
+        Err(err) => eprintln!("Could not save the auth scheme of profile {profile_id}: {err}"),
+    }
+}
+
+#[tauri::command]
+pub async fn kimai_inspect_certificate(
+    secrets: State<'_, SecretStore>,
+    mut profile: KimaiProfile,
+) -> KimaiResult<PeerCertificate> {
+    secrets.resolve(&mut profile.auth)?;
+    KimaiClient::new(&profile)?.inspect_certificate().await
+}
+
+#[tauri::command]
+pub fn kimai_disconnect(state: State<'_, KimaiState>, profile_id: String) {
+    state.remove(&profile_id);
+}
+
+#[tauri::command]
+pub async fn kimai_get_version(state: State<'_, KimaiState>, profile_id: String) -> KimaiResult<KimaiVersion> {
+    state.client(&profile_id)?.get_version().await
+}
+
+#[tauri::command]
+pub async fn kimai_get_capabilities(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+) -> KimaiResult<KimaiCapabilities> {
+    let client = state.client(&profile_id)?;
+    match client.capabilities() {
+        Some(capabilities) => Ok(capabilities),
+        None => client.probe_capabilities(&client.get_version().await?).await,
+    }
+}
+
+#[tauri::command]
+pub async fn kimai_get_current_user(state: State<'_, KimaiState>, profile_id: String) -> KimaiResult<KimaiUser> {
+    state.client(&profile_id)?.get_current_user().await
+}
+
+// Meta Fields
+/// Custom field definitions, optionally only those of one entity type,
+/// so the dialogs can render matching inputs.
+#[tauri::command]
+pub async fn kimai_list_meta_fields(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    entity: Option<MetaFieldEntity>,
+) -> KimaiResult<Vec<MetaFieldDefinition>> {
+    let mut definitions = state.client(&profile_id)?.get_meta_field_definitions().await?;
+    if let Some(entity) = entity {
+        definitions.retain(|definition| definition.entity == entity);
+    }
+    Ok(definitions)
+}
+
+/// Checks meta field values the same way saving does, for inline form errors.
+#[tauri::command]
+pub async fn kimai_validate_meta_fields(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    entity: MetaFieldEntity,
+    values: MetaFieldValues,
+    creating: bool,
+) -> KimaiResult<()> {
+    state
+        .client(&profile_id)?
+        .validate_meta_fields(entity, Some(&values), creating)
+        .await
+}
+
+// Entity Cache
+pub fn cache_path<R: Runtime>(app: &AppHandle<R>, profile_id: &str) -> KimaiResult<std::path::PathBuf> {
+    let dir = app.path().app_cache_dir().map_err(|err| KimaiError::Storage(err.to_string()))?;
+    KimaiCache::path(&dir, profile_id)
+}
+
+/// Has the next refresh reload `list`, which a command just changed
+fn expire_cached<R: Runtime>(app: &AppHandle<R>, profile_id: &str, list: &str) {
+    if let Err(err) = cache_path(app, profile_id).and_then(|path| KimaiCache::expire(&path, list)) {
+        eprintln!("Could not mark the cached {list} for a reload: {err}");
+    }
+}
+
+/// Returns the saved cache of a profile without touching the network.
+#[tauri::command]
+pub fn kimai_load_cache<R: Runtime>(app: AppHandle<R>, profile_id: String) -> KimaiResult<Option<KimaiCache>> {
+    Ok(KimaiCache::load(&cache_path(&app, &profile_id)?))
+}
+
+/// Sync metadata key holding when timesheets were last copied into the history
+const HISTORY_TIMESHEETS_SYNCED: &str = "timesheets.synced";
+/// Sync metadata key holding the begin of the last page an unfinished backfill stored
+const HISTORY_TIMESHEETS_BACKFILLED: &str = "timesheets.backfilled";
+
+/// Walk id of a profile's history backfill, for `kimai_cancel_timesheet_walk`
+pub fn history_walk_id(profile_id: &str) -> String {
+    format!("history-{profile_id}")
+}
+
+/// Refreshes the saved cache against the server, incrementally unless `force` is set.
+/// Fetched timesheets are kept in the history database too, which outlives
+/// the cache's 31-day window: the first sync, and one after a gap longer
+/// than the window, start a background backfill of what the window doesn't reach.
+#[tauri::command]
+pub async fn kimai_refresh_cache<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    history: State<'_, HistoryStore>,
+    profile_id: String,
+    force: Option<bool>,
+) -> KimaiResult<KimaiCache> {
+    let client = state.client(&profile_id)?;
+    let path = cache_path(&app, &profile_id)?;
+
+    let mut cache = KimaiCache::load(&path).unwrap_or_default();
+    cache.refresh(&client, force.unwrap_or(false)).await?;
+    cache.save(&path)?;
+
+    // The cache is saved by now, so history trouble is only logged
+    let now = chrono::Utc::now();
+    if let Err(err) = history.upsert_timesheets(&profile_id, &cache.time_sheets) {
+        eprintln!("Could not copy timesheets into the history: {err}");
+        return Ok(cache);
+    }
+    let synced = [HISTORY_TIMESHEETS_SYNCED, HISTORY_TIMESHEETS_BACKFILLED]
+        .into_iter()
+        .filter_map(|key| history.sync_value(&profile_id, key).ok().flatten())
+        .filter_map(|value| chrono::DateTime::parse_from_rfc3339(&value).ok())
+        .map(|synced| synced.with_timezone(&chrono::Utc))
+        .max();
+    match timesheets_before_window(synced, now) {
+        Some(query) => spawn_history_backfill(&app, client, profile_id, query, now),
+        None => {
+            if let Err(err) = history.set_sync_value(&profile_id, HISTORY_TIMESHEETS_SYNCED, &now.to_rfc3339()) {
+                eprintln!("Could not record the history sync: {err}");
+            }
+        }
+    }
+    Ok(cache)
+}
+
+/// Walks `query` into the history in the background, unless a backfill for
+/// the profile is already running. Each stored page moves the backfill
+/// marker on, so an interrupted backfill resumes there on the next refresh.
+fn spawn_history_backfill<R: Runtime>(
+    app: &AppHandle<R>,
+    client: std::sync::Arc<KimaiClient>,
+    profile_id: String,
+    query: KimaiTimeSheetQuery,
+    synced: chrono::DateTime<chrono::Utc>,
+) {
+    let walk_id = history_walk_id(&profile_id);
+    let Some(cancel) = app.state::<TimesheetWalks>().start_unless_running(&walk_id) else {
+        return;
+    };
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let history = app.state::<HistoryStore>();
+        let result = pagination::walk_timesheets(&client, &walk_id, query, &cancel, |page| {
+            let Some(last) = page.timesheets.last() else {
+                return;
+            };
+            let marker = parse_kimai_datetime(&last.begin).map(|begin| begin.to_rfc3339()).unwrap_or_default();
+            let stored = history
+                .upsert_timesheets(&profile_id, &page.timesheets)
+                .and_then(|_| history.set_sync_value(&profile_id, HISTORY_TIMESHEETS_BACKFILLED, &marker));
+            if let Err(err) = stored {
+                eprintln!("Could not copy older timesheets into the history: {err}");
+                cancel.cancel();
+            }
+        })
+        .await;
+        app.state::<TimesheetWalks>().finish(&walk_id);
+
+        match result {
+            Ok(walk) if walk.cancelled => {}
+            Ok(_) => {
+                if let Err(err) = history.set_sync_value(&profile_id, HISTORY_TIMESHEETS_SYNCED, &synced.to_rfc3339()) {
+                    eprintln!("Could not record the history sync: {err}");
+                }
+            }
+            Err(err) => eprintln!("Backfilling the history stopped: {err}"),
+        }
+    });
+}
+
+// Customer Management
+#[tauri::command]
+pub async fn kimai_list_customers(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+) -> KimaiResult<Vec<KimaiCustomer>> {
+    state.client(&profile_id)?.get_customers().await
+}
+
+#[tauri::command]
+pub async fn kimai_get_customer(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<KimaiCustomer> {
+    state.client(&profile_id)?.get_customer(id).await
+}
+
+#[tauri::command]
+pub async fn kimai_create_customer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    customer: KimaiCustomerForm,
+) -> KimaiResult<KimaiCustomer> {
+    let created = state.client(&profile_id)?.create_customer(&customer).await?;
+    expire_cached(&app, &profile_id, CUSTOMERS);
+    Ok(created)
+}
+
+#[tauri::command]
+pub async fn kimai_update_customer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+    customer: KimaiCustomerForm,
+) -> KimaiResult<KimaiCustomer> {
+    let updated = state.client(&profile_id)?.update_customer(id, &customer).await?;
+    expire_cached(&app, &profile_id, CUSTOMERS);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn kimai_delete_customer<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<()> {
+    state.client(&profile_id)?.delete_customer(id).await?;
+    expire_cached(&app, &profile_id, CUSTOMERS);
+    Ok(())
+}
+
+// Project Management
+#[tauri::command]
+pub async fn kimai_list_projects(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    customer: Option<u64>,
+) -> KimaiResult<Vec<KimaiProject>> {
+    state.client(&profile_id)?.get_projects(customer).await
+}
+
+#[tauri::command]
+pub async fn kimai_get_project(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<KimaiProject> {
+    state.client(&profile_id)?.get_project(id).await
+}
+
+#[tauri::command]
+pub async fn kimai_create_project<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    project: KimaiProjectForm,
+) -> KimaiResult<KimaiProject> {
+    let created = state.client(&profile_id)?.create_project(&project).await?;
+    expire_cached(&app, &profile_id, PROJECTS);
+    Ok(created)
+}
+
+#[tauri::command]
+pub async fn kimai_update_project<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+    project: KimaiProjectForm,
+) -> KimaiResult<KimaiProject> {
+    let updated = state.client(&profile_id)?.update_project(id, &project).await?;
+    expire_cached(&app, &profile_id, PROJECTS);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn kimai_delete_project<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<()> {
+    state.client(&profile_id)?.delete_project(id).await?;
+    expire_cached(&app, &profile_id, PROJECTS);
+    Ok(())
+}
+
+// Activity Management
+#[tauri::command]
+pub async fn kimai_list_activities(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    project: Option<u64>,
+) -> KimaiResult<Vec<KimaiActivity>> {
+    state.client(&profile_id)?.get_activities(project).await
+}
+
+#[tauri::command]
+pub async fn kimai_get_activity(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<KimaiActivity> {
+    state.client(&profile_id)?.get_activity(id).await
+}
+
+#[tauri::command]
+pub async fn kimai_create_activity<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    activity: KimaiActivityForm,
+) -> KimaiResult<KimaiActivity> {
+    let created = state.client(&profile_id)?.create_activity(&activity).await?;
+    expire_cached(&app, &profile_id, ACTIVITIES);
+    Ok(created)
+}
+
+#[tauri::command]
+pub async fn kimai_update_activity<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+    activity: KimaiActivityForm,
+) -> KimaiResult<KimaiActivity> {
+    let updated = state.client(&profile_id)?.update_activity(id, &activity).await?;
+    expire_cached(&app, &profile_id, ACTIVITIES);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn kimai_delete_activity<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<()> {
+    state.client(&profile_id)?.delete_activity(id).await?;
+    expire_cached(&app, &profile_id, ACTIVITIES);
+    Ok(())
+}
+
+// Time Sheet Management
+#[tauri::command]
+pub async fn kimai_list_timesheets(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    query: Option<KimaiTimeSheetQuery>,
+) -> KimaiResult<Vec<KimaiTimeSheet>> {
+    state
+        .client(&profile_id)?
+        .get_timesheets(&query.unwrap_or_default())
+        .await
+}
+
+/// Loads every page of timesheets matching `query`, emitting `timesheets://page`
+/// as pages arrive. `walk_id` identifies the walk for cancellation.
+#[tauri::command]
+pub async fn kimai_walk_timesheets<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    walks: State<'_, TimesheetWalks>,
+    profile_id: String,
+    walk_id: String,
+    query: Option<KimaiTimeSheetQuery>,
+) -> KimaiResult<TimesheetWalkResult> {
+    let client = state.client(&profile_id)?;
+    let cancel = walks.start(&walk_id);
+
+    let result = pagination::walk_timesheets(&client, &walk_id, query.unwrap_or_default(), &cancel, |page| {
+        let _ = app.emit(TIMESHEETS_PAGE_EVENT, page);
+    })
+    .await;
+
+    walks.finish(&walk_id);
+    result
+}
+
+#[tauri::command]
+pub fn kimai_cancel_timesheet_walk(walks: State<'_, TimesheetWalks>, walk_id: String) -> bool {
+    walks.cancel(&walk_id)
+}
+
+#[tauri::command]
+pub async fn kimai_list_active_timesheets(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+) -> KimaiResult<Vec<KimaiTimeSheet>> {
+    state.client(&profile_id)?.get_active_timesheets().await
+}
+
+#[tauri::command]
+pub async fn kimai_get_timesheet(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+) -> KimaiResult<KimaiTimeSheet> {
+    state.client(&profile_id)?.get_timesheet(id).await
+}
+
+#[tauri::command]
+pub async fn kimai_get_timesheet_config(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+) -> KimaiResult<TimesheetConfig> {
+    state.client(&profile_id)?.get_timesheet_config().await
+}
+
+/// Checks a new timesheet, or the one with `id`, against the server's
+/// timesheet rules, its project's dates and the cached timesheets, which are
+/// brought up to date first. An empty list means nothing is known to stand
+/// in the way.
+#[tauri::command]
+pub async fn kimai_validate_timesheet<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: Option<u64>,
+    timesheet: KimaiTimeSheetForm,
+) -> KimaiResult<Vec<PolicyViolation>> {
+    let client = state.client(&profile_id)?;
+    let config = client.get_timesheet_config().await?;
+    let path = cache_path(&app, &profile_id)?;
+    let mut cache = KimaiCache::load(&path).unwrap_or_default();
+    // Offline, the saved timesheets are the best there is
+    match cache.refresh(&client, false).await {
+        Ok(()) => cache.save(&path)?,
+        Err(err) => eprintln!("Validating against the saved cache of profile {profile_id}: {err}"),
+    }
+    Ok(policy::validate(&config, &cache, id, &timesheet, chrono::Local::now()))
+}
+
+#[tauri::command]
+pub async fn kimai_create_timesheet(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    timesheet: KimaiTimeSheetForm,
+) -> KimaiResult<KimaiTimeSheet> {
+    state.client(&profile_id)?.create_timesheet(&timesheet).await
+}
+
+#[tauri::command]
+pub async fn kimai_update_timesheet(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+    timesheet: KimaiTimeSheetForm,
+) -> KimaiResult<KimaiTimeSheet> {
+    state.client(&profile_id)?.update_timesheet(id, &timesheet).await
+}
+
+#[tauri::command]
+pub async fn kimai_delete_timesheet(state: State<'_, KimaiState>, profile_id: String, id: u64) -> KimaiResult<()> {
+    state.client(&profile_id)?.delete_timesheet(id).await
+}
+
+/// Stops timesheet `id` and starts `timesheet` at the same second. A start
+/// the server couldn't be reached for is retried in the background, and its
+/// result emitted as `timesheets://switch`.
+#[tauri::command]
+pub async fn kimai_switch_timesheet<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, KimaiState>,
+    queued: State<'_, QueuedStarts>,
+    profile_id: String,
+    id: u64,
+    timesheet: KimaiTimeSheetForm,
+) -> KimaiResult<SwitchOutcome> {
+    let client = state.client(&profile_id)?;
+    let at = switch::handover_time(chrono::Local::now());
+    let start = switch::starting_at(&timesheet, at);
+    let outcome = switch::switch_timesheet(&client, id, &start).await?;
+
+    if let SwitchOutcome::Queued { begin, .. } = &outcome {
+        let cancel = queued.queue(&profile_id);
+        let begin = begin.clone();
+        tauri::async_runtime::spawn(async move {
+            let Some(result) = switch::retry_start(&client, &start, at.to_utc(), &cancel).await else {
+                return;
+            };
+            app.state::<QueuedStarts>().finish(&profile_id, &cancel);
+            let (started, error) = match result {
+                Ok(started) => (Some(started), None),
+                Err(err) => (None, Some(err)),
+            };
+            let payload = QueuedStartResult {
+                profile_id,
+                begin,
+                started,
+                error,
+            };
+            let _ = app.emit(TIMESHEET_SWITCH_EVENT, &payload);
+        });
+    }
+    Ok(outcome)
+}
+
+/// Gives up the profile's queued start, e.g. when another timesheet is
+/// started by hand
+#[tauri::command]
+pub fn kimai_cancel_queued_start(queued: State<'_, QueuedStarts>, profile_id: String) -> bool {
+    queued.cancel(&profile_id)
+}
+
+// Task Management
+#[tauri::command]
+pub async fn kimai_list_tasks(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    query: Option<KimaiTaskQuery>,
+) -> KimaiResult<Vec<KimaiTask>> {
+    state.client(&profile_id)?.get_tasks(&query.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn kimai_get_task(state: State<'_, KimaiState>, profile_id: String, id: u64) -> KimaiResult<KimaiTask> {
+    state.client(&profile_id)?.get_task(id).await
+}
+
+#[tauri::command]
+pub async fn kimai_create_task(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    task: KimaiTaskForm,
+) -> KimaiResult<KimaiTask> {
+    state.client(&profile_id)?.create_task(&task).await
+}
+
+#[tauri::command]
+pub async fn kimai_update_task(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+    task: KimaiTaskForm,
+) -> KimaiResult<KimaiTask> {
+    state.client(&profile_id)?.update_task(id, &task).await
+}
+
+#[tauri::command]
+pub async fn kimai_delete_task(state: State<'_, KimaiState>, profile_id: String, id: u64) -> KimaiResult<()> {
+    state.client(&profile_id)?.delete_task(id).await
+}
+
+/// Starts, stops, closes, reopens, assigns or unassigns a task
+#[tauri::command]
+pub async fn kimai_task_action(
+    state: State<'_, KimaiState>,
+    profile_id: String,
+    id: u64,
+    action: KimaiTaskAction,
+) -> KimaiResult<KimaiTask> {
+    state.client(&profile_id)?.task_action(id, action).await
+}
@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+
+use reqwest::StatusCode;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use super::capabilities::KimaiFeature;
+use crate::secrets::SecretError;
+
+/// Form errors keyed by field path, e.g. `name` or `metaFields.ticket`.
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KimaiError {
+    #[error("Not connected to Kimai")]
+    NotConnected,
+    #[error("Invalid authentication configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Invalid certificate: {0}")]
+    Certificate(String),
+    #[error("Client certificate: {0}")]
+    ClientCertificate(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{message}")]
+    Validation { message: String, field_errors: FieldErrors },
+    #[error("{message}")]
+    LockdownViolation { message: String, field_errors: FieldErrors },
+    #[error("TLS handshake failed: {message} (certificate SHA-256 fingerprint {fingerprint})")]
+    Tls { message: String, fingerprint: String },
+    #[error("The Kimai server did not respond in time")]
+    Timeout,
+    #[error("Kimai server is unreachable: {0}")]
+    Offline(String),
+    #[error("{message}")]
+    Server { code: u16, message: String },
+    #[error("Unexpected response from Kimai: {0}")]
+    InvalidResponse(String),
+    #[error("The Kimai server does not support {}", .0.label())]
+    Unsupported(KimaiFeature),
+    #[error("Network error: {0}")]
+    Network(reqwest::Error),
+    /// The entity was saved, but storing one of its custom fields failed
+    #[error("Saved, but the custom field {field} was not: {error}")]
+    PartialSave { saved: Box<Value>, field: String, error: Box<KimaiError> },
+    #[error("Local storage error: {0}")]
+    Storage(String),
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+}
+
+impl KimaiError {
+    /// Stable identifier the frontend switches on
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KimaiError::NotConnected => "not_connected",
+            KimaiError::InvalidConfig(_) => "invalid_config",
+            KimaiError::Certificate(_) => "certificate",
+            KimaiError::ClientCertificate(_) => "client_certificate",
+            KimaiError::Unauthorized(_) => "unauthorized",
+            KimaiError::Forbidden(_) => "forbidden",
+            KimaiError::NotFound(_) => "not_found",
+            KimaiError::Validation { .. } => "validation",
+            KimaiError::LockdownViolation { .. } => "lockdown",
+            KimaiError::Tls { .. } => "tls",
+            KimaiError::Timeout => "timeout",
+            KimaiError::Offline(_) => "offline",
+            KimaiError::Server { .. } => "server",
+            KimaiError::InvalidResponse(_) => "invalid_response",
+            KimaiError::Unsupported(_) => "unsupported",
+            KimaiError::Network(_) => "network",
+            KimaiError::PartialSave { .. } => "partial_save",
+            KimaiError::Storage(_) => "storage",
+            // The UI asks for the passphrase on this one
+            KimaiError::Secret(SecretError::Locked) => "secret_locked",
+            KimaiError::Secret(_) => "secret",
+        }
+    }
+
+    pub fn field_errors(&self) -> Option<&FieldErrors> {
+        match self {
+            KimaiError::Validation { field_errors, .. } | KimaiError::LockdownViolation { field_errors, .. } => {
+                Some(field_errors)
+            }
+            KimaiError::PartialSave { error, .. } => error.field_errors(),
+            _ => None,
+        }
+    }
+
+    /// Builds the error for a non-success response from its status and JSON body.
+    pub fn from_response(status: StatusCode, body: &Value) -> Self {
+        let message = body
+            .get("message")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("HTTP {}: {}", status.as_u16(), status.canonical_reason().unwrap_or("")));
+
+        match status {
+            StatusCode::UNAUTHORIZED => KimaiError::Unauthorized(message),
+            StatusCode::FORBIDDEN => KimaiError::Forbidden(message),
+            StatusCode::NOT_FOUND => KimaiError::NotFound(message),
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+                let mut field_errors = FieldErrors::new();
+                if let Some(errors) = body.get("errors") {
+                    collect_form_errors(errors, "", &mut field_errors);
+                }
+
+                let is_lockdown = field_errors
+                    .values()
+                    .flatten()
+                    .chain(std::iter::once(&message))
+                    .any(|error| is_lockdown_message(error));
+
+                if is_lockdown {
+                    KimaiError::LockdownViolation { message, field_errors }
+                } else {
+                    KimaiError::Validation { message, field_errors }
+                }
+            }
+            _ => KimaiError::Server {
+                code: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<reqwest::Error> for KimaiError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            KimaiError::Timeout
+        } else if err.is_connect() {
+            KimaiError::Offline(err.to_string())
+        } else if err.is_decode() {
+            KimaiError::InvalidResponse(err.to_string())
+        } else {
+            KimaiError::Network(err)
+        }
+    }
+}
+
+/// Walks Kimai's form error tree:
+/// `{"errors": [..], "children": {"name": {"errors": [..]}, ...}}`
+fn collect_form_errors(node: &Value, path: &str, field_errors: &mut FieldErrors) {
+    if let Some(errors) = node.get("errors").and_then(Value::as_array) {
+        let messages: Vec<String> = errors.iter().filter_map(Value::as_str).map(str::to_string).collect();
+        if !messages.is_empty() {
+            field_errors.entry(path.to_string()).or_default().extend(messages);
+        }
+    }
+
+    if let Some(children) = node.get("children").and_then(Value::as_object) {
+        for (name, child) in children {
+            let child_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{path}.{name}")
+            };
+            collect_form_errors(child, &child_path, field_errors);
+        }
+    }
+}
+
+fn is_lockdown_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("period is locked") || message.contains("lockdown")
+}
+
+// Commands hand errors to the webview as {kind, message, field_errors}, plus
+// the certificate fingerprint of TLS errors and the saved entity of partial saves
+impl Serialize for KimaiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorPayload<'a> {
+            kind: &'static str,
+            message: String,
+            field_errors: Option<&'a FieldErrors>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            fingerprint: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            saved: Option<&'a Value>,
+        }
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+            field_errors: self.field_errors(),
+            fingerprint: match self {
+                KimaiError::Tls { fingerprint, .. } => Some(fingerprint),
+                _ => None,
+            },
+            saved: match self {
+                KimaiError::PartialSave { saved, .. } => Some(saved),
+                _ => None,
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<std::io::Error> for KimaiError {
+    fn from(err: std::io::Error) -> Self {
+        KimaiError::Storage(err.to_string())
+    }
+}
+
+pub type KimaiResult<T> = Result<T, KimaiError>;
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).expect("fixture should deserialize")
+    }
+
+    #[test]
+    fn kimai1_form_errors_are_keyed_by_field() {
+        let body = fixture(include_str!("../../tests/fixtures/kimai/v1/timesheet_invalid.json"));
+        let err = KimaiError::from_response(StatusCode::BAD_REQUEST, &body);
+        assert_eq!(err.kind(), "validation");
+        assert_eq!(err.to_string(), "Validation Failed");
+
+        let field_errors = err.field_errors().unwrap();
+        assert_eq!(field_errors.keys().collect::<Vec<_>>(), ["end", "project"]);
+        assert_eq!(field_errors["project"], ["This value should not be blank."]);
+    }
+
+    #[test]
+    fn kimai2_form_errors_keep_form_level_and_nested_fields() {
+        let body = fixture(include_str!("../../tests/fixtures/kimai/v2/timesheet_invalid.json"));
+        let err = KimaiError::from_response(StatusCode::UNPROCESSABLE_ENTITY, &body);
+
+        let field_errors = err.field_errors().unwrap();
+        assert_eq!(field_errors.keys().collect::<Vec<_>>(), ["", "activity", "metaFields.ticket"]);
+        assert_eq!(field_errors[""], ["This form should not contain extra fields."]);
+    }
+
+    #[test]
+    fn locked_periods_are_told_apart_from_other_validation_errors() {
+        let body = fixture(include_str!("../../tests/fixtures/kimai/v2/timesheet_locked.json"));
+        let err = KimaiError::from_response(StatusCode::BAD_REQUEST, &body);
+        assert_eq!(err.kind(), "lockdown");
+        assert_eq!(err.field_errors().unwrap()["begin"].len(), 1);
+
+        let body = json!({"code": 400, "message": "Timesheet lockdown is active"});
+        assert_eq!(KimaiError::from_response(StatusCode::BAD_REQUEST, &body).kind(), "lockdown");
+    }
+
+    #[test]
+    fn statuses_map_to_their_kind_with_a_fallback_message() {
+        let body = json!({"code": 403, "message": "Access denied."});
+        assert!(matches!(
+            KimaiError::from_response(StatusCode::FORBIDDEN, &body),
+            KimaiError::Forbidden(message) if message == "Access denied."
+        ));
+        assert_eq!(KimaiError::from_response(StatusCode::UNAUTHORIZED, &Value::Null).kind(), "unauthorized");
+        assert_eq!(KimaiError::from_response(StatusCode::NOT_FOUND, &Value::Null).kind(), "not_found");
+
+        let err = KimaiError::from_response(StatusCode::BAD_GATEWAY, &Value::Null);
+        assert!(matches!(err, KimaiError::Server { code: 502, .. }));
+        assert_eq!(err.to_string(), "HTTP 502: Bad Gateway");
+    }
+
+    #[test]
+    fn serialized_errors_carry_kind_and_field_errors() {
+        let body = fixture(include_str!("../../tests/fixtures/kimai/v1/timesheet_invalid.json"));
+        let payload = serde_json::to_value(KimaiError::from_response(StatusCode::BAD_REQUEST, &body)).unwrap();
+        assert_eq!(payload["kind"], "validation");
+        assert_eq!(payload["field_errors"]["end"][0], "End date must not be earlier then start date.");
+        assert!(payload.get("fingerprint").is_none());
+    }
+}
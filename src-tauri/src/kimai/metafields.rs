@@ -0,0 +1,282 @@
+// Custom meta fields
+// Definitions come from the custom-fields plugin (/api/metafields). Values are
+// checked against them before anything is sent, then stored one by one
+// through the entity's /meta endpoint.
+
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use super::error::FieldErrors;
+
+/// Meta field values keyed by field name, as sent by the dialogs
+pub type MetaFieldValues = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetaFieldEntity {
+    Timesheet,
+    Customer,
+    Project,
+    Activity,
+}
+
+impl MetaFieldEntity {
+    /// Collection path of the entity in the API
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            MetaFieldEntity::Timesheet => "/api/timesheets",
+            MetaFieldEntity::Customer => "/api/customers",
+            MetaFieldEntity::Project => "/api/projects",
+            MetaFieldEntity::Activity => "/api/activities",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetaFieldType {
+    #[serde(alias = "text")]
+    String,
+    Textarea,
+    Integer,
+    Number,
+    Money,
+    Duration,
+    #[serde(alias = "checkbox")]
+    Boolean,
+    #[serde(alias = "choice-search")]
+    Choice,
+    ChoiceMultiple,
+    Date,
+    Datetime,
+    Email,
+    Url,
+    Color,
+    Country,
+    Currency,
+    Language,
+    /// Types added by newer plugin versions; only `required` is checked
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaFieldDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(alias = "entityType")]
+    pub entity: MetaFieldEntity,
+    #[serde(rename = "type")]
+    pub field_type: MetaFieldType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(default, alias = "value")]
+    pub default_value: Option<String>,
+    #[serde(default)]
+    pub help: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_choices")]
+    pub choices: Vec<String>,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+/// The plugin stores choices as one comma separated string; newer versions
+/// send a list.
+fn deserialize_choices<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(choices)) => split_list(&choices),
+        Some(Value::Array(choices)) => choices.iter().filter_map(scalar_to_string).collect(),
+        Some(Value::Object(choices)) => choices.values().filter_map(scalar_to_string).collect(),
+        _ => Vec::new(),
+    })
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(if *value { "1" } else { "0" }.to_string()),
+        _ => None,
+    }
+}
+
+/// Renders a value the way the /meta endpoint stores it
+pub fn to_meta_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Array(items) => items.iter().filter_map(scalar_to_string).collect::<Vec<_>>().join(","),
+        value => scalar_to_string(value).unwrap_or_else(|| value.to_string()),
+    }
+}
+
+/// Checks `values` against the definitions for `entity`. When `creating`,
+/// required fields must be present; updates only check what they send.
+/// Errors are keyed `metaFields.<name>`, like the server's form errors.
+pub fn validate(
+    definitions: &[MetaFieldDefinition],
+    entity: MetaFieldEntity,
+    values: &MetaFieldValues,
+    creating: bool,
+) -> FieldErrors {
+    let mut errors = FieldErrors::new();
+    let definitions: Vec<_> = definitions.iter().filter(|definition| definition.entity == entity).collect();
+
+    for definition in &definitions {
+        let value = values.get(&definition.name);
+        let blank = value.is_none_or(is_blank);
+        let message = if blank {
+            let must_be_set = creating || value.is_some();
+            (definition.required && definition.visible && must_be_set && definition.default_value.is_none())
+                .then(|| "This value should not be blank.".to_string())
+        } else {
+            value.and_then(|value| check_value(definition, value).err())
+        };
+        if let Some(message) = message {
+            errors.insert(format!("metaFields.{}", definition.name), vec![message]);
+        }
+    }
+
+    for name in values.keys() {
+        if !definitions.iter().any(|definition| &definition.name == name) {
+            errors.insert(format!("metaFields.{name}"), vec!["This field does not exist.".to_string()]);
+        }
+    }
+
+    errors
+}
+
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(value) => value.trim().is_empty(),
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn check_value(definition: &MetaFieldDefinition, value: &Value) -> Result<(), String> {
+    let text = to_meta_value(value);
+    let text = text.trim();
+
+    let valid = match definition.field_type {
+        MetaFieldType::Integer => text.parse::<i64>().is_ok(),
+        MetaFieldType::Number | MetaFieldType::Money => text.parse::<f64>().is_ok_and(f64::is_finite),
+        MetaFieldType::Duration => is_duration(text),
+        MetaFieldType::Boolean => matches!(text, "0" | "1" | "true" | "false"),
+        MetaFieldType::Choice => definition.choices.is_empty() || definition.choices.iter().any(|choice| choice == text),
+        MetaFieldType::ChoiceMultiple => {
+            definition.choices.is_empty()
+                || split_list(text)
+                    .iter()
+                    .all(|item| definition.choices.contains(item))
+        }
+        MetaFieldType::Date => NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok(),
+        MetaFieldType::Datetime => ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+            .iter()
+            .any(|format| NaiveDateTime::parse_from_str(text, format).is_ok()),
+        MetaFieldType::Email => text
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.') && !text.contains(' ')),
+        MetaFieldType::Url => text.starts_with("http://") || text.starts_with("https://"),
+        MetaFieldType::Color => {
+            text.len() == 7 && text.starts_with('#') && text[1..].chars().all(|c| c.is_ascii_hexdigit())
+        }
+        MetaFieldType::String => text.chars().count() <= 255,
+        _ => true,
+    };
+
+    if valid {
+        return Ok(());
+    }
+    Err(match definition.field_type {
+        MetaFieldType::Choice | MetaFieldType::ChoiceMultiple => "The value you selected is not a valid choice.".into(),
+        MetaFieldType::String => "This value is too long. It should have 255 characters or less.".into(),
+        _ => "This value is not valid.".into(),
+    })
+}
+
+/// Seconds, or `h:mm` / `h:mm:ss`
+fn is_duration(text: &str) -> bool {
+    if text.parse::<u64>().is_ok() {
+        return true;
+    }
+    let parts: Vec<_> = text.split(':').collect();
+    (2..=3).contains(&parts.len())
+        && parts.iter().all(|part| part.parse::<u64>().is_ok())
+        && parts[1..].iter().all(|part| part.len() == 2 && part.parse::<u64>().is_ok_and(|n| n < 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn definitions() -> Vec<MetaFieldDefinition> {
+        serde_json::from_value(json!([
+            {"name": "ticket", "label": "Ticket", "entityType": "timesheet", "type": "string", "required": true},
+            {"name": "effort", "entityType": "timesheet", "type": "choice", "choices": "low,medium, high"},
+            {"name": "hours", "entityType": "timesheet", "type": "duration"},
+            {"name": "po", "entityType": "project", "type": "integer", "required": true}
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn required_fields_are_enforced_on_create_only() {
+        let definitions = definitions();
+        let errors = validate(&definitions, MetaFieldEntity::Timesheet, &MetaFieldValues::new(), true);
+        assert_eq!(errors.keys().collect::<Vec<_>>(), ["metaFields.ticket"]);
+
+        let errors = validate(&definitions, MetaFieldEntity::Timesheet, &MetaFieldValues::new(), false);
+        assert!(errors.is_empty());
+
+        let values = MetaFieldValues::from([("ticket".to_string(), json!(""))]);
+        let errors = validate(&definitions, MetaFieldEntity::Timesheet, &values, false);
+        assert!(errors.contains_key("metaFields.ticket"));
+    }
+
+    #[test]
+    fn values_are_checked_against_their_type() {
+        let definitions = definitions();
+        let values = MetaFieldValues::from([
+            ("ticket".to_string(), json!("T-42")),
+            ("effort".to_string(), json!("extreme")),
+            ("hours".to_string(), json!("1:30")),
+            ("unknown".to_string(), json!(1)),
+        ]);
+
+        let errors = validate(&definitions, MetaFieldEntity::Timesheet, &values, true);
+        assert_eq!(errors.keys().collect::<Vec<_>>(), ["metaFields.effort", "metaFields.unknown"]);
+
+        let values = MetaFieldValues::from([("po".to_string(), json!("12a"))]);
+        let errors = validate(&definitions, MetaFieldEntity::Project, &values, true);
+        assert!(errors.contains_key("metaFields.po"));
+    }
+
+    #[test]
+    fn meta_values_are_rendered_as_strings() {
+        assert_eq!(to_meta_value(&json!(true)), "1");
+        assert_eq!(to_meta_value(&json!(["a", "b"])), "a,b");
+        assert_eq!(to_meta_value(&json!(3.5)), "3.5");
+        assert_eq!(to_meta_value(&Value::Null), "");
+    }
+}
@@ -0,0 +1,52 @@
+// Kimai integration
+// Networking, authentication and errors live here so they keep working
+// when the window is hidden or reloaded.
+
+pub mod cache;
+pub mod capabilities;
+pub mod client;
+pub mod commands;
+pub mod error;
+pub mod metafields;
+pub mod models;
+pub mod normalize;
+pub mod pagination;
+pub mod policy;
+pub mod retry;
+pub mod switch;
+#[cfg(test)]
+pub mod test_server;
+pub mod tls;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub use client::KimaiClient;
+pub use error::{KimaiError, KimaiResult};
+
+/// Connected clients, keyed by profile id.
+#[derive(Default)]
+pub struct KimaiState {
+    clients: Mutex<HashMap<String, Arc<KimaiClient>>>,
+}
+
+impl KimaiState {
+    pub fn client(&self, profile_id: &str) -> KimaiResult<Arc<KimaiClient>> {
+        self.clients
+            .lock()
+            .unwrap()
+            .get(profile_id)
+            .cloned()
+            .ok_or(KimaiError::NotConnected)
+    }
+
+    pub fn insert(&self, profile_id: String, client: KimaiClient) -> Arc<KimaiClient> {
+        let client = Arc::new(client);
+        self.clients.lock().unwrap().insert(profile_id, client.clone());
+        client
+    }
+
+    pub fn remove(&self, profile_id: &str) {
+        self.clients.lock().unwrap().remove(profile_id);
+    }
+}
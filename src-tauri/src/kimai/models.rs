@@ -0,0 +1,790 @@
+// Kimai API models
+// Mirrors the types in src/lib/types/kimai.ts
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use super::capabilities::KimaiCapabilities;
+use super::metafields::MetaFieldValues;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiUser {
+    pub id: u64,
+    pub username: String,
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub preferences: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiCustomer {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub number: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub company: Option<String>,
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub fax: Option<String>,
+    #[serde(default)]
+    pub mobile: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    pub visible: bool,
+    #[serde(default)]
+    pub budget: Option<f64>,
+    #[serde(default)]
+    pub time_budget: Option<i64>,
+    #[serde(default)]
+    pub meta_fields: Option<Value>,
+}
+
+/// Kimai returns related entities either as their id (collections) or
+/// expanded (detail endpoints, `full=true`), depending on endpoint and version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EntityRef<T> {
+    Id(u64),
+    Entity(Box<T>),
+}
+
+pub trait KimaiEntity {
+    fn id(&self) -> u64;
+}
+
+impl<T: KimaiEntity> EntityRef<T> {
+    pub fn id(&self) -> u64 {
+        match self {
+            EntityRef::Id(id) => *id,
+            EntityRef::Entity(entity) => entity.id(),
+        }
+    }
+
+    pub fn entity(&self) -> Option<&T> {
+        match self {
+            EntityRef::Id(_) => None,
+            EntityRef::Entity(entity) => Some(entity),
+        }
+    }
+}
+
+impl KimaiEntity for KimaiUser {
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl KimaiEntity for KimaiCustomer {
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl KimaiEntity for KimaiProject {
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl KimaiEntity for KimaiActivity {
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl KimaiEntity for KimaiTimeSheet {
+    fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiProject {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub order_number: Option<String>,
+    #[serde(default)]
+    pub order_date: Option<String>,
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default)]
+    pub end: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    pub visible: bool,
+    #[serde(default)]
+    pub budget: Option<f64>,
+    #[serde(default)]
+    pub time_budget: Option<i64>,
+    pub customer: EntityRef<KimaiCustomer>,
+    #[serde(default)]
+    pub customer_name: Option<String>,
+    /// Customer name in Kimai's collection responses
+    #[serde(default, skip_serializing)]
+    pub parent_title: Option<String>,
+    #[serde(default)]
+    pub meta_fields: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiActivity {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub visible: bool,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub budget: Option<f64>,
+    #[serde(default)]
+    pub time_budget: Option<i64>,
+    /// Unset for global activities
+    #[serde(default)]
+    pub project: Option<EntityRef<KimaiProject>>,
+    #[serde(default)]
+    pub project_name: Option<String>,
+    /// Project name in Kimai's collection responses
+    #[serde(default, skip_serializing)]
+    pub parent_title: Option<String>,
+    #[serde(default)]
+    pub meta_fields: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiTimeSheet {
+    pub id: u64,
+    pub begin: String,
+    #[serde(default)]
+    pub end: Option<String>,
+    #[serde(default)]
+    pub duration: Option<i64>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub rate: Option<f64>,
+    #[serde(default)]
+    pub internal_rate: Option<f64>,
+    #[serde(default)]
+    pub billable: bool,
+    #[serde(default)]
+    pub exported: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub user: EntityRef<KimaiUser>,
+    #[serde(default)]
+    pub user_name: Option<String>,
+    pub activity: EntityRef<KimaiActivity>,
+    #[serde(default)]
+    pub activity_name: Option<String>,
+    pub project: EntityRef<KimaiProject>,
+    #[serde(default)]
+    pub project_name: Option<String>,
+    #[serde(default)]
+    pub customer: Option<u64>,
+    #[serde(default)]
+    pub customer_name: Option<String>,
+    #[serde(default)]
+    pub meta_fields: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KimaiTaskStatus {
+    Open,
+    Closed,
+    Pending,
+    Progress,
+}
+
+impl KimaiTaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KimaiTaskStatus::Open => "open",
+            KimaiTaskStatus::Closed => "closed",
+            KimaiTaskStatus::Pending => "pending",
+            KimaiTaskStatus::Progress => "progress",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KimaiTaskPriority {
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiTask {
+    pub id: u64,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub status: KimaiTaskStatus,
+    #[serde(default)]
+    pub priority: Option<KimaiTaskPriority>,
+    #[serde(default)]
+    pub due_date: Option<String>,
+    #[serde(default)]
+    pub estimated_duration: Option<i64>,
+    #[serde(default)]
+    pub actual_duration: Option<i64>,
+    #[serde(default)]
+    pub user: Option<EntityRef<KimaiUser>>,
+    pub activity: EntityRef<KimaiActivity>,
+    pub project: EntityRef<KimaiProject>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub meta_fields: Option<Value>,
+    #[serde(default)]
+    pub active_timesheets: Vec<KimaiTimeSheet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiVersion {
+    pub version: String,
+    pub version_id: u64,
+    #[serde(default, alias = "semver")]
+    pub semantic_version: Option<String>,
+    #[serde(default)]
+    pub candidate: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub copyright: Option<String>,
+}
+
+// Authentication
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KimaiAuthType {
+    ApiToken,
+    Legacy,
+}
+
+/// How credentials go over the wire: `Authorization: Bearer` for Kimai 2
+/// API tokens, `X-AUTH-USER`/`X-AUTH-TOKEN` for older installs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KimaiAuthScheme {
+    Bearer,
+    XAuth,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiAuthConfig {
+    #[serde(rename = "type")]
+    pub kind: KimaiAuthType,
+    /// Detected at connect time, unset until the first successful connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<KimaiAuthScheme>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+    /// Where the token or password is kept once moved into the secret store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_ref: Option<String>,
+    pub base_url: String,
+}
+
+impl KimaiAuthConfig {
+    /// API token for token profiles, the API password for legacy ones
+    pub fn credential(&self) -> Option<&str> {
+        match self.kind {
+            KimaiAuthType::ApiToken => self.api_token.as_deref(),
+            KimaiAuthType::Legacy => self.password.as_deref(),
+        }
+        .filter(|credential| !credential.is_empty())
+    }
+
+    pub fn set_credential(&mut self, credential: Option<String>) {
+        match self.kind {
+            KimaiAuthType::ApiToken => self.api_token = credential,
+            KimaiAuthType::Legacy => self.password = credential,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SslSettings {
+    pub ignore_ssl_errors: bool,
+    /// PEM files, inline PEM blocks or SHA-256 fingerprints
+    pub trusted_certificates: Vec<String>,
+    pub verify_hostname: bool,
+}
+
+impl Default for SslSettings {
+    fn default() -> Self {
+        Self {
+            ignore_ssl_errors: false,
+            trusted_certificates: Vec::new(),
+            verify_hostname: true,
+        }
+    }
+}
+
+/// Certificate presented to servers that require client authentication.
+/// `path` is either a PKCS#12 bundle or a PEM certificate chain; PEM keys
+/// are read from `key_path`, or from `path` when both live in one file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCertificate {
+    pub path: String,
+    #[serde(default)]
+    pub key_path: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Timeouts and retry policy for requests to one Kimai server.
+/// Only GET requests are retried; writes fail on the first error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RequestSettings {
+    #[serde(deserialize_with = "at_least_one_second")]
+    pub connect_timeout_secs: u64,
+    #[serde(deserialize_with = "at_least_one_second")]
+    pub timeout_secs: u64,
+    pub max_retries: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+}
+
+impl Default for RequestSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            timeout_secs: 30,
+            max_retries: 3,
+            retry_base_delay_ms: 500,
+            retry_max_delay_ms: 10_000,
+        }
+    }
+}
+
+/// A zero timeout fails every request at once, so timeouts are at least a second
+fn at_least_one_second<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Ok(u64::deserialize(deserializer)?.max(1))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiProfile {
+    pub id: String,
+    pub name: String,
+    pub auth: KimaiAuthConfig,
+    #[serde(default)]
+    pub ssl: SslSettings,
+    #[serde(default)]
+    pub client_certificate: Option<ClientCertificate>,
+    #[serde(default)]
+    pub requests: RequestSettings,
+    #[serde(default)]
+    pub auto_connect: bool,
+    #[serde(default)]
+    pub last_used: Option<String>,
+    #[serde(default)]
+    pub pause_policy: PausePolicy,
+    #[serde(default)]
+    pub rounding: RoundingSettings,
+}
+
+impl KimaiProfile {
+    /// Ids name the profile's cache file, so only letters, digits, `_` and `-` are allowed
+    pub fn is_valid_id(id: &str) -> bool {
+        !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+}
+
+/// How a paused timer maps onto Kimai timesheets, which know no pauses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PausePolicy {
+    /// One timesheet, its end moved back by the time spent paused
+    #[default]
+    Single,
+    /// A pause closes the timesheet; resuming starts a new one for the same
+    /// customer, project, activity, description and tags
+    Split,
+}
+
+/// Which way Kimai's rounding modes move begin, end and duration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Begin down, end and duration up
+    #[default]
+    Default,
+    Closest,
+    Floor,
+    Ceil,
+}
+
+/// Steps in minutes to round to; 0 leaves the value as tracked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundingRule {
+    #[serde(default)]
+    pub mode: RoundingMode,
+    #[serde(default)]
+    pub begin: u32,
+    #[serde(default)]
+    pub end: u32,
+    #[serde(default)]
+    pub duration: u32,
+}
+
+impl RoundingRule {
+    pub fn is_active(&self) -> bool {
+        self.begin > 0 || self.end > 0 || self.duration > 0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundingSettings {
+    #[serde(default)]
+    pub rule: RoundingRule,
+    /// Keyed by customer id, used instead of the profile's rule
+    #[serde(default)]
+    pub customers: BTreeMap<u64, RoundingRule>,
+}
+
+impl RoundingSettings {
+    pub fn rule_for(&self, customer: Option<u64>) -> RoundingRule {
+        customer
+            .and_then(|customer| self.customers.get(&customer))
+            .copied()
+            .unwrap_or(self.rule)
+    }
+}
+
+/// The part of `/api/config/calendar` the timer's automatic stop uses
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiCalendarConfig {
+    /// Local `HH:MM`
+    #[serde(default)]
+    pub business_hours_end: Option<String>,
+    /// Hours
+    #[serde(default)]
+    pub day_limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiConnectionState {
+    pub is_connected: bool,
+    pub is_connecting: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_connected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<KimaiVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<KimaiUser>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_scheme: Option<KimaiAuthScheme>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<KimaiCapabilities>,
+}
+
+// Request payloads
+// Every field is optional so the same form serves create (POST) and update (PATCH)
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiCustomerForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub company: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fax: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mobile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_budget: Option<i64>,
+    /// Checked against the meta field definitions, then stored through /meta
+    #[serde(default, skip_serializing)]
+    pub meta_fields: Option<MetaFieldValues>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiProjectForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_budget: Option<i64>,
+    /// Checked against the meta field definitions, then stored through /meta
+    #[serde(default, skip_serializing)]
+    pub meta_fields: Option<MetaFieldValues>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiActivityForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_budget: Option<i64>,
+    /// Checked against the meta field definitions, then stored through /meta
+    #[serde(default, skip_serializing)]
+    pub meta_fields: Option<MetaFieldValues>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiTimeSheetForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub begin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Comma separated, as expected by the Kimai form
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exported: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<u64>,
+    /// Checked against the meta field definitions, then stored through /meta
+    #[serde(default, skip_serializing)]
+    pub meta_fields: Option<MetaFieldValues>,
+}
+
+/// Task state changes, each a PATCH on `/api/tasks/{id}/<action>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KimaiTaskAction {
+    Start,
+    Stop,
+    Close,
+    Reopen,
+    Assign,
+    Unassign,
+}
+
+impl KimaiTaskAction {
+    pub fn path(&self) -> &'static str {
+        match self {
+            KimaiTaskAction::Start => "start",
+            KimaiTaskAction::Stop => "stop",
+            KimaiTaskAction::Close => "close",
+            KimaiTaskAction::Reopen => "reopen",
+            KimaiTaskAction::Assign => "assign",
+            KimaiTaskAction::Unassign => "unassign",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiTaskForm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<KimaiTaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimation: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Comma separated, as expected by the Kimai form
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+}
+
+// Query parameters
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiTimeSheetQuery {
+    pub user: Option<u64>,
+    pub customer: Option<u64>,
+    pub project: Option<u64>,
+    pub activity: Option<u64>,
+    pub begin: Option<String>,
+    pub end: Option<String>,
+    /// Only entries changed since this local date-time (Kimai 1.15+)
+    pub modified_after: Option<String>,
+    /// `id`, `begin`, `end` or `rate`
+    pub order_by: Option<String>,
+    /// `ASC` or `DESC`
+    pub order: Option<String>,
+    pub page: Option<u32>,
+    pub size: Option<u32>,
+}
+
+impl KimaiTimeSheetQuery {
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        push_opt(&mut query, "user", self.user);
+        push_opt(&mut query, "customer", self.customer);
+        push_opt(&mut query, "project", self.project);
+        push_opt(&mut query, "activity", self.activity);
+        push_opt(&mut query, "begin", self.begin.as_ref());
+        push_opt(&mut query, "end", self.end.as_ref());
+        push_opt(&mut query, "modified_after", self.modified_after.as_ref());
+        push_opt(&mut query, "orderBy", self.order_by.as_ref());
+        push_opt(&mut query, "order", self.order.as_ref());
+        push_opt(&mut query, "page", self.page);
+        push_opt(&mut query, "size", self.size);
+        query
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiTaskQuery {
+    pub user: Option<u64>,
+    pub customer: Option<u64>,
+    pub project: Option<u64>,
+    pub activity: Option<u64>,
+    #[serde(default)]
+    pub status: Vec<KimaiTaskStatus>,
+    pub page: Option<u32>,
+    pub size: Option<u32>,
+}
+
+impl KimaiTaskQuery {
+    pub fn to_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        // The task API filters with users[] and status[] arrays
+        push_opt(&mut query, "users[]", self.user);
+        push_opt(&mut query, "customer", self.customer);
+        push_opt(&mut query, "project", self.project);
+        push_opt(&mut query, "activity", self.activity);
+        for status in &self.status {
+            query.push(("status[]", status.as_str().to_string()));
+        }
+        push_opt(&mut query, "page", self.page);
+        push_opt(&mut query, "size", self.size);
+        query
+    }
+}
+
+fn push_opt<T: ToString>(query: &mut Vec<(&'static str, String)>, key: &'static str, value: Option<T>) {
+    if let Some(value) = value {
+        query.push((key, value.to_string()));
+    }
+}
@@ -0,0 +1,316 @@
+// Normalization
+// Resolves Kimai's id-or-expanded entity fields into the shapes the UI
+// types describe: ids plus names for timesheets, projects and activities,
+// expanded entities for tasks.
+
+use std::collections::HashMap;
+
+use super::models::*;
+
+/// Entities seen so far, used to resolve names for id-only references.
+#[derive(Debug, Default)]
+pub struct EntityIndex {
+    users: HashMap<u64, KimaiUser>,
+    customers: HashMap<u64, KimaiCustomer>,
+    projects: HashMap<u64, KimaiProject>,
+    activities: HashMap<u64, KimaiActivity>,
+}
+
+impl EntityIndex {
+    pub fn learn_user(&mut self, user: &KimaiUser) {
+        self.users.insert(user.id, user.clone());
+    }
+
+    pub fn learn_customer(&mut self, customer: &KimaiCustomer) {
+        self.customers.insert(customer.id, customer.clone());
+    }
+
+    pub fn learn_project(&mut self, project: &KimaiProject) {
+        if let Some(customer) = project.customer.entity() {
+            self.learn_customer(customer);
+        }
+        let mut project = project.clone();
+        self.resolve_project(&mut project);
+        self.projects.insert(project.id, project);
+    }
+
+    pub fn learn_activity(&mut self, activity: &KimaiActivity) {
+        if let Some(project) = activity.project.as_ref().and_then(EntityRef::entity) {
+            self.learn_project(project);
+        }
+        let mut activity = activity.clone();
+        self.resolve_activity(&mut activity);
+        self.activities.insert(activity.id, activity);
+    }
+
+    fn user_name(&self, id: u64) -> Option<String> {
+        self.users.get(&id).map(display_name)
+    }
+
+    fn customer_name(&self, id: u64) -> Option<String> {
+        self.customers.get(&id).map(|customer| customer.name.clone())
+    }
+
+    fn resolve_project(&self, project: &mut KimaiProject) {
+        let customer = project.customer.id();
+        project.customer_name = project
+            .customer
+            .entity()
+            .map(|customer| customer.name.clone())
+            .or_else(|| project.parent_title.clone())
+            .or_else(|| project.customer_name.clone())
+            .or_else(|| self.customer_name(customer));
+        project.customer = EntityRef::Id(customer);
+    }
+
+    fn resolve_activity(&self, activity: &mut KimaiActivity) {
+        let Some(project_ref) = &activity.project else {
+            return;
+        };
+        let project = project_ref.id();
+        activity.project_name = project_ref
+            .entity()
+            .map(|project| project.name.clone())
+            .or_else(|| activity.parent_title.clone())
+            .or_else(|| activity.project_name.clone())
+            .or_else(|| self.projects.get(&project).map(|project| project.name.clone()));
+        activity.project = Some(EntityRef::Id(project));
+    }
+}
+
+fn display_name(user: &KimaiUser) -> String {
+    user.alias
+        .clone()
+        .filter(|alias| !alias.is_empty())
+        .unwrap_or_else(|| user.username.clone())
+}
+
+pub trait Normalize {
+    /// Records expanded entities in `index` and resolves references against it.
+    fn normalize(&mut self, index: &mut EntityIndex);
+}
+
+impl<T: Normalize> Normalize for Vec<T> {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        for item in self {
+            item.normalize(index);
+        }
+    }
+}
+
+impl Normalize for KimaiUser {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        index.learn_user(self);
+    }
+}
+
+impl Normalize for KimaiCustomer {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        index.learn_customer(self);
+    }
+}
+
+impl Normalize for KimaiProject {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        index.learn_project(self);
+        index.resolve_project(self);
+    }
+}
+
+impl Normalize for KimaiActivity {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        index.learn_activity(self);
+        index.resolve_activity(self);
+    }
+}
+
+impl Normalize for KimaiTimeSheet {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        if let Some(user) = self.user.entity() {
+            index.learn_user(user);
+        }
+        if let Some(project) = self.project.entity() {
+            index.learn_project(project);
+        }
+        if let Some(activity) = self.activity.entity() {
+            index.learn_activity(activity);
+        }
+
+        let (user, activity, project) = (self.user.id(), self.activity.id(), self.project.id());
+        self.user_name = self.user_name.take().or_else(|| index.user_name(user));
+        self.activity_name = self
+            .activity_name
+            .take()
+            .or_else(|| index.activities.get(&activity).map(|activity| activity.name.clone()));
+
+        if let Some(project) = index.projects.get(&project) {
+            self.project_name = self.project_name.take().or_else(|| Some(project.name.clone()));
+            let customer = project.customer.id();
+            self.customer = Some(customer);
+            self.customer_name = self
+                .customer_name
+                .take()
+                .or_else(|| project.customer_name.clone())
+                .or_else(|| index.customer_name(customer));
+        }
+
+        self.user = EntityRef::Id(user);
+        self.activity = EntityRef::Id(activity);
+        self.project = EntityRef::Id(project);
+    }
+}
+
+impl Normalize for KimaiTask {
+    fn normalize(&mut self, index: &mut EntityIndex) {
+        if let Some(user) = self.user.as_ref().and_then(EntityRef::entity) {
+            index.learn_user(user);
+        }
+        if let Some(project) = self.project.entity() {
+            index.learn_project(project);
+        }
+        if let Some(activity) = self.activity.entity() {
+            index.learn_activity(activity);
+        }
+        self.active_timesheets.normalize(index);
+
+        // Tasks keep their related entities expanded, as far as they are known
+        if let Some(user) = self.user.as_ref().and_then(|user| index.users.get(&user.id())) {
+            self.user = Some(EntityRef::Entity(Box::new(user.clone())));
+        }
+        if let Some(project) = index.projects.get(&self.project.id()) {
+            self.project = EntityRef::Entity(Box::new(project.clone()));
+        }
+        if let Some(activity) = index.activities.get(&self.activity.id()) {
+            self.activity = EntityRef::Entity(Box::new(activity.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture<T: serde::de::DeserializeOwned>(json: &str) -> T {
+        serde_json::from_str(json).expect("fixture should deserialize")
+    }
+
+    #[test]
+    fn kimai1_project_collection_resolves_customer_from_parent_title() {
+        let mut projects: Vec<KimaiProject> = fixture(include_str!("../../tests/fixtures/kimai/v1/projects.json"));
+        projects.normalize(&mut EntityIndex::default());
+
+        assert_eq!(projects[0].customer.id(), 1);
+        assert_eq!(projects[0].customer_name.as_deref(), Some("Acme Corp"));
+        assert!(projects[0].customer.entity().is_none());
+    }
+
+    #[test]
+    fn kimai2_project_detail_collapses_expanded_customer() {
+        let mut project: KimaiProject = fixture(include_str!("../../tests/fixtures/kimai/v2/project.json"));
+        project.normalize(&mut EntityIndex::default());
+
+        assert_eq!(project.customer.id(), 1);
+        assert_eq!(project.customer_name.as_deref(), Some("Acme Corp"));
+        assert!(project.customer.entity().is_none());
+    }
+
+    #[test]
+    fn kimai1_activity_collection_keeps_global_activities() {
+        let mut activities: Vec<KimaiActivity> =
+            fixture(include_str!("../../tests/fixtures/kimai/v1/activities.json"));
+        activities.normalize(&mut EntityIndex::default());
+
+        assert_eq!(activities[0].project.as_ref().map(EntityRef::id), Some(1));
+        assert_eq!(activities[0].project_name.as_deref(), Some("Web relaunch"));
+        assert!(activities[1].project.is_none());
+        assert!(activities[1].project_name.is_none());
+    }
+
+    #[test]
+    fn kimai2_timesheet_collection_resolves_names_from_index() {
+        let mut index = EntityIndex::default();
+        let mut customers: Vec<KimaiCustomer> = fixture(include_str!("../../tests/fixtures/kimai/v2/customers.json"));
+        let mut projects: Vec<KimaiProject> = fixture(include_str!("../../tests/fixtures/kimai/v2/projects.json"));
+        let mut activities: Vec<KimaiActivity> =
+            fixture(include_str!("../../tests/fixtures/kimai/v2/activities.json"));
+        let mut user: KimaiUser = fixture(include_str!("../../tests/fixtures/kimai/v2/user_me.json"));
+        customers.normalize(&mut index);
+        projects.normalize(&mut index);
+        activities.normalize(&mut index);
+        user.normalize(&mut index);
+
+        let mut timesheets: Vec<KimaiTimeSheet> =
+            fixture(include_str!("../../tests/fixtures/kimai/v2/timesheets.json"));
+        timesheets.normalize(&mut index);
+
+        let timesheet = &timesheets[0];
+        assert_eq!(timesheet.activity.id(), 1);
+        assert_eq!(timesheet.activity_name.as_deref(), Some("Development"));
+        assert_eq!(timesheet.project_name.as_deref(), Some("Web relaunch"));
+        assert_eq!(timesheet.customer, Some(1));
+        assert_eq!(timesheet.customer_name.as_deref(), Some("Acme Corp"));
+        assert_eq!(timesheet.user_name.as_deref(), Some("Anna Doe"));
+        assert!(timesheets[1].end.is_none());
+    }
+
+    #[test]
+    fn kimai2_full_timesheets_learn_expanded_entities() {
+        let mut index = EntityIndex::default();
+        let mut timesheets: Vec<KimaiTimeSheet> =
+            fixture(include_str!("../../tests/fixtures/kimai/v2/timesheets_full.json"));
+        timesheets.normalize(&mut index);
+
+        let timesheet = &timesheets[0];
+        assert!(timesheet.activity.entity().is_none());
+        assert_eq!(timesheet.activity_name.as_deref(), Some("Development"));
+        assert_eq!(timesheet.project.id(), 1);
+        assert_eq!(timesheet.customer_name.as_deref(), Some("Acme Corp"));
+        assert_eq!(timesheet.user_name.as_deref(), Some("Anna Doe"));
+
+        // Later id-only responses resolve against what was learned
+        let mut collection: Vec<KimaiTimeSheet> =
+            fixture(include_str!("../../tests/fixtures/kimai/v2/timesheets.json"));
+        collection.normalize(&mut index);
+        assert_eq!(collection[0].project_name.as_deref(), Some("Web relaunch"));
+    }
+
+    #[test]
+    fn kimai1_full_timesheets_with_user_id() {
+        let mut timesheets: Vec<KimaiTimeSheet> =
+            fixture(include_str!("../../tests/fixtures/kimai/v1/timesheets_full.json"));
+        timesheets.normalize(&mut EntityIndex::default());
+
+        let timesheet = &timesheets[0];
+        assert_eq!(timesheet.user.id(), 2);
+        assert!(timesheet.user_name.is_none());
+        assert_eq!(timesheet.activity_name.as_deref(), Some("Support"));
+        assert_eq!(timesheet.project_name.as_deref(), Some("Maintenance"));
+        assert_eq!(timesheet.customer, Some(3));
+        assert_eq!(timesheet.customer_name.as_deref(), Some("Globex"));
+        assert_eq!(timesheet.tags, vec!["hotline".to_string()]);
+    }
+
+    #[test]
+    fn tasks_keep_expanded_entities() {
+        let mut tasks: Vec<KimaiTask> = fixture(include_str!("../../tests/fixtures/kimai/v2/tasks.json"));
+        tasks.normalize(&mut EntityIndex::default());
+
+        let task = &tasks[0];
+        let project = task.project.entity().expect("project stays expanded");
+        assert_eq!(project.customer.id(), 1);
+        assert_eq!(project.customer_name.as_deref(), Some("Acme Corp"));
+        assert_eq!(task.activity.entity().map(|activity| activity.name.as_str()), Some("Development"));
+        assert_eq!(task.user.as_ref().map(EntityRef::id), Some(1));
+        assert_eq!(task.active_timesheets[0].activity_name.as_deref(), Some("Development"));
+    }
+
+    #[test]
+    fn kimai1_version_exposes_semver() {
+        let version: KimaiVersion = fixture(include_str!("../../tests/fixtures/kimai/v1/version.json"));
+        assert_eq!(version.semantic_version.as_deref(), Some("1.30.11-prod"));
+
+        let version: KimaiVersion = fixture(include_str!("../../tests/fixtures/kimai/v2/version.json"));
+        assert_eq!(version.version, "2.0.33");
+        assert!(version.semantic_version.is_none());
+    }
+}
@@ -0,0 +1,262 @@
+// Pagination
+// Kimai reports paging through X-Page / X-Total-Count / X-Total-Pages headers
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use super::client::KimaiClient;
+use super::error::KimaiResult;
+use super::models::{KimaiTimeSheet, KimaiTimeSheetQuery};
+
+pub const TIMESHEETS_PAGE_EVENT: &str = "timesheets://page";
+const DEFAULT_PAGE_SIZE: u32 = 250;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KimaiPage<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub total_pages: u32,
+    pub total_count: u64,
+}
+
+impl<T> KimaiPage<T> {
+    pub fn from_headers(items: Vec<T>, headers: &HeaderMap, requested_page: u32) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+
+        // Servers without the headers get treated as a single page
+        let total_count = header("X-Total-Count").unwrap_or(items.len() as u64);
+        Self {
+            page: header("X-Page").map(|page| page as u32).unwrap_or(requested_page),
+            total_pages: header("X-Total-Pages").map(|pages| pages as u32).unwrap_or(1),
+            total_count,
+            items,
+        }
+    }
+}
+
+/// Progress payload of the `timesheets://page` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetPageEvent {
+    pub walk_id: String,
+    pub page: u32,
+    pub total_pages: u32,
+    pub total_count: u64,
+    pub timesheets: Vec<KimaiTimeSheet>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetWalkResult {
+    pub walk_id: String,
+    pub timesheets: Vec<KimaiTimeSheet>,
+    pub total_count: u64,
+    pub cancelled: bool,
+}
+
+/// Running walks, keyed by the walk id the frontend chose.
+#[derive(Default)]
+pub struct TimesheetWalks {
+    walks: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl TimesheetWalks {
+    pub fn start(&self, walk_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        if let Some(previous) = self.walks.lock().unwrap().insert(walk_id.to_string(), token.clone()) {
+            previous.cancel();
+        }
+        token
+    }
+
+    /// Like `start`, but leaves a walk already running under `walk_id` alone
+    pub fn start_unless_running(&self, walk_id: &str) -> Option<CancellationToken> {
+        let mut walks = self.walks.lock().unwrap();
+        if walks.contains_key(walk_id) {
+            return None;
+        }
+        let token = CancellationToken::new();
+        walks.insert(walk_id.to_string(), token.clone());
+        Some(token)
+    }
+
+    pub fn finish(&self, walk_id: &str) {
+        self.walks.lock().unwrap().remove(walk_id);
+    }
+
+    pub fn cancel(&self, walk_id: &str) -> bool {
+        match self.walks.lock().unwrap().remove(walk_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Loads every page matching `query`, calling `on_page` as each one arrives.
+/// Stops early, keeping what was loaded so far, once `cancel` fires.
+pub async fn walk_timesheets<F>(
+    client: &KimaiClient,
+    walk_id: &str,
+    mut query: KimaiTimeSheetQuery,
+    cancel: &CancellationToken,
+    mut on_page: F,
+) -> KimaiResult<TimesheetWalkResult>
+where
+    F: FnMut(&TimesheetPageEvent),
+{
+    query.size = query.size.or(Some(DEFAULT_PAGE_SIZE));
+    let mut page = 1;
+    let mut result = TimesheetWalkResult {
+        walk_id: walk_id.to_string(),
+        timesheets: Vec::new(),
+        total_count: 0,
+        cancelled: false,
+    };
+
+    loop {
+        query.page = Some(page);
+        // Cancellation comes first, so a walk cancelled between pages sends no further request
+        let fetched = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                result.cancelled = true;
+                return Ok(result);
+            }
+            fetched = client.get_timesheets_page(&query) => fetched?,
+        };
+
+        let event = TimesheetPageEvent {
+            walk_id: walk_id.to_string(),
+            page: fetched.page,
+            total_pages: fetched.total_pages,
+            total_count: fetched.total_count,
+            timesheets: fetched.items,
+        };
+        on_page(&event);
+
+        result.total_count = event.total_count;
+        let is_last = event.timesheets.is_empty() || page >= event.total_pages;
+        result.timesheets.extend(event.timesheets);
+        if is_last {
+            return Ok(result);
+        }
+        page += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+    use crate::kimai::test_server::{self, Response};
+
+    const TIMESHEETS: &str = include_str!("../../tests/fixtures/kimai/v2/timesheets.json");
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    /// A server claiming `total_pages` pages; pages from `empty_from` on have no entries
+    fn serve_pages(total_pages: u32, empty_from: u32, requests: Arc<AtomicU32>) -> KimaiClient {
+        let url = test_server::serve(move |request| {
+            requests.fetch_add(1, Ordering::SeqCst);
+            let page: u32 = request.query("page").and_then(|page| page.parse().ok()).unwrap_or(1);
+            let body = if page >= empty_from { "[]" } else { TIMESHEETS };
+            Response::json("200 OK", body)
+                .header("X-Page", page)
+                .header("X-Total-Pages", total_pages)
+                .header("X-Total-Count", total_pages * 2)
+        });
+        test_server::client(&url)
+    }
+
+    #[test]
+    fn pages_are_read_from_the_headers() {
+        let page = KimaiPage::from_headers(
+            vec![1, 2],
+            &headers(&[("X-Page", "3"), ("X-Total-Pages", " 7 "), ("X-Total-Count", "13")]),
+            1,
+        );
+        assert_eq!((page.page, page.total_pages, page.total_count), (3, 7, 13));
+    }
+
+    #[test]
+    fn missing_or_garbled_headers_mean_a_single_page() {
+        let page = KimaiPage::from_headers(vec![1, 2, 3], &HeaderMap::new(), 2);
+        assert_eq!((page.page, page.total_pages, page.total_count), (2, 1, 3));
+
+        let page = KimaiPage::from_headers(
+            vec![1],
+            &headers(&[("X-Page", "two"), ("X-Total-Pages", "-1"), ("X-Total-Count", "")]),
+            4,
+        );
+        assert_eq!((page.page, page.total_pages, page.total_count), (4, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn walks_stop_after_the_last_page() {
+        let requests = Arc::new(AtomicU32::new(0));
+        let client = serve_pages(3, u32::MAX, requests.clone());
+        let mut pages = Vec::new();
+
+        let result = walk_timesheets(&client, "all", KimaiTimeSheetQuery::default(), &CancellationToken::new(), |event| {
+            pages.push((event.page, event.total_pages))
+        })
+        .await
+        .unwrap();
+        assert_eq!(pages, [(1, 3), (2, 3), (3, 3)]);
+        assert_eq!((result.timesheets.len(), result.total_count, result.cancelled), (6, 6, false));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn walks_stop_at_an_empty_page() {
+        let requests = Arc::new(AtomicU32::new(0));
+        let client = serve_pages(5, 3, requests.clone());
+
+        let result = walk_timesheets(&client, "all", KimaiTimeSheetQuery::default(), &CancellationToken::new(), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(result.timesheets.len(), 4);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn cancelled_walks_keep_the_pages_loaded_so_far() {
+        let requests = Arc::new(AtomicU32::new(0));
+        let client = serve_pages(5, u32::MAX, requests.clone());
+        let cancel = CancellationToken::new();
+
+        let result = walk_timesheets(&client, "all", KimaiTimeSheetQuery::default(), &cancel, |event| {
+            if event.page == 2 {
+                cancel.cancel();
+            }
+        })
+        .await
+        .unwrap();
+        assert!(result.cancelled);
+        assert_eq!(result.timesheets.len(), 4);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
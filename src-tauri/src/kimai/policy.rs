@@ -0,0 +1,497 @@
+// Timesheet policy
+// Checks a timesheet against the server's rules (/api/config/timesheet),
+// the project dates and the cached timesheets before it is submitted, so
+// violations show up in the form instead of as server errors. The server
+// stays the judge: permissions such as lockdown overrides aren't known here.
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use super::cache::{parse_kimai_datetime, KimaiCache};
+use super::models::{KimaiTimeSheet, KimaiTimeSheetForm};
+
+/// Timesheet settings, under the names of both the API and the web config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimesheetConfig {
+    #[serde(default = "allowed", alias = "isAllowFutureTimes")]
+    pub allow_future_times: bool,
+    #[serde(default = "allowed", alias = "isAllowOverlapping")]
+    pub allow_overlapping: bool,
+    /// PHP relative date, e.g. `first day of last month`
+    #[serde(default)]
+    pub lockdown_period_start: Option<String>,
+    #[serde(default)]
+    pub lockdown_period_end: Option<String>,
+    /// Modifier applied to the lockdown end, e.g. `+10 days`; plain numbers are days
+    #[serde(default, deserialize_with = "grace_period")]
+    pub lockdown_grace_period: Option<String>,
+}
+
+impl Default for TimesheetConfig {
+    fn default() -> Self {
+        Self {
+            allow_future_times: true,
+            allow_overlapping: true,
+            lockdown_period_start: None,
+            lockdown_period_end: None,
+            lockdown_grace_period: None,
+        }
+    }
+}
+
+fn allowed() -> bool {
+    true
+}
+
+fn grace_period<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(days)) => days.as_i64().filter(|days| *days != 0).map(|days| format!("{days:+} days")),
+        Some(Value::String(modifier)) if !modifier.trim().is_empty() => Some(modifier),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    InvalidTime,
+    EndBeforeBegin,
+    FutureTime,
+    Overlap,
+    Lockdown,
+    ProjectNotStarted,
+    ProjectEnded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyViolation {
+    pub kind: ViolationKind,
+    /// Form field the violation belongs to: `begin`, `end` or `project`
+    pub field: &'static str,
+    pub message: String,
+    /// The cached timesheet it conflicts with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timesheet_id: Option<u64>,
+}
+
+impl PolicyViolation {
+    fn new(kind: ViolationKind, field: &'static str, message: String) -> Self {
+        Self {
+            kind,
+            field,
+            message,
+            timesheet_id: None,
+        }
+    }
+}
+
+/// Checks `form`, the new timesheet or the one with `id`, as of `now`.
+/// Times without an offset are read in `now`'s timezone, like the forms
+/// enter them.
+pub fn validate<Tz: TimeZone>(
+    config: &TimesheetConfig,
+    cache: &KimaiCache,
+    id: Option<u64>,
+    form: &KimaiTimeSheetForm,
+    now: DateTime<Tz>,
+) -> Vec<PolicyViolation> {
+    // An edit or stop keeps the cached timesheet's times and project where it doesn't change them
+    let existing = id.and_then(|id| cache.time_sheets.iter().find(|timesheet| timesheet.id == id));
+    let form = &KimaiTimeSheetForm {
+        begin: form.begin.clone().or_else(|| existing.map(|timesheet| timesheet.begin.clone())),
+        end: form.end.clone().or_else(|| existing.and_then(|timesheet| timesheet.end.clone())),
+        project: form.project.or_else(|| existing.map(|timesheet| timesheet.project.id())),
+        ..form.clone()
+    };
+    let zone = now.timezone();
+    let mut violations = Vec::new();
+    let read = |field: &'static str, value: &Option<String>, violations: &mut Vec<PolicyViolation>| {
+        let value = value.as_deref()?;
+        let time = parse_time(value, &zone);
+        if time.is_none() {
+            violations.push(PolicyViolation::new(
+                ViolationKind::InvalidTime,
+                field,
+                format!("\"{value}\" is not a valid date and time"),
+            ));
+        }
+        time
+    };
+    let begin = match form.begin {
+        Some(_) => read("begin", &form.begin, &mut violations),
+        None => Some(now.with_timezone(&Utc)),
+    };
+    let end = read("end", &form.end, &mut violations);
+    let Some(begin) = begin else {
+        return violations;
+    };
+    let show = |time: DateTime<Utc>| time.with_timezone(&zone).naive_local().format("%Y-%m-%d %H:%M").to_string();
+
+    if end.is_some_and(|end| end < begin) {
+        violations.push(PolicyViolation::new(
+            ViolationKind::EndBeforeBegin,
+            "end",
+            "The end is before the begin".into(),
+        ));
+    }
+
+    let now_utc = now.with_timezone(&Utc);
+    if !config.allow_future_times {
+        for (field, time) in [("begin", Some(begin)), ("end", end)] {
+            if let Some(time) = time.filter(|time| *time > now_utc) {
+                violations.push(PolicyViolation::new(
+                    ViolationKind::FutureTime,
+                    field,
+                    format!("Times in the future are not allowed ({})", show(time)),
+                ));
+            }
+        }
+    }
+
+    if let Some(message) = lockdown(config, begin, &now, &show) {
+        violations.push(PolicyViolation::new(ViolationKind::Lockdown, "begin", message));
+    }
+
+    if let Some(project) = form.project.and_then(|project| cache.projects.iter().find(|cached| cached.id == project)) {
+        let project_start = project.start.as_deref().and_then(|start| parse_day_bound(start, &zone, false));
+        if let Some(start) = project_start.filter(|start| begin < *start) {
+            violations.push(PolicyViolation::new(
+                ViolationKind::ProjectNotStarted,
+                "project",
+                format!("The project \"{}\" only starts on {}", project.name, show(start)),
+            ));
+        }
+        let project_end = project.end.as_deref().and_then(|end| parse_day_bound(end, &zone, true));
+        if let Some(project_end) = project_end.filter(|project_end| end.unwrap_or(begin) > *project_end) {
+            violations.push(PolicyViolation::new(
+                ViolationKind::ProjectEnded,
+                "project",
+                format!("The project \"{}\" ended on {}", project.name, show(project_end)),
+            ));
+        }
+    }
+
+    if !config.allow_overlapping {
+        let end = end.unwrap_or(now_utc).max(begin);
+        for timesheet in cache.time_sheets.iter().filter(|timesheet| Some(timesheet.id) != id) {
+            let Some((other_begin, other_end)) = span(timesheet, now_utc) else {
+                continue;
+            };
+            if other_begin < end && begin < other_end {
+                let project = timesheet.project_name.as_deref().unwrap_or("another project");
+                violations.push(PolicyViolation {
+                    timesheet_id: Some(timesheet.id),
+                    ..PolicyViolation::new(
+                        ViolationKind::Overlap,
+                        "begin",
+                        format!(
+                            "Overlaps the timesheet for {project} from {} to {}",
+                            show(other_begin),
+                            show(other_end)
+                        ),
+                    )
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+/// Kimai locks timesheets up to the end of the lockdown period once the
+/// grace period after it is over.
+fn lockdown<Tz: TimeZone>(
+    config: &TimesheetConfig,
+    begin: DateTime<Utc>,
+    now: &DateTime<Tz>,
+    show: &impl Fn(DateTime<Utc>) -> String,
+) -> Option<String> {
+    let (Some(start), Some(end)) = (&config.lockdown_period_start, &config.lockdown_period_end) else {
+        return None;
+    };
+    let zone = now.timezone();
+    let local_now = now.naive_local();
+    let in_zone = |time: NaiveDateTime| zone.from_local_datetime(&time).earliest().map(|time| time.with_timezone(&Utc));
+    let start = in_zone(relative_time(start, local_now)?)?;
+    let end_local = relative_time(end, local_now)?;
+    let grace = match &config.lockdown_grace_period {
+        Some(modifier) => in_zone(relative_time(modifier, end_local)?)?,
+        None => in_zone(end_local)?,
+    };
+    let end = in_zone(end_local)?;
+
+    if begin > end || (begin >= start && now.with_timezone(&Utc) < grace) {
+        return None;
+    }
+    Some(format!(
+        "Timesheets up to {} are locked; this one begins {}",
+        show(end),
+        show(begin)
+    ))
+}
+
+fn span(timesheet: &KimaiTimeSheet, now: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let begin = parse_kimai_datetime(&timesheet.begin)?;
+    let end = match timesheet.end.as_deref() {
+        Some(end) => parse_kimai_datetime(end)?,
+        // Still running
+        None => now,
+    };
+    Some((begin, end.max(begin)))
+}
+
+/// RFC 3339, Kimai's `+0200` offsets, or local times as HTML inputs send them
+fn parse_time<Tz: TimeZone>(value: &str, zone: &Tz) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::<FixedOffset>::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    if let Some(time) = parse_kimai_datetime(value) {
+        return Some(time);
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|time| zone.from_local_datetime(&time).earliest())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Project dates may come without a time; they then span the whole day
+fn parse_day_bound<Tz: TimeZone>(value: &str, zone: &Tz, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Some(time) = parse_time(value, zone) {
+        return Some(time);
+    }
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let time = match end_of_day {
+        true => day.and_hms_opt(23, 59, 59)?,
+        false => day.and_hms_opt(0, 0, 0)?,
+    };
+    zone.from_local_datetime(&time).earliest().map(|time| time.with_timezone(&Utc))
+}
+
+/// The PHP relative formats Kimai's lockdown settings use, e.g.
+/// `first day of last month`, `last day of last month 23:59:59`,
+/// `monday this week`, `-1 day` or `+10 days`, applied to `base`.
+/// Anything else is not understood and skips the check.
+fn relative_time(expression: &str, base: NaiveDateTime) -> Option<NaiveDateTime> {
+    let expression = expression.to_lowercase();
+    let tokens: Vec<&str> = expression.split_whitespace().collect();
+    let (mut date, mut time) = (base.date(), base.time());
+    let midnight = NaiveTime::MIN;
+
+    let mut index = 0;
+    while let Some(&token) = tokens.get(index) {
+        index += 1;
+        match token {
+            "now" => {}
+            "today" | "midnight" => time = midnight,
+            "noon" => time = NaiveTime::from_hms_opt(12, 0, 0)?,
+            "yesterday" => (date, time) = (date.pred_opt()?, midnight),
+            "tomorrow" => (date, time) = (date.succ_opt()?, midnight),
+            "first" | "last" if tokens.get(index..index + 2) == Some(&["day", "of"][..]) => {
+                let month = shift(tokens.get(index + 2)?)?;
+                if !matches!(tokens.get(index + 3), Some(&"month")) {
+                    return None;
+                }
+                index += 4;
+                let first = add_months(date.with_day(1)?, month)?;
+                date = match token {
+                    "first" => first,
+                    _ => add_months(first, 1)?.pred_opt()?,
+                };
+            }
+            _ if token.contains(':') => {
+                time = NaiveTime::parse_from_str(token, "%H:%M:%S")
+                    .or_else(|_| NaiveTime::parse_from_str(token, "%H:%M"))
+                    .ok()?;
+            }
+            _ if token.starts_with(['+', '-']) || token.parse::<i64>().is_ok() => {
+                let amount: i64 = token.parse().ok()?;
+                let unit = tokens.get(index)?.trim_end_matches('s');
+                index += 1;
+                let moved = date.and_time(time);
+                let moved = match unit {
+                    "minute" | "min" => moved + Duration::minutes(amount),
+                    "hour" => moved + Duration::hours(amount),
+                    "day" => moved + Duration::days(amount),
+                    "week" => moved + Duration::weeks(amount),
+                    "month" => add_months(moved.date(), amount)?.and_time(moved.time()),
+                    "year" => add_months(moved.date(), amount * 12)?.and_time(moved.time()),
+                    _ => return None,
+                };
+                (date, time) = (moved.date(), moved.time());
+            }
+            _ => {
+                let weekday = token.parse::<Weekday>().ok()?;
+                time = midnight;
+                if let (Some(week), Some(&"week")) = (tokens.get(index).and_then(|word| shift(word)), tokens.get(index + 1))
+                {
+                    index += 2;
+                    let monday = date - Duration::days(date.weekday().num_days_from_monday().into());
+                    date = monday + Duration::weeks(week) + Duration::days(weekday.num_days_from_monday().into());
+                } else {
+                    let ahead = (7 + weekday.num_days_from_monday() - date.weekday().num_days_from_monday()) % 7;
+                    date += Duration::days(ahead.into());
+                }
+            }
+        }
+    }
+    Some(date.and_time(time))
+}
+
+fn shift(word: &str) -> Option<i64> {
+    match word {
+        "this" => Some(0),
+        "last" | "previous" => Some(-1),
+        "next" => Some(1),
+        _ => None,
+    }
+}
+
+fn add_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let count = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    match months < 0 {
+        true => date.checked_sub_months(count),
+        false => date.checked_add_months(count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn form(begin: &str, end: Option<&str>) -> KimaiTimeSheetForm {
+        KimaiTimeSheetForm {
+            begin: Some(begin.into()),
+            end: end.map(Into::into),
+            project: Some(2),
+            activity: Some(3),
+            ..Default::default()
+        }
+    }
+
+    fn kinds(violations: &[PolicyViolation]) -> Vec<ViolationKind> {
+        violations.iter().map(|violation| violation.kind).collect()
+    }
+
+    fn cache() -> KimaiCache {
+        serde_json::from_value(json!({
+            "customers": [],
+            "projects": [{"id": 2, "name": "Website", "visible": true, "customer": 1, "end": "2024-06-30"}],
+            "activities": [],
+            "timeSheets": [
+                {"id": 10, "begin": "2024-05-06T09:00:00+0000", "end": "2024-05-06T10:00:00+0000", "user": 1, "activity": 3, "project": 2, "projectName": "Website"},
+                {"id": 11, "begin": "2024-05-07T08:00:00+0000", "user": 1, "activity": 3, "project": 2}
+            ],
+            "tasks": [],
+            "lastUpdated": {},
+            "version": "1"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn config_reads_api_and_web_names() {
+        let config: TimesheetConfig =
+            serde_json::from_value(json!({"isAllowFutureTimes": false, "lockdownGracePeriod": 10})).unwrap();
+        assert!(!config.allow_future_times && config.allow_overlapping);
+        assert_eq!(config.lockdown_grace_period.as_deref(), Some("+10 days"));
+    }
+
+    #[test]
+    fn reports_overlaps_future_times_and_project_dates() {
+        let config = TimesheetConfig {
+            allow_future_times: false,
+            allow_overlapping: false,
+            ..Default::default()
+        };
+        let now = at("2024-05-07T12:00:00Z");
+
+        let overlapping = validate(&config, &cache(), None, &form("2024-05-06T09:30:00Z", Some("2024-05-06T11:00:00Z")), now);
+        assert_eq!(kinds(&overlapping), [ViolationKind::Overlap]);
+        assert_eq!(overlapping[0].timesheet_id, Some(10));
+        assert!(overlapping[0].message.contains("Website"));
+        // Editing the same timesheet doesn't overlap itself
+        assert!(validate(&config, &cache(), Some(10), &form("2024-05-06T09:30:00Z", Some("2024-05-06T10:30:00Z")), now).is_empty());
+        // The running timesheet reaches up to now
+        let running = validate(&config, &cache(), None, &form("2024-05-07T11:00:00", Some("2024-05-07T11:30:00")), now);
+        assert_eq!(running[0].timesheet_id, Some(11));
+
+        let future = validate(&config, &cache(), None, &form("2024-05-07T13:00:00Z", Some("2024-05-07T12:30:00Z")), now);
+        assert_eq!(kinds(&future), [ViolationKind::EndBeforeBegin, ViolationKind::FutureTime, ViolationKind::FutureTime]);
+
+        let ended = validate(&TimesheetConfig::default(), &cache(), None, &form("2024-07-01T09:00:00Z", None), now);
+        assert_eq!(kinds(&ended), [ViolationKind::ProjectEnded]);
+        assert_eq!(kinds(&validate(&config, &cache(), None, &form("yesterday", None), now)), [ViolationKind::InvalidTime]);
+    }
+
+    #[test]
+    fn edits_and_stops_keep_what_they_do_not_change() {
+        let config = TimesheetConfig {
+            allow_overlapping: false,
+            ..Default::default()
+        };
+        let now = at("2024-05-07T12:00:00Z");
+        let stop = KimaiTimeSheetForm {
+            end: Some("2024-05-07T11:00:00Z".into()),
+            ..Default::default()
+        };
+        assert!(validate(&config, &cache(), Some(11), &stop, now).is_empty());
+
+        // Stopped before it began
+        let early = KimaiTimeSheetForm {
+            end: Some("2024-05-07T07:00:00Z".into()),
+            ..Default::default()
+        };
+        assert_eq!(kinds(&validate(&config, &cache(), Some(11), &early, now)), [ViolationKind::EndBeforeBegin]);
+
+        // Moved onto the running timesheet
+        let moved = KimaiTimeSheetForm {
+            begin: Some("2024-05-07T08:30:00Z".into()),
+            end: Some("2024-05-07T09:00:00Z".into()),
+            ..Default::default()
+        };
+        let violations = validate(&config, &cache(), Some(10), &moved, now);
+        assert_eq!((kinds(&violations), violations[0].timesheet_id), (vec![ViolationKind::Overlap], Some(11)));
+    }
+
+    #[test]
+    fn lockdown_honours_the_grace_period() {
+        let config = TimesheetConfig {
+            lockdown_period_start: Some("first day of last month 00:00".into()),
+            lockdown_period_end: Some("last day of last month 23:59:59".into()),
+            lockdown_grace_period: Some("+5 days".into()),
+            ..Default::default()
+        };
+        let last_month = form("2024-04-15T09:00:00Z", Some("2024-04-15T10:00:00Z"));
+        let in_grace = validate(&config, &cache(), None, &last_month, at("2024-05-03T12:00:00Z"));
+        assert!(in_grace.is_empty());
+        let after_grace = validate(&config, &cache(), None, &last_month, at("2024-05-07T12:00:00Z"));
+        assert_eq!(kinds(&after_grace), [ViolationKind::Lockdown]);
+        let older = validate(&config, &cache(), None, &form("2024-03-15T09:00:00Z", None), at("2024-05-03T12:00:00Z"));
+        assert_eq!(kinds(&older), [ViolationKind::Lockdown]);
+    }
+
+    #[test]
+    fn relative_times_follow_php() {
+        let base = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap().and_hms_opt(15, 30, 0).unwrap();
+        let resolve = |expression: &str| relative_time(expression, base).map(|time| time.to_string());
+        assert_eq!(resolve("first day of last month").unwrap(), "2024-02-01 15:30:00");
+        assert_eq!(resolve("last day of last month 23:59:59").unwrap(), "2024-02-29 23:59:59");
+        assert_eq!(resolve("monday this week").unwrap(), "2024-03-11 00:00:00");
+        assert_eq!(resolve("sunday last week").unwrap(), "2024-03-10 00:00:00");
+        assert_eq!(resolve("+10 days").unwrap(), "2024-03-24 15:30:00");
+        assert_eq!(resolve("today -1 month").unwrap(), "2024-02-14 00:00:00");
+        assert_eq!(resolve("the ides of march"), None);
+    }
+}
@@ -0,0 +1,105 @@
+// Retries
+// Exponential backoff with full jitter for idempotent requests, and the
+// per-attempt report the status indicator listens to
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::error::KimaiError;
+use super::models::RequestSettings;
+
+pub const REQUEST_ATTEMPT_EVENT: &str = "kimai://request-attempt";
+
+/// Payload of the `kimai://request-attempt` event, sent once per attempt.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestAttempt {
+    pub profile_id: String,
+    pub method: String,
+    pub path: String,
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub succeeded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set when another attempt follows after this delay
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_ms: Option<u64>,
+}
+
+pub type AttemptListener = Arc<dyn Fn(&RequestAttempt) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(settings: &RequestSettings) -> Self {
+        Self {
+            max_retries: settings.max_retries,
+            base_delay: Duration::from_millis(settings.retry_base_delay_ms),
+            max_delay: Duration::from_millis(settings.retry_max_delay_ms.max(settings.retry_base_delay_ms)),
+        }
+    }
+
+    /// Delay before retry number `retry` (1-based): a random duration up to
+    /// `base * 2^(retry - 1)`, capped at the maximum delay.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_delay);
+        ceiling.mul_f64(fastrand::f64())
+    }
+}
+
+/// Failures that may go away on their own. Everything the server answered
+/// deliberately (auth, validation, not found) is final.
+pub fn is_retryable(err: &KimaiError) -> bool {
+    match err {
+        KimaiError::Timeout | KimaiError::Offline(_) | KimaiError::Network(_) => true,
+        KimaiError::Server { code, .. } => matches!(code, 429 | 502 | 503 | 504),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy::new(&RequestSettings {
+            retry_base_delay_ms: 100,
+            retry_max_delay_ms: 1_000,
+            ..Default::default()
+        });
+
+        for _ in 0..50 {
+            assert!(policy.delay(1) <= Duration::from_millis(100));
+            assert!(policy.delay(3) <= Duration::from_millis(400));
+            assert!(policy.delay(10) <= Duration::from_millis(1_000));
+        }
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        assert!(is_retryable(&KimaiError::Timeout));
+        assert!(is_retryable(&KimaiError::Server {
+            code: 503,
+            message: String::new()
+        }));
+        assert!(!is_retryable(&KimaiError::Server {
+            code: 500,
+            message: String::new()
+        }));
+        assert!(!is_retryable(&KimaiError::Unauthorized(String::new())));
+    }
+}
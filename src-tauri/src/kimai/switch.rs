@@ -0,0 +1,242 @@
+// Activity switch
+// Stops the running timesheet and starts the next one at the same second, so
+// nothing between them is lost or booked twice. A start the server refuses
+// reopens the stopped timesheet; one it never saw is retried in the
+// background with the same begin.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, SubsecRound, Utc};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use super::cache::parse_kimai_datetime;
+use super::models::{KimaiTimeSheet, KimaiTimeSheetForm};
+use super::{retry, KimaiClient, KimaiError, KimaiResult};
+
+pub const TIMESHEET_SWITCH_EVENT: &str = "timesheets://switch";
+/// Pause before each attempt at a queued start
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 20;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SwitchOutcome {
+    /// The next timesheet begins where the stopped one ends
+    Switched {
+        stopped: Box<KimaiTimeSheet>,
+        started: Box<KimaiTimeSheet>,
+    },
+    /// The start was refused and the stopped timesheet runs again
+    RolledBack { running: KimaiTimeSheet, error: KimaiError },
+    /// The server couldn't be reached for the start, which is retried
+    Queued { stopped: KimaiTimeSheet, begin: String, error: KimaiError },
+    /// The start was refused, and so was reopening the stopped timesheet
+    Failed {
+        stopped: KimaiTimeSheet,
+        error: KimaiError,
+        #[serde(rename = "rollbackError")]
+        rollback_error: KimaiError,
+    },
+}
+
+/// Payload of the `timesheets://switch` event, sent once a queued start
+/// landed or was given up
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedStartResult {
+    pub profile_id: String,
+    pub begin: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started: Option<KimaiTimeSheet>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<KimaiError>,
+}
+
+/// Starts being retried, one per profile
+#[derive(Default)]
+pub struct QueuedStarts {
+    starts: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl QueuedStarts {
+    /// Cancels the profile's previous queued start, if any
+    pub fn queue(&self, profile_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        if let Some(previous) = self.starts.lock().unwrap().insert(profile_id.to_string(), token.clone()) {
+            previous.cancel();
+        }
+        token
+    }
+
+    pub fn finish(&self, profile_id: &str, token: &CancellationToken) {
+        // A cancelled token was replaced by a newer start, which stays
+        if !token.is_cancelled() {
+            self.starts.lock().unwrap().remove(profile_id);
+        }
+    }
+
+    pub fn cancel(&self, profile_id: &str) -> bool {
+        match self.starts.lock().unwrap().remove(profile_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The handover second. Kimai takes local date-times without an offset.
+pub fn handover_time(now: DateTime<Local>) -> DateTime<Local> {
+    now.trunc_subsecs(0)
+}
+
+/// `next`, running from `at`
+pub fn starting_at(next: &KimaiTimeSheetForm, at: DateTime<Local>) -> KimaiTimeSheetForm {
+    KimaiTimeSheetForm {
+        begin: Some(at.format("%Y-%m-%dT%H:%M:%S").to_string()),
+        end: None,
+        ..next.clone()
+    }
+}
+
+/// Ends timesheet `running` where `start` begins, then creates `start`.
+/// Fails only when the stop does, with nothing changed.
+pub async fn switch_timesheet(
+    client: &KimaiClient,
+    running: u64,
+    start: &KimaiTimeSheetForm,
+) -> KimaiResult<SwitchOutcome> {
+    let begin = start.begin.clone().unwrap_or_default();
+    let stop = KimaiTimeSheetForm {
+        end: Some(begin.clone()),
+        ..Default::default()
+    };
+    let stopped = client.update_timesheet(running, &stop).await?;
+
+    let error = match started(client.create_timesheet(start).await) {
+        Ok(started) => {
+            return Ok(SwitchOutcome::Switched {
+                stopped: Box::new(stopped),
+                started: Box::new(started),
+            })
+        }
+        Err(err) => err,
+    };
+    if retry::is_retryable(&error) {
+        return Ok(SwitchOutcome::Queued { stopped, begin, error });
+    }
+    Ok(match client.reopen_timesheet(running).await {
+        Ok(running) => SwitchOutcome::RolledBack { running, error },
+        Err(rollback_error) => SwitchOutcome::Failed {
+            stopped,
+            error,
+            rollback_error,
+        },
+    })
+}
+
+/// Retries a queued start until it lands, is refused, runs out of attempts
+/// or `cancel` fires (`None`). An attempt that reached the server despite
+/// failing shows up among the active timesheets and isn't created twice.
+pub async fn retry_start(
+    client: &KimaiClient,
+    start: &KimaiTimeSheetForm,
+    at: DateTime<Utc>,
+    cancel: &CancellationToken,
+) -> Option<KimaiResult<KimaiTimeSheet>> {
+    let mut attempts = 0;
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return None,
+            _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+        }
+        attempts += 1;
+
+        let result = match client.get_active_timesheets().await {
+            Ok(active) => match landed(&active, start, at) {
+                Some(started) => Ok(started.clone()),
+                None => started(client.create_timesheet(start).await),
+            },
+            Err(err) => Err(err),
+        };
+        match result {
+            Err(err) if retry::is_retryable(&err) && attempts < MAX_ATTEMPTS => {}
+            result => return Some(result),
+        }
+    }
+}
+
+/// A start whose custom fields failed runs on the server all the same, so it
+/// must not be rolled back or created again
+fn started(result: KimaiResult<KimaiTimeSheet>) -> KimaiResult<KimaiTimeSheet> {
+    match result {
+        Err(KimaiError::PartialSave { saved, field, error }) => {
+            eprintln!("Started a timesheet without its custom field {field}: {error}");
+            serde_json::from_value(*saved).map_err(|err| KimaiError::InvalidResponse(err.to_string()))
+        }
+        result => result,
+    }
+}
+
+/// The active timesheet that is `start`, begun at `at`
+fn landed<'a>(active: &'a [KimaiTimeSheet], start: &KimaiTimeSheetForm, at: DateTime<Utc>) -> Option<&'a KimaiTimeSheet> {
+    active.iter().find(|timesheet| {
+        parse_kimai_datetime(&timesheet.begin) == Some(at)
+            && start.project == Some(timesheet.project.id())
+            && start.activity == Some(timesheet.activity.id())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn starts_begin_at_the_handover_second() {
+        let now = Local.with_ymd_and_hms(2024, 5, 6, 9, 30, 15).unwrap() + chrono::Duration::milliseconds(640);
+        let next = KimaiTimeSheetForm {
+            end: Some("2024-05-06T10:00:00".into()),
+            project: Some(2),
+            activity: Some(3),
+            ..Default::default()
+        };
+        let start = starting_at(&next, handover_time(now));
+        assert_eq!(start.begin.as_deref(), Some("2024-05-06T09:30:15"));
+        assert_eq!((start.end, start.activity), (None, Some(3)));
+    }
+
+    #[test]
+    fn a_start_that_landed_is_found_among_the_active_ones() {
+        let active: Vec<KimaiTimeSheet> = serde_json::from_value(json!([
+            {"id": 7, "begin": "2024-05-06T09:30:15+0000", "user": 1, "activity": 4, "project": 2},
+            {"id": 8, "begin": "2024-05-06T09:30:15+0000", "user": 1, "activity": 3, "project": 2}
+        ]))
+        .unwrap();
+        let start = KimaiTimeSheetForm {
+            project: Some(2),
+            activity: Some(3),
+            ..Default::default()
+        };
+        let at = Utc.with_ymd_and_hms(2024, 5, 6, 9, 30, 15).unwrap();
+        assert_eq!(landed(&active, &start, at).map(|timesheet| timesheet.id), Some(8));
+        assert!(landed(&active, &start, at + chrono::Duration::seconds(1)).is_none());
+    }
+
+    #[test]
+    fn a_start_missing_a_custom_field_still_counts_as_started() {
+        let partial = KimaiError::PartialSave {
+            saved: Box::new(json!({"id": 8, "begin": "2024-05-06T09:30:15+0000", "user": 1, "activity": 3, "project": 2})),
+            field: "ticket".into(),
+            error: Box::new(KimaiError::Timeout),
+        };
+        assert_eq!(started(Err(partial)).unwrap().id, 8);
+        assert!(matches!(started(Err(KimaiError::Timeout)), Err(KimaiError::Timeout)));
+    }
+}
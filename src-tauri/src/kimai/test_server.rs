@@ -0,0 +1,120 @@
+// Test server
+// A throwaway HTTP/1.1 server on localhost for client tests. Every request
+// gets its own connection, which the response closes.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+use serde_json::json;
+
+use super::client::KimaiClient;
+use super::models::KimaiProfile;
+
+pub struct Request {
+    pub method: String,
+    /// Path and query, e.g. `/api/timesheets?page=2`
+    pub target: String,
+    headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        let (_, query) = self.target.split_once('?')?;
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+pub struct Response {
+    pub status: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+}
+
+/// Profile "work" signing in with an API token to the Kimai at `base_url`
+pub fn profile(base_url: &str) -> KimaiProfile {
+    serde_json::from_value(json!({
+        "id": "work",
+        "name": "Work",
+        "auth": {"type": "api_token", "apiToken": "secret-token", "baseUrl": base_url}
+    }))
+    .unwrap()
+}
+
+/// Client of the test profile for the Kimai at `base_url`
+pub fn client(base_url: &str) -> KimaiClient {
+    KimaiClient::new(&profile(base_url)).unwrap()
+}
+
+/// Answers requests with `handler` until the test ends; returns the base URL
+pub fn serve(handler: impl Fn(&Request) -> Response + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().map_while(Result::ok) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            if reader.read_line(&mut line).is_err() {
+                continue;
+            }
+            let mut request_line = line.split_whitespace().map(str::to_string);
+            let method = request_line.next().unwrap_or_default();
+            let target = request_line.next().unwrap_or_default();
+            let mut headers = Vec::new();
+            line.clear();
+            while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.push((name.trim().to_string(), value.trim().to_string()));
+                }
+                line.clear();
+            }
+
+            let mut request = Request { method, target, headers, body: String::new() };
+            let length = request.header("Content-Length").and_then(|length| length.parse().ok()).unwrap_or(0);
+            let mut body = vec![0; length];
+            if reader.read_exact(&mut body).is_err() {
+                continue;
+            }
+            request.body = String::from_utf8_lossy(&body).into_owned();
+
+            let response = handler(&request);
+            let mut head = format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\n", response.status);
+            for (name, value) in &response.headers {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+            let _ = write!(
+                stream,
+                "{head}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.body.len(),
+                response.body
+            );
+        }
+    });
+    url
+}
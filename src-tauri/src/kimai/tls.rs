@@ -0,0 +1,369 @@
+// TLS configuration
+// Builds one rustls config per profile from its SSL settings
+
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::error::{KimaiError, KimaiResult};
+use super::models::{ClientCertificate, SslSettings};
+
+/// Certificate presented by the server during the last handshake.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerCertificate {
+    /// SHA-256 fingerprint as colon separated uppercase hex
+    pub fingerprint: String,
+    pub pem: String,
+    pub trusted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Shared slot the verifier writes the peer certificate into,
+/// so failed handshakes can be reported with their fingerprint.
+#[derive(Debug, Default)]
+pub struct CertificateCapture(Mutex<Option<PeerCertificate>>);
+
+impl CertificateCapture {
+    pub fn last(&self) -> Option<PeerCertificate> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Takes the certificate of the last handshake if it was rejected.
+    pub fn take_rejected(&self) -> Option<PeerCertificate> {
+        let mut slot = self.0.lock().unwrap();
+        if slot.as_ref().is_some_and(|certificate| !certificate.trusted) {
+            slot.take()
+        } else {
+            None
+        }
+    }
+
+    fn record(&self, certificate: PeerCertificate) {
+        *self.0.lock().unwrap() = Some(certificate);
+    }
+}
+
+pub fn build_tls_config(
+    ssl: &SslSettings,
+    client_certificate: Option<&ClientCertificate>,
+    capture: Arc<CertificateCapture>,
+) -> KimaiResult<rustls::ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = ProfileVerifier::new(ssl, provider.clone(), capture)?;
+
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|err| KimaiError::Certificate(err.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier));
+
+    let config = match client_certificate {
+        Some(client_certificate) => {
+            let (chain, key) = load_client_identity(client_certificate)?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|err| KimaiError::ClientCertificate(format!("{}: {err}", client_certificate.path)))?
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(config)
+}
+
+fn load_client_identity(
+    client_certificate: &ClientCertificate,
+) -> KimaiResult<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let path = &client_certificate.path;
+    let data = std::fs::read(path).map_err(|err| KimaiError::ClientCertificate(format!("{path}: {err}")))?;
+
+    if data.starts_with(b"-----BEGIN") {
+        let chain = CertificateDer::pem_slice_iter(&data)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| KimaiError::ClientCertificate(format!("{path}: {err}")))?;
+        if chain.is_empty() {
+            return Err(KimaiError::ClientCertificate(format!("{path}: no certificate found")));
+        }
+
+        let key_path = client_certificate.key_path.as_deref().unwrap_or(path);
+        let key = PrivateKeyDer::from_pem_file(key_path).map_err(|err| match err {
+            rustls::pki_types::pem::Error::NoItemsFound => {
+                KimaiError::ClientCertificate(format!("{key_path}: no private key found"))
+            }
+            err => KimaiError::ClientCertificate(format!("{key_path}: unreadable private key: {err}")),
+        })?;
+        return Ok((chain, key));
+    }
+
+    let password = client_certificate.password.as_deref().unwrap_or_default();
+    let keystore = p12_keystore::KeyStore::from_pkcs12(&data, password, Default::default())
+        .map_err(|err| KimaiError::ClientCertificate(format!("{path}: {err}")))?;
+    let (_, key_chain) = keystore
+        .private_key_chain()
+        .ok_or_else(|| KimaiError::ClientCertificate(format!("{path}: no private key found")))?;
+
+    let chain = key_chain
+        .certs()
+        .iter()
+        .map(|certificate| CertificateDer::from(certificate.as_der().to_vec()))
+        .collect();
+    let key = PrivatePkcs8KeyDer::from(key_chain.key().as_der().to_vec()).into();
+    Ok((chain, key))
+}
+
+/// Accepts a trusted certificate entry: inline PEM or a path to a PEM file.
+fn load_certificates(entry: &str) -> KimaiResult<Vec<CertificateDer<'static>>> {
+    let certificates: Result<Vec<_>, _> = if entry.starts_with("-----BEGIN") {
+        CertificateDer::pem_slice_iter(entry.as_bytes()).collect()
+    } else {
+        CertificateDer::pem_file_iter(entry)
+            .map_err(|err| KimaiError::Certificate(format!("{entry}: {err}")))?
+            .collect()
+    };
+
+    let certificates = certificates.map_err(|err| KimaiError::Certificate(format!("{entry}: {err}")))?;
+    if certificates.is_empty() {
+        return Err(KimaiError::Certificate(format!("{entry}: no certificate found")));
+    }
+    Ok(certificates)
+}
+
+fn parse_fingerprint(entry: &str) -> Option<String> {
+    let hex: String = entry.chars().filter(|c| *c != ':').collect();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format_fingerprint(&hex.to_ascii_uppercase()))
+}
+
+fn format_fingerprint(hex: &str) -> String {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| std::str::from_utf8(pair).unwrap())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+pub fn fingerprint(certificate: &CertificateDer<'_>) -> String {
+    let digest = Sha256::digest(certificate.as_ref());
+    let hex: String = digest.iter().map(|byte| format!("{byte:02X}")).collect();
+    format_fingerprint(&hex)
+}
+
+pub fn to_pem(certificate: &CertificateDer<'_>) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(certificate.as_ref());
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+#[derive(Debug)]
+struct ProfileVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    provider: Arc<CryptoProvider>,
+    pinned: Vec<String>,
+    ignore_errors: bool,
+    verify_hostname: bool,
+    capture: Arc<CertificateCapture>,
+}
+
+impl ProfileVerifier {
+    fn new(ssl: &SslSettings, provider: Arc<CryptoProvider>, capture: Arc<CertificateCapture>) -> KimaiResult<Self> {
+        let mut roots = RootCertStore::empty();
+        // Unreadable system certificates are skipped, same as the platform does
+        let native = rustls_native_certs::load_native_certs();
+        roots.add_parsable_certificates(native.certs);
+
+        let mut pinned = Vec::new();
+        for entry in ssl.trusted_certificates.iter().map(|entry| entry.trim()) {
+            if entry.is_empty() {
+                continue;
+            }
+            if let Some(fingerprint) = parse_fingerprint(entry) {
+                pinned.push(fingerprint);
+                continue;
+            }
+            for certificate in load_certificates(entry)? {
+                pinned.push(fingerprint(&certificate));
+                roots
+                    .add(certificate)
+                    .map_err(|err| KimaiError::Certificate(format!("{entry}: {err}")))?;
+            }
+        }
+
+        let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|err| KimaiError::Certificate(err.to_string()))?;
+
+        Ok(ProfileVerifier {
+            inner,
+            provider,
+            pinned,
+            ignore_errors: ssl.ignore_ssl_errors,
+            verify_hostname: ssl.verify_hostname,
+            capture,
+        })
+    }
+}
+
+impl ServerCertVerifier for ProfileVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = fingerprint(end_entity);
+        let result = if self.pinned.contains(&fingerprint) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            match self
+                .inner
+                .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            {
+                Err(rustls::Error::InvalidCertificate(
+                    CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+                )) if !self.verify_hostname => Ok(ServerCertVerified::assertion()),
+                Err(_) if self.ignore_errors => Ok(ServerCertVerified::assertion()),
+                result => result,
+            }
+        };
+
+        self.capture.record(PeerCertificate {
+            fingerprint,
+            pem: to_pem(end_entity),
+            trusted: result.is_ok(),
+            error: result.as_ref().err().map(|err| err.to_string()),
+        });
+
+        result
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA: &str = include_str!("../../tests/fixtures/tls/ca.pem");
+    const SERVER: &str = include_str!("../../tests/fixtures/tls/server.pem");
+
+    fn server_certificate() -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(SERVER.as_bytes()).unwrap()
+    }
+
+    fn ssl(trusted_certificates: &[&str]) -> SslSettings {
+        SslSettings {
+            trusted_certificates: trusted_certificates.iter().map(|entry| entry.to_string()).collect(),
+            ..SslSettings::default()
+        }
+    }
+
+    /// Verifies the fixture server certificate, issued for kimai.test, as `host`
+    fn verify(ssl: &SslSettings, host: &str) -> (Result<ServerCertVerified, rustls::Error>, PeerCertificate) {
+        let capture = Arc::new(CertificateCapture::default());
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = ProfileVerifier::new(ssl, provider, capture.clone()).unwrap();
+        let server_name = ServerName::try_from(host.to_string()).unwrap();
+        let result = verifier.verify_server_cert(&server_certificate(), &[], &server_name, &[], UnixTime::now());
+        (result, capture.last().unwrap())
+    }
+
+    #[test]
+    fn an_untrusted_certificate_is_rejected_with_its_fingerprint() {
+        let (result, peer) = verify(&ssl(&[]), "kimai.test");
+
+        assert!(matches!(
+            result,
+            Err(rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer))
+        ));
+        assert!(!peer.trusted);
+        assert_eq!(peer.fingerprint, fingerprint(&server_certificate()));
+        assert_eq!(peer.pem, SERVER);
+    }
+
+    #[test]
+    fn a_pinned_fingerprint_is_trusted_whatever_the_host() {
+        let pinned = fingerprint(&server_certificate());
+        let lowercase = pinned.replace(':', "").to_ascii_lowercase();
+
+        for entry in [pinned.as_str(), lowercase.as_str()] {
+            let (result, peer) = verify(&ssl(&[entry]), "other.test");
+            assert!(result.is_ok(), "{entry}: {result:?}");
+            assert!(peer.trusted);
+        }
+    }
+
+    #[test]
+    fn a_trusted_issuer_still_checks_the_hostname() {
+        let (result, _) = verify(&ssl(&[CA]), "kimai.test");
+        assert!(result.is_ok(), "{result:?}");
+
+        let (result, peer) = verify(&ssl(&[CA]), "other.test");
+        assert!(matches!(
+            result,
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. }
+            ))
+        ));
+        assert!(!peer.trusted);
+    }
+
+    #[test]
+    fn skipping_hostname_verification_accepts_other_names_only() {
+        let (result, peer) = verify(&SslSettings { verify_hostname: false, ..ssl(&[CA]) }, "other.test");
+        assert!(result.is_ok(), "{result:?}");
+        assert!(peer.trusted);
+
+        // The issuer must still be trusted
+        let (result, _) = verify(&SslSettings { verify_hostname: false, ..ssl(&[]) }, "kimai.test");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn ignoring_errors_accepts_any_certificate() {
+        let ssl = SslSettings { ignore_ssl_errors: true, ..ssl(&[]) };
+
+        let (result, peer) = verify(&ssl, "other.test");
+
+        assert!(result.is_ok(), "{result:?}");
+        assert!(peer.trusted);
+        assert!(peer.error.is_none());
+    }
+}
@@ -1,18 +1,159 @@
-// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
-}
-
+mod history;
+mod kimai;
+mod profiles;
+mod secrets;
+mod settings;
+mod timer;
+#[cfg(target_os = "macos")]
 mod tray;
 
+use tauri::{Emitter, Manager};
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_positioner::init())
-        .invoke_handler(tauri::generate_handler![greet])
+        .manage(kimai::KimaiState::default())
+        .manage(kimai::pagination::TimesheetWalks::default())
+        .manage(kimai::switch::QueuedStarts::default())
+        .invoke_handler(tauri::generate_handler![
+            history::commands::history_upsert_timesheets,
+            history::commands::history_delete_timesheets,
+            history::commands::history_timesheets,
+            history::commands::history_summary,
+            history::commands::history_record_event,
+            history::commands::history_events,
+            kimai::commands::kimai_connect,
+            kimai::commands::kimai_inspect_certificate,
+            kimai::commands::kimai_disconnect,
+            kimai::commands::kimai_get_version,
+            kimai::commands::kimai_get_capabilities,
+            kimai::commands::kimai_get_current_user,
+            kimai::commands::kimai_list_meta_fields,
+            kimai::commands::kimai_validate_meta_fields,
+            kimai::commands::kimai_load_cache,
+            kimai::commands::kimai_refresh_cache,
+            kimai::commands::kimai_list_customers,
+            kimai::commands::kimai_get_customer,
+            kimai::commands::kimai_create_customer,
+            kimai::commands::kimai_update_customer,
+            kimai::commands::kimai_delete_customer,
+            kimai::commands::kimai_list_projects,
+            kimai::commands::kimai_get_project,
+            kimai::commands::kimai_create_project,
+            kimai::commands::kimai_update_project,
+            kimai::commands::kimai_delete_project,
+            kimai::commands::kimai_list_activities,
+            kimai::commands::kimai_get_activity,
+            kimai::commands::kimai_create_activity,
+            kimai::commands::kimai_update_activity,
+            kimai::commands::kimai_delete_activity,
+            kimai::commands::kimai_list_timesheets,
+            kimai::commands::kimai_walk_timesheets,
+            kimai::commands::kimai_cancel_timesheet_walk,
+            kimai::commands::kimai_list_active_timesheets,
+            kimai::commands::kimai_get_timesheet,
+            kimai::commands::kimai_get_timesheet_config,
+            kimai::commands::kimai_validate_timesheet,
+            kimai::commands::kimai_create_timesheet,
+            kimai::commands::kimai_update_timesheet,
+            kimai::commands::kimai_delete_timesheet,
+            kimai::commands::kimai_switch_timesheet,
+            kimai::commands::kimai_cancel_queued_start,
+            kimai::commands::kimai_list_tasks,
+            kimai::commands::kimai_get_task,
+            kimai::commands::kimai_create_task,
+            kimai::commands::kimai_update_task,
+            kimai::commands::kimai_delete_task,
+            kimai::commands::kimai_task_action,
+            profiles::commands::profiles_delete,
+            profiles::commands::profiles_purge,
+            secrets::commands::secrets_status,
+            secrets::commands::secrets_unlock,
+            secrets::commands::secrets_lock,
+            settings::commands::settings_get,
+            settings::commands::settings_patch,
+            settings::commands::settings_import_local_storage,
+            settings::commands::settings_export_bundle,
+            settings::commands::settings_bundle_has_secrets,
+            settings::commands::settings_import_bundle,
+            settings::commands::settings_kemai_default_path,
+            settings::commands::settings_kemai_preview,
+            settings::commands::settings_kemai_import,
+            timer::commands::timer_state,
+            timer::commands::timer_start,
+            timer::commands::timer_continue,
+            timer::commands::timer_pause,
+            timer::commands::timer_resume,
+            timer::commands::timer_stop,
+            timer::commands::timer_reset,
+            timer::commands::timer_update_entry,
+            timer::commands::timer_split,
+            timer::commands::timer_preview_bookings,
+            timer::commands::timer_preview_rounding,
+            timer::commands::timer_book,
+            timer::commands::timer_recover,
+            timer::commands::timer_recovery_resume,
+            timer::commands::timer_recovery_stop,
+            timer::commands::timer_recovery_discard,
+        ])
         .setup(|app| {
+            // Settings are needed before the first window shows, e.g. to start minimized
+            let config_dir = app.path().app_config_dir()?;
+            let secrets = secrets::SecretStore::open(secrets::SecretStore::path(&app.path().app_data_dir()?));
+            let store = secrets.clone();
+            let settings = settings::SettingsState::load(settings::SettingsState::path(&config_dir))
+                .with_before_save(move |previous, next| {
+                    Ok(store.protect_profiles(&previous.profiles, &mut next.profiles)?)
+                });
+            // Moves credentials saved by older versions out of the file; a
+            // locked encrypted store retries once it is unlocked
+            if let Err(err) = settings.resave() {
+                eprintln!("Credentials stay in the settings file for now: {err}");
+            }
+            if settings.get().ui.tray_behavior.start_minimized {
+                if let Some(window) = app.get_webview_window("main") {
+                    window.hide()?;
+                }
+            }
+            app.manage(settings);
+            app.manage(secrets);
+
+            let journal = timer::TimerJournal::open(timer::TimerJournal::path(&app.path().app_data_dir()?));
+            journal.spawn_heartbeat();
+            let handle = app.handle().clone();
+            let engine = timer::TimerEngine::new(journal.clone()).on_change(move |snapshot| {
+                let _ = handle.emit(timer::engine::TIMER_STATE_EVENT, snapshot);
+                #[cfg(target_os = "macos")]
+                tray::show_elapsed(&handle, snapshot.is_running.then_some(snapshot.elapsed_time as i64));
+            });
+            let handle = app.handle().clone();
+            let engine = engine.on_auto_stop(move |notice| {
+                let _ = handle.emit(timer::autostop::TIMER_AUTO_STOP_EVENT, notice);
+                if notice.kind == timer::autostop::AutoStopNoticeKind::Stopped {
+                    let handle = handle.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let (engine, kimai) = (handle.state::<timer::TimerEngine>(), handle.state::<kimai::KimaiState>());
+                        if let Err(err) = timer::booking::submit_unsubmitted(&engine, &kimai).await {
+                            eprintln!("{err}");
+                        }
+                        timer::commands::spawn_booking_retry(&handle);
+                    });
+                }
+            });
+            let handle = app.handle().clone();
+            engine.spawn_ticker(move |tick| {
+                let _ = handle.emit(timer::engine::TIMER_TICK_EVENT, tick);
+                #[cfg(target_os = "macos")]
+                tray::show_elapsed(&handle, Some(tick.elapsed));
+            });
+            app.manage(journal);
+            app.manage(engine);
+
+            let history = history::HistoryStore::open(&history::HistoryStore::path(&app.path().app_data_dir()?))?;
+            app.manage(history);
+
             #[cfg(target_os = "macos")]
             {
                 tray::init_macos_menu_extra(app.handle())?;
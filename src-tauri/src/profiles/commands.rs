@@ -0,0 +1,44 @@
+// Profile Tauri commands
+
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use super::{ProfileError, ProfileResult};
+use crate::settings::{AppSettings, SettingsState, SETTINGS_CHANGED_EVENT};
+
+/// Deletes a profile and securely purges its cache, history, timer journal
+/// and credential. Returns the updated settings.
+#[tauri::command]
+pub fn profiles_delete<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SettingsState>,
+    profile_id: String,
+) -> ProfileResult<AppSettings> {
+    if !state.get().profiles.iter().any(|profile| profile.id == profile_id) {
+        return Err(ProfileError::NotFound(profile_id));
+    }
+    let (settings, ()) = state.update(|settings| {
+        settings.profiles.retain(|profile| profile.id != profile_id);
+        if settings.current_profile().is_none() {
+            settings.current_profile_id = settings.profiles.first().map(|profile| profile.id.clone());
+        }
+        Ok(())
+    })?;
+    let _ = app.emit(SETTINGS_CHANGED_EVENT, &settings);
+
+    super::purge(&app, &profile_id)?;
+    Ok(settings)
+}
+
+/// Purges what is left of a profile that no longer exists in the settings,
+/// e.g. after a failed purge
+#[tauri::command]
+pub fn profiles_purge<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SettingsState>,
+    profile_id: String,
+) -> ProfileResult<()> {
+    if state.get().profiles.iter().any(|profile| profile.id == profile_id) {
+        return Err(ProfileError::InUse(profile_id));
+    }
+    super::purge(&app, &profile_id)
+}
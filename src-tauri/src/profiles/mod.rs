@@ -0,0 +1,146 @@
+// Profile data
+// Everything kept locally for a profile is stored under its id: the entity
+// cache, history rows, the timer journal and the credential in the secret
+// store. Deleting a profile purges all of it, overwriting files before they
+// are removed, since one machine may hold data of several clients.
+
+pub mod commands;
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Serialize, Serializer};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::history::{HistoryError, HistoryStore};
+use crate::kimai::cache::KimaiCache;
+use crate::kimai::commands::history_walk_id;
+use crate::kimai::pagination::TimesheetWalks;
+use crate::kimai::switch::QueuedStarts;
+use crate::kimai::KimaiState;
+use crate::settings::{AppSettings, SettingsError};
+use crate::timer::{TimerError, TimerJournal};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("No profile with id {0}")]
+    NotFound(String),
+    #[error("Profile {0} still exists; delete it instead")]
+    InUse(String),
+    #[error("Could not remove the profile's files: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Settings(#[from] SettingsError),
+    #[error(transparent)]
+    History(#[from] HistoryError),
+    #[error(transparent)]
+    Timer(#[from] TimerError),
+}
+
+impl ProfileError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProfileError::NotFound(_) => "not_found",
+            ProfileError::InUse(_) => "in_use",
+            ProfileError::Io(_) => "io",
+            ProfileError::Settings(err) => err.kind(),
+            ProfileError::History(err) => err.kind(),
+            ProfileError::Timer(err) => err.kind(),
+        }
+    }
+}
+
+impl Serialize for ProfileError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorPayload {
+            kind: &'static str,
+            message: String,
+        }
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type ProfileResult<T> = Result<T, ProfileError>;
+
+/// Removes everything stored for `profile_id` except its credential, which
+/// the settings hook deletes once the profile is gone from the settings.
+pub fn purge<R: Runtime>(app: &AppHandle<R>, profile_id: &str) -> ProfileResult<()> {
+    app.state::<KimaiState>().remove(profile_id);
+    // A start queued by a switch would otherwise keep retrying for it
+    app.state::<QueuedStarts>().cancel(profile_id);
+    app.state::<TimesheetWalks>().cancel(&history_walk_id(profile_id));
+
+    let cache_dir = app.path().app_cache_dir().map_err(|err| io::Error::other(err.to_string()))?;
+    // Ids that can't name a file never had a cache
+    if let Ok(cache) = KimaiCache::path(&cache_dir, profile_id) {
+        remove_securely(&cache)?;
+        remove_securely(&cache.with_extension("json.tmp"))?;
+    }
+
+    app.state::<HistoryStore>().purge_profile(profile_id)?;
+    app.state::<TimerJournal>().purge_profile(profile_id)?;
+    Ok(())
+}
+
+/// Purges the profiles present in `previous` but not in `current`. Every
+/// profile is attempted; the first failure is returned.
+pub fn purge_removed<R: Runtime>(app: &AppHandle<R>, previous: &AppSettings, current: &AppSettings) -> ProfileResult<()> {
+    let mut result = Ok(());
+    for profile in &previous.profiles {
+        if current.profiles.iter().any(|kept| kept.id == profile.id) {
+            continue;
+        }
+        if let Err(err) = purge(app, &profile.id) {
+            eprintln!("Could not purge the data of profile {}: {err}", profile.id);
+            result = result.and(Err(err));
+        }
+    }
+    result
+}
+
+/// Overwrites a file with zeros and syncs it before removing it, so its
+/// contents don't linger in free blocks. A missing file is not an error.
+pub fn remove_securely(path: &Path) -> io::Result<()> {
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 8192];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secure_removal_tolerates_missing_files() {
+        let dir = std::env::temp_dir().join(format!("tikker-profiles-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cache.json");
+        fs::write(&path, vec![b'x'; 10_000]).unwrap();
+
+        remove_securely(&path).unwrap();
+        assert!(!path.exists());
+        remove_securely(&path).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+// Secrets Tauri commands
+// Only status and locking are exposed; credentials never leave Rust.
+
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use super::{SecretResult, SecretStatus, SecretStore};
+use crate::settings::{SettingsResult, SettingsState, SETTINGS_CHANGED_EVENT};
+
+#[tauri::command]
+pub fn secrets_status(store: State<'_, SecretStore>) -> SecretStatus {
+    store.status()
+}
+
+/// Unlocks the encrypted file (choosing the passphrase on first use), then
+/// moves any credentials still kept in the settings into it.
+#[tauri::command]
+pub fn secrets_unlock<R: Runtime>(
+    app: AppHandle<R>,
+    store: State<'_, SecretStore>,
+    settings: State<'_, SettingsState>,
+    passphrase: String,
+) -> SettingsResult<SecretStatus> {
+    store.unlock(&passphrase)?;
+    if let Some(updated) = settings.resave()? {
+        let _ = app.emit(SETTINGS_CHANGED_EVENT, &updated);
+    }
+    Ok(store.status())
+}
+
+#[tauri::command]
+pub fn secrets_lock(store: State<'_, SecretStore>) -> SecretResult<SecretStatus> {
+    store.lock();
+    Ok(store.status())
+}
@@ -0,0 +1,253 @@
+// Encrypted file backend
+// For systems without a secret service. Each entry is sealed with
+// XChaCha20-Poly1305 under a key derived from the user's passphrase (Argon2id);
+// the key only lives in memory while the store is unlocked.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use super::{SecretError, SecretResult};
+
+const FILE_VERSION: u32 = 1;
+/// Sealed at creation so a wrong passphrase is detected even without entries
+const CHECK_PLAINTEXT: &[u8] = b"tikker-secrets";
+const CHECK_AAD: &[u8] = b"check";
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SecretsFile {
+    version: u32,
+    salt: String,
+    check: Sealed,
+    entries: BTreeMap<String, Sealed>,
+}
+
+struct Unlocked {
+    cipher: XChaCha20Poly1305,
+    file: SecretsFile,
+}
+
+pub struct EncryptedFile {
+    path: PathBuf,
+    unlocked: Mutex<Option<Unlocked>>,
+}
+
+impl EncryptedFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            unlocked: Mutex::new(None),
+        }
+    }
+
+    /// Whether a passphrase has been chosen yet
+    pub fn is_initialized(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.unlocked.lock().unwrap().is_none()
+    }
+
+    /// Opens the file with `passphrase`, or creates it with that passphrase
+    /// when it doesn't exist yet.
+    pub fn unlock(&self, passphrase: &str) -> SecretResult<()> {
+        let unlocked = if self.is_initialized() {
+            let file: SecretsFile =
+                serde_json::from_slice(&fs::read(&self.path)?).map_err(|err| SecretError::Corrupt(err.to_string()))?;
+            if file.version != FILE_VERSION {
+                return Err(SecretError::Corrupt(format!("unsupported version {}", file.version)));
+            }
+
+            let cipher = derive_cipher(passphrase, &decode(&file.salt)?)?;
+            open(&cipher, &file.check, CHECK_AAD).map_err(|_| SecretError::WrongPassphrase)?;
+            Unlocked { cipher, file }
+        } else {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let cipher = derive_cipher(passphrase, &salt)?;
+            let file = SecretsFile {
+                version: FILE_VERSION,
+                salt: STANDARD.encode(salt),
+                check: seal(&cipher, CHECK_PLAINTEXT, CHECK_AAD)?,
+                entries: BTreeMap::new(),
+            };
+            self.write(&file)?;
+            Unlocked { cipher, file }
+        };
+
+        *self.unlocked.lock().unwrap() = Some(unlocked);
+        Ok(())
+    }
+
+    pub fn lock(&self) {
+        *self.unlocked.lock().unwrap() = None;
+    }
+
+    pub fn get(&self, reference: &str) -> SecretResult<Option<String>> {
+        let guard = self.unlocked.lock().unwrap();
+        let unlocked = guard.as_ref().ok_or(SecretError::Locked)?;
+        let Some(sealed) = unlocked.file.entries.get(reference) else {
+            return Ok(None);
+        };
+
+        let plaintext = open(&unlocked.cipher, sealed, reference.as_bytes())?;
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|err| SecretError::Corrupt(err.to_string()))
+    }
+
+    pub fn set(&self, reference: &str, secret: &str) -> SecretResult<()> {
+        let mut guard = self.unlocked.lock().unwrap();
+        let unlocked = guard.as_mut().ok_or(SecretError::Locked)?;
+
+        let mut file = unlocked.file.clone();
+        let sealed = seal(&unlocked.cipher, secret.as_bytes(), reference.as_bytes())?;
+        file.entries.insert(reference.to_string(), sealed);
+        self.write(&file)?;
+        unlocked.file = file;
+        Ok(())
+    }
+
+    pub fn delete(&self, reference: &str) -> SecretResult<()> {
+        let mut guard = self.unlocked.lock().unwrap();
+        let unlocked = guard.as_mut().ok_or(SecretError::Locked)?;
+
+        let mut file = unlocked.file.clone();
+        if file.entries.remove(reference).is_some() {
+            self.write(&file)?;
+            unlocked.file = file;
+        }
+        Ok(())
+    }
+
+    /// Writes to a temporary file first so a crash never leaves half a file behind.
+    fn write(&self, file: &SecretsFile) -> SecretResult<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_vec_pretty(file).map_err(|err| SecretError::Corrupt(err.to_string()))?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Secrets sealed under a passphrase of their own, independent of the
+/// secrets file, so they can travel in a settings bundle
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SealedSecrets {
+    salt: String,
+    check: Sealed,
+    entries: BTreeMap<String, Sealed>,
+}
+
+impl SealedSecrets {
+    pub fn seal(passphrase: &str, secrets: &BTreeMap<String, String>) -> SecretResult<Self> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = derive_cipher(passphrase, &salt)?;
+        let entries = secrets
+            .iter()
+            .map(|(key, secret)| Ok((key.clone(), seal(&cipher, secret.as_bytes(), key.as_bytes())?)))
+            .collect::<SecretResult<_>>()?;
+        Ok(Self {
+            salt: STANDARD.encode(salt),
+            check: seal(&cipher, CHECK_PLAINTEXT, CHECK_AAD)?,
+            entries,
+        })
+    }
+
+    pub fn open(&self, passphrase: &str) -> SecretResult<BTreeMap<String, String>> {
+        let cipher = derive_cipher(passphrase, &decode(&self.salt)?)?;
+        open(&cipher, &self.check, CHECK_AAD).map_err(|_| SecretError::WrongPassphrase)?;
+        self.entries
+            .iter()
+            .map(|(key, sealed)| {
+                let secret = open(&cipher, sealed, key.as_bytes())?;
+                let secret = String::from_utf8(secret).map_err(|err| SecretError::Corrupt(err.to_string()))?;
+                Ok((key.clone(), secret))
+            })
+            .collect()
+    }
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8]) -> SecretResult<XChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| SecretError::Corrupt(err.to_string()))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+/// Encrypts `plaintext`, binding it to `aad` (the entry's reference) so
+/// entries can't be swapped around in the file.
+fn seal(cipher: &XChaCha20Poly1305, plaintext: &[u8], aad: &[u8]) -> SecretResult<Sealed> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| SecretError::Corrupt("encryption failed".into()))?;
+    Ok(Sealed {
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+fn open(cipher: &XChaCha20Poly1305, sealed: &Sealed, aad: &[u8]) -> SecretResult<Vec<u8>> {
+    let nonce = decode(&sealed.nonce)?;
+    if nonce.len() != 24 {
+        return Err(SecretError::Corrupt("invalid nonce".into()));
+    }
+    let ciphertext = decode(&sealed.ciphertext)?;
+    cipher
+        .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad })
+        .map_err(|_| SecretError::Corrupt("entry could not be decrypted".into()))
+}
+
+fn decode(value: &str) -> SecretResult<Vec<u8>> {
+    STANDARD.decode(value).map_err(|err| SecretError::Corrupt(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_survive_lock_and_reject_wrong_passphrases() {
+        let dir = std::env::temp_dir().join(format!("tikker-secrets-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = EncryptedFile::new(dir.join("secrets.json"));
+        assert!(!store.is_initialized());
+
+        store.unlock("correct horse").unwrap();
+        store.set("ref-1", "api-token").unwrap();
+        store.lock();
+        assert!(matches!(store.get("ref-1"), Err(SecretError::Locked)));
+
+        assert!(matches!(store.unlock("wrong"), Err(SecretError::WrongPassphrase)));
+        store.unlock("correct horse").unwrap();
+        assert_eq!(store.get("ref-1").unwrap().as_deref(), Some("api-token"));
+
+        store.delete("ref-1").unwrap();
+        assert_eq!(store.get("ref-1").unwrap(), None);
+
+        let raw = fs::read_to_string(dir.join("secrets.json")).unwrap();
+        assert!(!raw.contains("api-token"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
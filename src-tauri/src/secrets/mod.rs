@@ -0,0 +1,264 @@
+// Secrets
+// Profile credentials live in the OS secret store (Secret Service on Linux,
+// Keychain, Credential Manager), or in a passphrase-encrypted file where none
+// is available. Settings only keep an opaque reference, and credentials are
+// never sent back to the webview.
+
+pub mod commands;
+mod file;
+
+pub use file::SealedSecrets;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::{Serialize, Serializer};
+
+use crate::kimai::models::{KimaiAuthConfig, KimaiProfile};
+
+/// Service name entries are filed under in the OS secret store
+const SERVICE: &str = "tikker";
+const SECRETS_FILE: &str = "secrets.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("The secret store is locked")]
+    Locked,
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("No stored credential for this profile")]
+    Missing,
+    #[error("Secret service error: {0}")]
+    Keyring(#[from] keyring::Error),
+    #[error("Could not access the secrets file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The secrets file is damaged: {0}")]
+    Corrupt(String),
+}
+
+impl SecretError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            // Same kind as the Kimai commands report, so one check prompts for the passphrase
+            SecretError::Locked => "secret_locked",
+            SecretError::WrongPassphrase => "wrong_passphrase",
+            SecretError::Missing => "missing",
+            SecretError::Keyring(_) => "keyring",
+            SecretError::Io(_) => "io",
+            SecretError::Corrupt(_) => "corrupt",
+        }
+    }
+}
+
+impl Serialize for SecretError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorPayload {
+            kind: &'static str,
+            message: String,
+        }
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type SecretResult<T> = Result<T, SecretError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackendKind {
+    Keyring,
+    EncryptedFile,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretStatus {
+    pub backend: SecretBackendKind,
+    /// The encrypted file needs a passphrase before credentials can be used
+    pub locked: bool,
+    /// False until a passphrase has been chosen for the encrypted file
+    pub initialized: bool,
+}
+
+enum Backend {
+    Keyring,
+    File(file::EncryptedFile),
+}
+
+/// Cheap to clone; clones share the backend and its unlocked state
+#[derive(Clone)]
+pub struct SecretStore {
+    backend: Arc<Backend>,
+}
+
+impl SecretStore {
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(SECRETS_FILE)
+    }
+
+    /// Uses the OS secret store when it answers, the encrypted file at
+    /// `file_path` otherwise (headless sessions, desktops without a keyring).
+    pub fn open(file_path: PathBuf) -> Self {
+        let backend = if keyring_available() {
+            Backend::Keyring
+        } else {
+            Backend::File(file::EncryptedFile::new(file_path))
+        };
+        Self {
+            backend: Arc::new(backend),
+        }
+    }
+
+    pub fn status(&self) -> SecretStatus {
+        match self.backend.as_ref() {
+            Backend::Keyring => SecretStatus {
+                backend: SecretBackendKind::Keyring,
+                locked: false,
+                initialized: true,
+            },
+            Backend::File(file) => SecretStatus {
+                backend: SecretBackendKind::EncryptedFile,
+                locked: file.is_locked(),
+                initialized: file.is_initialized(),
+            },
+        }
+    }
+
+    /// Unlocks the encrypted file, choosing `passphrase` on first use.
+    /// The OS secret store needs no passphrase.
+    pub fn unlock(&self, passphrase: &str) -> SecretResult<()> {
+        match self.backend.as_ref() {
+            Backend::Keyring => Ok(()),
+            Backend::File(file) => file.unlock(passphrase),
+        }
+    }
+
+    pub fn lock(&self) {
+        if let Backend::File(file) = self.backend.as_ref() {
+            file.lock();
+        }
+    }
+
+    pub fn get(&self, reference: &str) -> SecretResult<String> {
+        let secret = match self.backend.as_ref() {
+            Backend::Keyring => match keyring::Entry::new(SERVICE, reference)?.get_password() {
+                Ok(secret) => Some(secret),
+                Err(keyring::Error::NoEntry) => None,
+                Err(err) => return Err(err.into()),
+            },
+            Backend::File(file) => file.get(reference)?,
+        };
+        secret.ok_or(SecretError::Missing)
+    }
+
+    pub fn set(&self, reference: &str, secret: &str) -> SecretResult<()> {
+        match self.backend.as_ref() {
+            Backend::Keyring => Ok(keyring::Entry::new(SERVICE, reference)?.set_password(secret)?),
+            Backend::File(file) => file.set(reference, secret),
+        }
+    }
+
+    pub fn delete(&self, reference: &str) -> SecretResult<()> {
+        match self.backend.as_ref() {
+            Backend::Keyring => match keyring::Entry::new(SERVICE, reference)?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(err) => Err(err.into()),
+            },
+            Backend::File(file) => file.delete(reference),
+        }
+    }
+
+    /// Moves plaintext credentials of `profiles` into the store, leaving a
+    /// reference behind, and drops the secrets of profiles that were removed.
+    pub fn protect_profiles(&self, previous: &[KimaiProfile], profiles: &mut [KimaiProfile]) -> SecretResult<()> {
+        for profile in profiles.iter_mut() {
+            let auth = &mut profile.auth;
+            let Some(credential) = auth.credential().map(str::to_string) else {
+                // A blank field while editing keeps the stored credential
+                auth.api_token = None;
+                auth.password = None;
+                continue;
+            };
+
+            let reference = auth.secret_ref.clone().unwrap_or_else(new_reference);
+            self.set(&reference, &credential)?;
+            auth.api_token = None;
+            auth.password = None;
+            auth.secret_ref = Some(reference);
+        }
+
+        let kept: HashSet<_> = profiles.iter().filter_map(|profile| profile.auth.secret_ref.as_deref()).collect();
+        for reference in previous.iter().filter_map(|profile| profile.auth.secret_ref.as_deref()) {
+            if !kept.contains(reference) {
+                // A leftover entry is harmless, so removal is best effort
+                let _ = self.delete(reference);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the credential of `auth` from its secret reference, for use
+    /// on the Rust side only.
+    pub fn resolve(&self, auth: &mut KimaiAuthConfig) -> SecretResult<()> {
+        if auth.credential().is_some() {
+            return Ok(());
+        }
+        if let Some(reference) = &auth.secret_ref {
+            let credential = self.get(reference)?;
+            auth.set_credential(Some(credential));
+        }
+        Ok(())
+    }
+}
+
+/// The secret service can be missing or refuse access (no D-Bus session,
+/// no keyring daemon); a missing entry still means it works.
+fn keyring_available() -> bool {
+    let probe = keyring::Entry::new(SERVICE, "availability-check").and_then(|entry| entry.get_password());
+    matches!(probe, Ok(_) | Err(keyring::Error::NoEntry))
+}
+
+fn new_reference() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("profile-{hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_keep_only_a_reference_to_their_credential() {
+        let dir = std::env::temp_dir().join(format!("tikker-secret-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = SecretStore {
+            backend: Arc::new(Backend::File(file::EncryptedFile::new(SecretStore::path(&dir)))),
+        };
+        store.unlock("passphrase").unwrap();
+
+        let mut profiles = vec![crate::kimai::test_server::profile("https://kimai.example")];
+        store.protect_profiles(&[], &mut profiles).unwrap();
+
+        let saved = serde_json::to_string(&profiles).unwrap();
+        assert!(!saved.contains("secret-token"));
+        let mut auth = profiles[0].auth.clone();
+        store.resolve(&mut auth).unwrap();
+        assert_eq!(auth.credential(), Some("secret-token"));
+
+        let reference = profiles[0].auth.secret_ref.clone().unwrap();
+        store.protect_profiles(&profiles, &mut []).unwrap();
+        assert!(matches!(store.get(&reference), Err(SecretError::Missing)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
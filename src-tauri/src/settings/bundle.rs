@@ -0,0 +1,262 @@
+// Settings bundles
+// The settings and profiles in one file, to hand a setup to someone else.
+// Credentials are left out unless sealed with a passphrase chosen at export.
+// Window geometry belongs to the machine and is never taken from a bundle.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::migrations;
+use super::{AppSettings, SettingsError, SettingsResult};
+use crate::kimai::models::{KimaiAuthConfig, KimaiProfile};
+use crate::secrets::{SealedSecrets, SecretResult};
+
+const BUNDLE_FORMAT: &str = "tikker-settings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Adds the bundle's profiles, replacing those with the same id, and
+    /// takes its preferences
+    Merge,
+    /// Swaps the settings and profiles for the bundle's
+    Replace,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsBundle {
+    format: String,
+    exported_at: DateTime<Utc>,
+    /// Settings with their schemaVersion, migrated on import like the settings file
+    settings: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secrets: Option<SealedSecrets>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleImport {
+    pub settings: AppSettings,
+    pub imported_profiles: usize,
+    pub imported_credentials: usize,
+    /// New profiles that came without a credential
+    pub missing_credentials: Vec<String>,
+}
+
+impl SettingsBundle {
+    /// Bundles `settings` without credentials, or with them sealed under
+    /// `passphrase`. `resolve` fills in a profile's stored credential.
+    pub fn export(
+        settings: &AppSettings,
+        passphrase: Option<&str>,
+        mut resolve: impl FnMut(&mut KimaiAuthConfig) -> SecretResult<()>,
+    ) -> SettingsResult<Self> {
+        let mut settings = settings.clone();
+        let mut credentials = BTreeMap::new();
+        for profile in &mut settings.profiles {
+            if passphrase.is_some() {
+                resolve(&mut profile.auth)?;
+                if let Some(credential) = profile.auth.credential() {
+                    credentials.insert(profile.id.clone(), credential.to_string());
+                }
+            }
+            profile.auth.api_token = None;
+            profile.auth.password = None;
+            profile.auth.secret_ref = None;
+        }
+
+        let mut value = serde_json::to_value(&settings)?;
+        value["schemaVersion"] = json!(migrations::SCHEMA_VERSION);
+        Ok(Self {
+            format: BUNDLE_FORMAT.into(),
+            exported_at: Utc::now(),
+            settings: value,
+            secrets: passphrase
+                .map(|passphrase| SealedSecrets::seal(passphrase, &credentials))
+                .transpose()?,
+        })
+    }
+
+    /// Also accepts the plain settings JSON older versions exported
+    pub fn parse(data: &str) -> SettingsResult<Self> {
+        let value: Value = serde_json::from_str(data)?;
+        if value.get("format").and_then(Value::as_str) == Some(BUNDLE_FORMAT) {
+            return Ok(serde_json::from_value(value)?);
+        }
+        if value.get("profiles").is_some_and(Value::is_array) {
+            return Ok(Self {
+                format: BUNDLE_FORMAT.into(),
+                exported_at: Utc::now(),
+                settings: value,
+                secrets: None,
+            });
+        }
+        Err(SettingsError::NotABundle)
+    }
+
+    pub fn has_secrets(&self) -> bool {
+        self.secrets.is_some()
+    }
+
+    /// Applies the bundle to `current`. Credentials are only imported with
+    /// the export passphrase; without them, a profile already configured
+    /// under the same id keeps its own.
+    pub fn apply(self, current: &mut AppSettings, mode: ImportMode, passphrase: Option<&str>) -> SettingsResult<BundleImport> {
+        let mut value = self.settings;
+        let version = migrations::schema_version(&value);
+        if version > migrations::SCHEMA_VERSION {
+            return Err(SettingsError::NewerSchema(version));
+        }
+        migrations::migrate(&mut value);
+        let mut incoming: AppSettings = serde_json::from_value(value)?;
+        if let Some(profile) = incoming.profiles.iter().find(|profile| !KimaiProfile::is_valid_id(&profile.id)) {
+            return Err(SettingsError::InvalidProfileId(profile.id.clone()));
+        }
+
+        let credentials = match (&self.secrets, passphrase) {
+            (Some(secrets), Some(passphrase)) => secrets.open(passphrase)?,
+            _ => BTreeMap::new(),
+        };
+        let mut imported_credentials = 0;
+        let mut missing_credentials = Vec::new();
+        for profile in &mut incoming.profiles {
+            let auth = &mut profile.auth;
+            auth.api_token = None;
+            auth.password = None;
+            auth.secret_ref = None;
+            let existing = current.profiles.iter().find(|existing| existing.id == profile.id);
+
+            if let Some(credential) = credentials.get(&profile.id) {
+                auth.set_credential(Some(credential.clone()));
+                imported_credentials += 1;
+            } else if let Some(existing) = existing {
+                auth.secret_ref = existing.auth.secret_ref.clone();
+            } else {
+                missing_credentials.push(profile.name.clone());
+            }
+        }
+        let imported_profiles = incoming.profiles.len();
+
+        incoming.ui.geometry = current.ui.geometry;
+        if mode == ImportMode::Merge {
+            let mut profiles = current.profiles.clone();
+            for profile in incoming.profiles {
+                match profiles.iter_mut().find(|existing| existing.id == profile.id) {
+                    Some(existing) => *existing = profile,
+                    None => profiles.push(profile),
+                }
+            }
+            incoming.profiles = profiles;
+            if current.current_profile().is_some() {
+                incoming.current_profile_id = current.current_profile_id.clone();
+            }
+        }
+        if incoming.current_profile().is_none() {
+            incoming.current_profile_id = incoming.profiles.first().map(|profile| profile.id.clone());
+        }
+
+        *current = incoming;
+        Ok(BundleImport {
+            settings: current.clone(),
+            imported_profiles,
+            imported_credentials,
+            missing_credentials,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::secrets::SecretError;
+
+    fn settings() -> AppSettings {
+        serde_json::from_value(json!({
+            "profiles": [
+                {"id": "work", "name": "Work", "auth": {"type": "api_token", "baseUrl": "https://kimai.example", "secretRef": "profile-work"}},
+                {"id": "home", "name": "Home", "auth": {"type": "legacy", "username": "me", "baseUrl": "https://home.example", "secretRef": "profile-home"}}
+            ],
+            "currentProfileId": "home",
+            "events": {"idleTimeout": 15}
+        }))
+        .unwrap()
+    }
+
+    fn resolve(auth: &mut KimaiAuthConfig) -> SecretResult<()> {
+        let credential = auth.secret_ref.as_deref().map(|reference| format!("secret-of-{reference}"));
+        auth.set_credential(credential);
+        Ok(())
+    }
+
+    #[test]
+    fn exports_leave_credentials_out_unless_sealed() {
+        let plain = serde_json::to_string(&SettingsBundle::export(&settings(), None, resolve).unwrap()).unwrap();
+        assert!(!plain.contains("secret-of") && !plain.contains("profile-work"));
+
+        let sealed = serde_json::to_string(&SettingsBundle::export(&settings(), Some("onboarding"), resolve).unwrap()).unwrap();
+        assert!(!sealed.contains("secret-of"));
+
+        let bundle = SettingsBundle::parse(&sealed).unwrap();
+        let mut current = AppSettings::default();
+        assert!(matches!(
+            SettingsBundle::parse(&sealed).unwrap().apply(&mut current, ImportMode::Replace, Some("wrong")),
+            Err(SettingsError::Secret(SecretError::WrongPassphrase))
+        ));
+
+        let imported = bundle.apply(&mut current, ImportMode::Replace, Some("onboarding")).unwrap();
+        assert_eq!(imported.imported_credentials, 2);
+        assert_eq!(current.profiles[0].auth.credential(), Some("secret-of-profile-work"));
+        assert_eq!(current.profiles[1].auth.credential(), Some("secret-of-profile-home"));
+        assert_eq!(current.current_profile_id.as_deref(), Some("home"));
+        assert_eq!(current.events.idle_timeout, 15);
+    }
+
+    #[test]
+    fn merging_keeps_local_profiles_and_their_credentials() {
+        let bundle = serde_json::to_string(&SettingsBundle::export(&settings(), None, resolve).unwrap()).unwrap();
+        let mut current: AppSettings = serde_json::from_value(json!({
+            "profiles": [
+                {"id": "work", "name": "Old name", "auth": {"type": "api_token", "baseUrl": "https://kimai.example", "secretRef": "local-ref"}},
+                {"id": "mine", "name": "Mine", "auth": {"type": "api_token", "baseUrl": "https://mine.example"}}
+            ],
+            "currentProfileId": "mine",
+            "ui": {"geometry": {"x": 5.0}}
+        }))
+        .unwrap();
+
+        let imported = SettingsBundle::parse(&bundle).unwrap().apply(&mut current, ImportMode::Merge, None).unwrap();
+        assert_eq!(imported.missing_credentials, ["Home"]);
+        let ids: Vec<_> = current.profiles.iter().map(|profile| profile.id.as_str()).collect();
+        assert_eq!(ids, ["work", "mine", "home"]);
+        assert_eq!(current.profiles[0].name, "Work");
+        assert_eq!(current.profiles[0].auth.secret_ref.as_deref(), Some("local-ref"));
+        assert_eq!(current.current_profile_id.as_deref(), Some("mine"));
+        assert_eq!(current.ui.geometry.x, 5.0);
+    }
+
+    #[test]
+    fn plain_settings_exports_are_accepted() {
+        let mut current = AppSettings::default();
+        let legacy = json!({"profiles": [], "events": {"idleTimeout": 20}}).to_string();
+        SettingsBundle::parse(&legacy).unwrap().apply(&mut current, ImportMode::Replace, None).unwrap();
+        assert_eq!(current.events.idle_timeout, 20);
+        assert!(matches!(SettingsBundle::parse("{}"), Err(SettingsError::NotABundle)));
+    }
+
+    #[test]
+    fn bundles_with_profile_ids_that_cannot_name_a_file_are_refused() {
+        let mut current = settings();
+        let bundle = json!({"profiles": [{"id": "../../evil", "name": "Evil", "auth": {"type": "api_token", "baseUrl": "https://kimai.example"}}]});
+        assert!(matches!(
+            SettingsBundle::parse(&bundle.to_string()).unwrap().apply(&mut current, ImportMode::Merge, None),
+            Err(SettingsError::InvalidProfileId(id)) if id == "../../evil"
+        ));
+        assert_eq!(current.profiles.len(), 2);
+    }
+}
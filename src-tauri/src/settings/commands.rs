@@ -0,0 +1,138 @@
+// Settings Tauri commands
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use crate::profiles;
+use crate::secrets::{SecretError, SecretStore};
+
+use super::bundle::{BundleImport, ImportMode, SettingsBundle};
+use super::kemai::{self, KemaiPreview, KemaiSettings};
+use super::{AppSettings, SettingsError, SettingsResult, SettingsState, SETTINGS_CHANGED_EVENT};
+
+#[tauri::command]
+pub fn settings_get(state: State<'_, SettingsState>) -> AppSettings {
+    state.get()
+}
+
+/// Merges `patch` into the settings (JSON merge patch, `null` clears a key)
+/// and broadcasts the result to every window.
+#[tauri::command]
+pub fn settings_patch<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SettingsState>,
+    patch: Value,
+) -> SettingsResult<AppSettings> {
+    let previous = state.get();
+    let settings = state.patch(&patch)?;
+    let _ = app.emit(SETTINGS_CHANGED_EVENT, &settings);
+    // Failures are logged; the data can still be purged with profiles_purge
+    let _ = profiles::purge_removed(&app, &previous, &settings);
+    Ok(settings)
+}
+
+/// One-time import of the settings older versions kept in localStorage.
+/// Returns `None` when settings were already saved to disk.
+#[tauri::command]
+pub fn settings_import_local_storage<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SettingsState>,
+    legacy: Value,
+) -> SettingsResult<Option<AppSettings>> {
+    let imported = state.import_legacy(legacy)?;
+    if let Some(settings) = &imported {
+        let _ = app.emit(SETTINGS_CHANGED_EVENT, settings);
+    }
+    Ok(imported)
+}
+
+/// Exports the settings and profiles as a bundle. Credentials are only
+/// included, sealed, when a passphrase is given.
+#[tauri::command]
+pub fn settings_export_bundle(
+    state: State<'_, SettingsState>,
+    secrets: State<'_, SecretStore>,
+    passphrase: Option<String>,
+) -> SettingsResult<String> {
+    let bundle = SettingsBundle::export(&state.get(), passphrase.as_deref(), |auth| match secrets.resolve(auth) {
+        Err(SecretError::Missing) => Ok(()),
+        result => result,
+    })?;
+    Ok(serde_json::to_string_pretty(&bundle)?)
+}
+
+/// Whether a bundle carries sealed credentials, i.e. importing them needs
+/// the export passphrase
+#[tauri::command]
+pub fn settings_bundle_has_secrets(bundle: String) -> SettingsResult<bool> {
+    Ok(SettingsBundle::parse(&bundle)?.has_secrets())
+}
+
+#[tauri::command]
+pub fn settings_import_bundle<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SettingsState>,
+    bundle: String,
+    mode: ImportMode,
+    passphrase: Option<String>,
+) -> SettingsResult<BundleImport> {
+    let bundle = SettingsBundle::parse(&bundle)?;
+    let previous = state.get();
+    let (settings, imported) = state.update(|settings| bundle.apply(settings, mode, passphrase.as_deref()))?;
+    let _ = app.emit(SETTINGS_CHANGED_EVENT, &settings);
+    let _ = profiles::purge_removed(&app, &previous, &settings);
+    Ok(BundleImport { settings, ..imported })
+}
+
+/// Where Kemai keeps its settings, if that file exists
+#[tauri::command]
+pub fn settings_kemai_default_path() -> Option<String> {
+    kemai::default_path()
+        .filter(|path| path.is_file())
+        .map(|path| path.display().to_string())
+}
+
+/// What importing a Kemai settings file would add, without its tokens.
+/// Reads the default location when no path is given.
+#[tauri::command]
+pub fn settings_kemai_preview(state: State<'_, SettingsState>, path: Option<String>) -> SettingsResult<KemaiPreview> {
+    let path = kemai_path(path)?;
+    let kemai = KemaiSettings::read(&path)?;
+    Ok(kemai.preview(&path, &state.get()))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KemaiImportResult {
+    pub settings: AppSettings,
+    pub added_profile_ids: Vec<String>,
+}
+
+/// Imports a Kemai settings file. Tokens go through the before-save hook
+/// into the secret store like any other credential.
+#[tauri::command]
+pub fn settings_kemai_import<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SettingsState>,
+    path: Option<String>,
+) -> SettingsResult<KemaiImportResult> {
+    let kemai = KemaiSettings::read(&kemai_path(path)?)?;
+    let (settings, added_profile_ids) = state.update(|settings| Ok(kemai.apply(settings)))?;
+    let _ = app.emit(SETTINGS_CHANGED_EVENT, &settings);
+    Ok(KemaiImportResult {
+        settings,
+        added_profile_ids,
+    })
+}
+
+fn kemai_path(path: Option<String>) -> SettingsResult<PathBuf> {
+    path.map(PathBuf::from).or_else(kemai::default_path).ok_or_else(|| {
+        SettingsError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Kemai's settings location is unknown; choose the file instead",
+        ))
+    })
+}
@@ -0,0 +1,476 @@
+// Kemai import
+// Reads the QSettings INI file of a Kemai installation and maps its profiles,
+// trusted certificates, event and tray preferences onto ours. The webview
+// only ever sees a preview without tokens; importing re-reads the file.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::models::AppSettings;
+use crate::kimai::models::{KimaiAuthConfig, KimaiAuthType, KimaiProfile, RequestSettings, SslSettings};
+
+/// QSettings keys, `group/key`, with array entries as `profiles/1/host`
+type IniValues = BTreeMap<String, Vec<String>>;
+
+/// Kemai's settings file under the XDG config directory
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("Kemai").join("Kemai.ini"))
+}
+
+/// Everything found in a Kemai settings file
+#[derive(Debug, Clone, Default)]
+pub struct KemaiSettings {
+    pub profiles: Vec<KimaiProfile>,
+    pub default_profile_id: Option<String>,
+    pub trusted_certificates: Vec<String>,
+    pub language: Option<String>,
+    pub stop_on_lock: Option<bool>,
+    pub stop_on_idle: Option<bool>,
+    /// Minutes
+    pub idle_delay: Option<u32>,
+    pub close_to_tray: Option<bool>,
+    pub minimize_to_tray: Option<bool>,
+    pub warnings: Vec<String>,
+}
+
+impl KemaiSettings {
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let data = fs::read(path)?;
+        Ok(Self::parse(&String::from_utf8_lossy(&data)))
+    }
+
+    pub fn parse(ini: &str) -> Self {
+        let values = parse_ini(ini);
+        let mut settings = Self {
+            default_profile_id: string(&values, "defaultProfileId").map(|id| profile_id(&id)),
+            trusted_certificates: values
+                .get("trustedCertificates")
+                .into_iter()
+                .flatten()
+                .map(|certificate| strip_byte_array(certificate).trim().to_string())
+                .filter(|certificate| !certificate.is_empty())
+                .collect(),
+            language: string(&values, "language").and_then(|language| {
+                let language = language.split(['_', '-']).next()?.to_lowercase();
+                (!language.is_empty()).then_some(language)
+            }),
+            stop_on_lock: boolean(&values, "events/stopOnLock"),
+            stop_on_idle: boolean(&values, "events/stopOnIdle"),
+            idle_delay: string(&values, "events/idleDelay").and_then(|delay| delay.parse().ok()),
+            close_to_tray: boolean(&values, "closeToSystemTray"),
+            minimize_to_tray: boolean(&values, "minimizeToSystemTray"),
+            ..Default::default()
+        };
+
+        let count: usize = string(&values, "profiles/size").and_then(|size| size.parse().ok()).unwrap_or(0);
+        for index in 1..=count {
+            let prefix = format!("profiles/{index}/");
+            let field = |name: &str| string(&values, &format!("{prefix}{name}")).filter(|value| !value.is_empty());
+            let id = field("id").map(|id| profile_id(&id)).unwrap_or_else(|| format!("kemai-{index}"));
+            let name = field("name").unwrap_or_else(|| format!("Kemai profile {index}"));
+            settings.push_profile(id, name, field("host"), field("username"), field("token"), field("apiToken"));
+        }
+
+        // Versions before profiles kept a single connection under [kimai]
+        if count == 0 {
+            let field = |name: &str| string(&values, &format!("kimai/{name}")).filter(|value| !value.is_empty());
+            if field("host").is_some() {
+                let (host, username, token) = (field("host"), field("username"), field("token"));
+                settings.push_profile("kemai".into(), "Kemai".into(), host, username, token, None);
+            }
+        }
+
+        let ssl = SslSettings {
+            trusted_certificates: settings.trusted_certificates.clone(),
+            ..Default::default()
+        };
+        for profile in &mut settings.profiles {
+            profile.ssl = ssl.clone();
+        }
+        settings
+    }
+
+    /// Kimai 2 API tokens are bearer tokens; older Kemai profiles hold the
+    /// user's API password, sent with X-AUTH headers.
+    fn push_profile(
+        &mut self,
+        id: String,
+        name: String,
+        host: Option<String>,
+        username: Option<String>,
+        token: Option<String>,
+        api_token: Option<String>,
+    ) {
+        let Some(host) = host else {
+            self.warnings.push(format!("Profile \"{name}\" has no host and was skipped"));
+            return;
+        };
+        let base_url = if host.contains("://") { host } else { format!("https://{host}") };
+
+        let auth = match (api_token, token) {
+            (Some(api_token), _) => KimaiAuthConfig {
+                kind: KimaiAuthType::ApiToken,
+                scheme: None,
+                username,
+                password: None,
+                api_token: Some(api_token),
+                secret_ref: None,
+                base_url,
+            },
+            (None, token) => {
+                if token.is_none() {
+                    self.warnings.push(format!("Profile \"{name}\" has no token; add it after importing"));
+                }
+                KimaiAuthConfig {
+                    kind: KimaiAuthType::Legacy,
+                    scheme: None,
+                    username,
+                    password: token,
+                    api_token: None,
+                    secret_ref: None,
+                    base_url,
+                }
+            }
+        };
+
+        self.profiles.push(KimaiProfile {
+            id,
+            name,
+            auth,
+            ssl: SslSettings::default(),
+            client_certificate: None,
+            requests: RequestSettings::default(),
+            auto_connect: false,
+            last_used: None,
+            pause_policy: Default::default(),
+            rounding: Default::default(),
+        });
+    }
+
+    /// Merges into `settings`. Profiles already configured (same id, or same
+    /// server and user) are left alone. Returns the ids of added profiles.
+    pub fn apply(&self, settings: &mut AppSettings) -> Vec<String> {
+        let mut added = Vec::new();
+        for profile in &self.profiles {
+            if find_existing(settings, profile).is_some() {
+                continue;
+            }
+            settings.profiles.push(profile.clone());
+            added.push(profile.id.clone());
+        }
+
+        if settings.current_profile().is_none() {
+            settings.current_profile_id = self
+                .default_profile_id
+                .clone()
+                .filter(|id| added.contains(id))
+                .or_else(|| added.first().cloned());
+        }
+
+        for certificate in &self.trusted_certificates {
+            if !settings.ssl.trusted_certificates.contains(certificate) {
+                settings.ssl.trusted_certificates.push(certificate.clone());
+            }
+        }
+        if let Some(language) = &self.language {
+            settings.ui.language = language.clone();
+        }
+        if let Some(stop_on_lock) = self.stop_on_lock {
+            settings.events.enable_lock_detection = stop_on_lock;
+            settings.events.auto_stop_on_lock = stop_on_lock;
+        }
+        if let Some(stop_on_idle) = self.stop_on_idle {
+            settings.events.enable_idle_detection = stop_on_idle;
+            settings.events.auto_stop_on_idle = stop_on_idle;
+        }
+        if let Some(idle_delay) = self.idle_delay.filter(|delay| *delay > 0) {
+            settings.events.idle_timeout = idle_delay;
+        }
+        if let Some(close_to_tray) = self.close_to_tray {
+            settings.ui.tray_behavior.close_to_tray = close_to_tray;
+        }
+        if let Some(minimize_to_tray) = self.minimize_to_tray {
+            settings.ui.tray_behavior.minimize_to_tray = minimize_to_tray;
+        }
+        added
+    }
+
+    /// What an import would do to `current`, without any credentials
+    pub fn preview(&self, path: &Path, current: &AppSettings) -> KemaiPreview {
+        KemaiPreview {
+            path: path.display().to_string(),
+            profiles: self
+                .profiles
+                .iter()
+                .map(|profile| KemaiProfilePreview {
+                    id: profile.id.clone(),
+                    name: profile.name.clone(),
+                    base_url: profile.auth.base_url.clone(),
+                    username: profile.auth.username.clone(),
+                    auth_type: profile.auth.kind,
+                    has_token: profile.auth.credential().is_some(),
+                    existing_profile_id: find_existing(current, profile).map(|existing| existing.id.clone()),
+                })
+                .collect(),
+            trusted_certificates: self.trusted_certificates.len(),
+            language: self.language.clone(),
+            stop_on_lock: self.stop_on_lock,
+            stop_on_idle: self.stop_on_idle,
+            idle_delay: self.idle_delay,
+            close_to_tray: self.close_to_tray,
+            minimize_to_tray: self.minimize_to_tray,
+            warnings: self.warnings.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KemaiPreview {
+    pub path: String,
+    pub profiles: Vec<KemaiProfilePreview>,
+    pub trusted_certificates: usize,
+    pub language: Option<String>,
+    pub stop_on_lock: Option<bool>,
+    pub stop_on_idle: Option<bool>,
+    pub idle_delay: Option<u32>,
+    pub close_to_tray: Option<bool>,
+    pub minimize_to_tray: Option<bool>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KemaiProfilePreview {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    pub username: Option<String>,
+    pub auth_type: KimaiAuthType,
+    pub has_token: bool,
+    /// Set when the profile is already configured and will be skipped
+    pub existing_profile_id: Option<String>,
+}
+
+fn find_existing<'a>(settings: &'a AppSettings, profile: &KimaiProfile) -> Option<&'a KimaiProfile> {
+    let normalize = |url: &str| url.trim_end_matches('/').to_lowercase();
+    settings.profiles.iter().find(|existing| {
+        existing.id == profile.id
+            || (normalize(&existing.auth.base_url) == normalize(&profile.auth.base_url)
+                && existing.auth.username == profile.auth.username)
+    })
+}
+
+/// Kemai ids are QUuids written as `{...}`; anything else that can't name a file is dropped
+fn profile_id(id: &str) -> String {
+    let id: String = id.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+    format!("kemai-{id}")
+}
+
+fn string(values: &IniValues, key: &str) -> Option<String> {
+    values.get(key).map(|items| items.join(", "))
+}
+
+fn boolean(values: &IniValues, key: &str) -> Option<bool> {
+    match string(values, key)?.as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn strip_byte_array(value: &str) -> &str {
+    value
+        .strip_prefix("@ByteArray(")
+        .and_then(|value| value.strip_suffix(')'))
+        .unwrap_or(value)
+}
+
+/// Parses QSettings' INI dialect: `[General]` holds top-level keys, `\` in
+/// keys separates array indexes and `%XX` escapes other characters.
+fn parse_ini(ini: &str) -> IniValues {
+    let mut values = IniValues::new();
+    let mut group = String::new();
+
+    for line in ini.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            let name = unescape_key(name);
+            group = if name.eq_ignore_ascii_case("general") { String::new() } else { name };
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let key = unescape_key(key.trim());
+        let key = if group.is_empty() { key } else { format!("{group}/{key}") };
+        values.insert(key, unescape_value(value.trim()));
+    }
+    values
+}
+
+fn unescape_key(key: &str) -> String {
+    let mut result = String::new();
+    let mut chars = key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.push('/'),
+            '%' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(decoded) => result.push(decoded),
+                    None => {
+                        result.push('%');
+                        result.push_str(&hex);
+                    }
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// A value is a list when it holds commas outside quotes
+fn unescape_value(value: &str) -> Vec<String> {
+    if value == "@Invalid()" {
+        return Vec::new();
+    }
+
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => current.push('\n'),
+                Some('r') => current.push('\r'),
+                Some('t') => current.push('\t'),
+                Some('0') => current.push('\0'),
+                Some('x') => {
+                    let mut hex = String::new();
+                    while let Some(digit) = chars.peek().filter(|digit| digit.is_ascii_hexdigit() && hex.len() < 4) {
+                        hex.push(*digit);
+                        chars.next();
+                    }
+                    if let Some(decoded) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        current.push(decoded);
+                    }
+                }
+                Some(other) => current.push(other),
+                None => {}
+            },
+            ',' if !quoted => {
+                items.push(current.trim().to_string());
+                current.clear();
+            }
+            c => current.push(c),
+        }
+    }
+    items.push(if items.is_empty() { current } else { current.trim().to_string() });
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEMAI_INI: &str = r#"
+[General]
+closeToSystemTray=true
+defaultProfileId={6f1e4a8c-0000-4000-8000-000000000002}
+language=fr_FR
+minimizeToSystemTray=false
+trustedCertificates="-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n", 3A:4B:5C
+
+[events]
+idleDelay=10
+stopOnIdle=true
+stopOnLock=false
+
+[profiles]
+1\host=kimai.example.org
+1\id={6f1e4a8c-0000-4000-8000-000000000001}
+1\name=Work
+1\token=api-password
+1\username=alice
+2\apiToken=bearer-token
+2\host=https://kimai.home.example/
+2\id={6f1e4a8c-0000-4000-8000-000000000002}
+2\name="Home, sweet home"
+3\name=Broken
+size=3
+"#;
+
+    #[test]
+    fn parses_profiles_certificates_and_preferences() {
+        let kemai = KemaiSettings::parse(KEMAI_INI);
+
+        assert_eq!(kemai.profiles.len(), 2);
+        let work = &kemai.profiles[0];
+        assert_eq!(work.id, "kemai-6f1e4a8c-0000-4000-8000-000000000001");
+        assert_eq!(work.auth.base_url, "https://kimai.example.org");
+        assert_eq!(work.auth.kind, KimaiAuthType::Legacy);
+        assert_eq!(work.auth.credential(), Some("api-password"));
+
+        let home = &kemai.profiles[1];
+        assert_eq!(home.name, "Home, sweet home");
+        assert_eq!(home.auth.kind, KimaiAuthType::ApiToken);
+        assert_eq!(home.ssl.trusted_certificates.len(), 2);
+        assert_eq!(kemai.warnings.len(), 1);
+
+        assert!(kemai.trusted_certificates[0].starts_with("-----BEGIN CERTIFICATE-----\nMIIB"));
+        assert_eq!(kemai.trusted_certificates[1], "3A:4B:5C");
+        assert_eq!(kemai.language.as_deref(), Some("fr"));
+        assert_eq!((kemai.stop_on_idle, kemai.stop_on_lock, kemai.idle_delay), (Some(true), Some(false), Some(10)));
+    }
+
+    #[test]
+    fn apply_skips_known_profiles_and_keeps_tokens_out_of_the_preview() {
+        let kemai = KemaiSettings::parse(KEMAI_INI);
+        let mut settings = AppSettings::default();
+        settings.profiles.push(kemai.profiles[0].clone());
+        settings.profiles[0].id = "existing".into();
+
+        let preview = kemai.preview(Path::new("Kemai.ini"), &settings);
+        assert_eq!(preview.profiles[0].existing_profile_id.as_deref(), Some("existing"));
+        assert!(preview.profiles.iter().all(|profile| profile.has_token));
+        let json = serde_json::to_string(&preview).unwrap();
+        assert!(!json.contains("api-password") && !json.contains("bearer-token"));
+
+        let added = kemai.apply(&mut settings);
+        assert_eq!(added, ["kemai-6f1e4a8c-0000-4000-8000-000000000002"]);
+        assert_eq!(settings.current_profile_id.as_deref(), Some(added[0].as_str()));
+        assert_eq!(settings.ui.language, "fr");
+        assert!(!settings.events.auto_stop_on_lock);
+        assert_eq!(settings.events.idle_timeout, 10);
+        assert!(!settings.ui.tray_behavior.minimize_to_tray);
+    }
+
+    #[test]
+    fn ids_are_reduced_to_what_can_name_a_file() {
+        assert_eq!(profile_id("{6f1e4a8c-0000-4000-8000-000000000001}"), "kemai-6f1e4a8c-0000-4000-8000-000000000001");
+        assert_eq!(profile_id("../../evil"), "kemai-evil");
+    }
+
+    #[test]
+    fn reads_the_single_connection_of_old_versions() {
+        let kemai = KemaiSettings::parse("[kimai]\nhost=https://old.example\nusername=bob\ntoken=secret\n");
+        assert_eq!(kemai.profiles.len(), 1);
+        assert_eq!(kemai.profiles[0].auth.username.as_deref(), Some("bob"));
+    }
+}
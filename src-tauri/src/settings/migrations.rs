@@ -0,0 +1,80 @@
+// Schema migrations
+// Each step upgrades the raw JSON by one version, so old files never need
+// to deserialize into today's types.
+
+use serde_json::{json, Value};
+
+/// Version written by this build
+pub const SCHEMA_VERSION: u64 = 2;
+
+/// Settings without a version field come from the localStorage era
+const UNVERSIONED: u64 = 1;
+
+type Migration = fn(&mut Value);
+
+/// `MIGRATIONS[n]` upgrades version `n + 1` to `n + 2`
+const MIGRATIONS: [Migration; (SCHEMA_VERSION - 1) as usize] = [profiles_inherit_global_ssl];
+
+pub fn schema_version(value: &Value) -> u64 {
+    value.get("schemaVersion").and_then(Value::as_u64).unwrap_or(UNVERSIONED)
+}
+
+/// Upgrades `value` in place to SCHEMA_VERSION. Versions from a newer build
+/// are left untouched; the caller decides what to do with them.
+pub fn migrate(value: &mut Value) {
+    let version = schema_version(value).max(UNVERSIONED);
+    if version > SCHEMA_VERSION || !value.is_object() {
+        return;
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(value);
+    }
+    value["schemaVersion"] = json!(SCHEMA_VERSION);
+}
+
+/// v1 → v2: TLS settings moved into each profile. Profiles saved before that
+/// keep behaving as they did by copying the global settings.
+fn profiles_inherit_global_ssl(value: &mut Value) {
+    let Some(ssl) = value.get("ssl").cloned() else {
+        return;
+    };
+    let Some(profiles) = value.get_mut("profiles").and_then(Value::as_array_mut) else {
+        return;
+    };
+
+    for profile in profiles.iter_mut().filter_map(Value::as_object_mut) {
+        profile.entry("ssl").or_insert_with(|| ssl.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_storage_settings_are_upgraded() {
+        let mut value = json!({
+            "profiles": [
+                {"id": "a", "name": "Work", "auth": {"type": "api_token", "baseUrl": "https://kimai.example"}},
+                {"id": "b", "name": "Home", "auth": {"type": "api_token", "baseUrl": "https://home.example"},
+                 "ssl": {"ignoreSslErrors": false, "trustedCertificates": [], "verifyHostname": true}}
+            ],
+            "ssl": {"ignoreSslErrors": true, "trustedCertificates": ["AB:CD"], "verifyHostname": false}
+        });
+
+        migrate(&mut value);
+
+        assert_eq!(schema_version(&value), SCHEMA_VERSION);
+        assert_eq!(value["profiles"][0]["ssl"]["ignoreSslErrors"], json!(true));
+        assert_eq!(value["profiles"][1]["ssl"]["ignoreSslErrors"], json!(false));
+    }
+
+    #[test]
+    fn newer_versions_are_left_alone() {
+        let mut value = json!({"schemaVersion": SCHEMA_VERSION + 1, "profiles": []});
+        let before = value.clone();
+        migrate(&mut value);
+        assert_eq!(value, before);
+    }
+}
@@ -0,0 +1,357 @@
+// Settings
+// Stored as settings.json in the app config directory, so Rust can read them
+// before any window exists and every window sees the same values.
+
+pub mod bundle;
+pub mod commands;
+pub mod kemai;
+pub mod migrations;
+pub mod models;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+pub use models::AppSettings;
+
+use crate::kimai::models::KimaiProfile;
+use crate::secrets::SecretError;
+
+pub const SETTINGS_CHANGED_EVENT: &str = "settings://changed";
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Could not access the settings file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid settings: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("The settings were saved by a newer version of tikker (schema {0}) and are read-only")]
+    NewerSchema(u64),
+    #[error("The file is not a tikker settings export")]
+    NotABundle,
+    #[error("Invalid profile id {0:?}: use letters, digits, _ and - only")]
+    InvalidProfileId(String),
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+}
+
+impl SettingsError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SettingsError::Io(_) => "io",
+            SettingsError::Invalid(_) => "invalid",
+            SettingsError::NewerSchema(_) => "newer_schema",
+            SettingsError::NotABundle => "not_a_bundle",
+            SettingsError::InvalidProfileId(_) => "invalid_profile_id",
+            SettingsError::Secret(err) => err.kind(),
+        }
+    }
+}
+
+impl Serialize for SettingsError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorPayload {
+            kind: &'static str,
+            message: String,
+        }
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type SettingsResult<T> = Result<T, SettingsError>;
+
+/// On-disk layout: the settings plus the schema version they were written with
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SettingsFile {
+    schema_version: u64,
+    #[serde(flatten)]
+    settings: AppSettings,
+}
+
+/// Runs before every save with the previous and the new settings, and may
+/// rewrite the new ones (e.g. to move credentials out of the file)
+pub type BeforeSave = Box<dyn Fn(&AppSettings, &mut AppSettings) -> SettingsResult<()> + Send + Sync>;
+
+pub struct SettingsState {
+    path: PathBuf,
+    settings: Mutex<AppSettings>,
+    /// Set when the file was written by a newer build; saving would drop its data
+    newer_schema: Option<u64>,
+    /// False until the file exists, so localStorage settings may still be imported
+    persisted: Mutex<bool>,
+    before_save: Option<BeforeSave>,
+}
+
+impl SettingsState {
+    pub fn path(config_dir: &Path) -> PathBuf {
+        config_dir.join(SETTINGS_FILE)
+    }
+
+    /// Reads and migrates the settings file. A missing file yields defaults;
+    /// an unreadable one is kept as `settings.json.bak` before starting over.
+    pub fn load(path: PathBuf) -> Self {
+        let mut state = Self {
+            settings: Mutex::new(AppSettings::default()),
+            newer_schema: None,
+            persisted: Mutex::new(path.exists()),
+            before_save: None,
+            path,
+        };
+
+        let Ok(data) = fs::read(&state.path) else {
+            return state;
+        };
+        let parsed = serde_json::from_slice::<Value>(&data).and_then(|mut value| {
+            let version = migrations::schema_version(&value);
+            migrations::migrate(&mut value);
+            serde_json::from_value::<AppSettings>(value).map(|settings| (version, settings))
+        });
+
+        match parsed {
+            Ok((version, settings)) => {
+                state.newer_schema = (version > migrations::SCHEMA_VERSION).then_some(version);
+                if version < migrations::SCHEMA_VERSION {
+                    let _ = state.save(&settings, &mut settings.clone());
+                }
+                *state.settings.get_mut().unwrap() = settings;
+            }
+            Err(err) => {
+                eprintln!("Discarding unreadable settings: {err}");
+                let _ = fs::rename(&state.path, state.path.with_extension("json.bak"));
+                *state.persisted.get_mut().unwrap() = false;
+            }
+        }
+        state
+    }
+
+    pub fn with_before_save(
+        mut self,
+        hook: impl Fn(&AppSettings, &mut AppSettings) -> SettingsResult<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.before_save = Some(Box::new(hook));
+        self
+    }
+
+    /// Runs the before-save hook over the current settings and saves them
+    /// if it changed anything, e.g. for files written before the hook existed.
+    pub fn resave(&self) -> SettingsResult<Option<AppSettings>> {
+        let Some(hook) = &self.before_save else {
+            return Ok(None);
+        };
+        let mut settings = self.settings.lock().unwrap();
+        let mut updated = settings.clone();
+        hook(&settings, &mut updated)?;
+        if serde_json::to_value(&updated)? == serde_json::to_value(&*settings)? {
+            return Ok(None);
+        }
+
+        self.save(&settings, &mut updated)?;
+        *settings = updated.clone();
+        Ok(Some(updated))
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// Applies a JSON merge patch (RFC 7386) and saves the result.
+    /// Objects merge key by key, `null` removes a key, anything else replaces.
+    pub fn patch(&self, patch: &Value) -> SettingsResult<AppSettings> {
+        let mut settings = self.settings.lock().unwrap();
+        let mut value = serde_json::to_value(&*settings)?;
+        merge_patch(&mut value, patch);
+
+        let mut updated: AppSettings = serde_json::from_value(value)?;
+        self.save(&settings, &mut updated)?;
+        *settings = updated.clone();
+        Ok(updated)
+    }
+
+    /// Changes the settings in place and saves the result
+    pub fn update<T>(
+        &self,
+        change: impl FnOnce(&mut AppSettings) -> SettingsResult<T>,
+    ) -> SettingsResult<(AppSettings, T)> {
+        let mut settings = self.settings.lock().unwrap();
+        let mut updated = settings.clone();
+        let result = change(&mut updated)?;
+        self.save(&settings, &mut updated)?;
+        *settings = updated.clone();
+        Ok((updated, result))
+    }
+
+    /// Imports settings the webview kept in localStorage, once. Returns `None`
+    /// when a settings file already exists.
+    pub fn import_legacy(&self, mut legacy: Value) -> SettingsResult<Option<AppSettings>> {
+        if *self.persisted.lock().unwrap() {
+            return Ok(None);
+        }
+
+        migrations::migrate(&mut legacy);
+        let mut settings: AppSettings = serde_json::from_value(legacy)?;
+        let mut current = self.settings.lock().unwrap();
+        self.save(&current, &mut settings)?;
+        *current = settings.clone();
+        Ok(Some(settings))
+    }
+
+    /// Writes to a temporary file first so a crash never leaves half a file behind.
+    fn save(&self, previous: &AppSettings, settings: &mut AppSettings) -> SettingsResult<()> {
+        if let Some(version) = self.newer_schema {
+            return Err(SettingsError::NewerSchema(version));
+        }
+        // Ids saved before they were checked stay usable; new ones must name a file
+        if let Some(profile) = settings.profiles.iter().find(|profile| {
+            !KimaiProfile::is_valid_id(&profile.id) && !previous.profiles.iter().any(|kept| kept.id == profile.id)
+        }) {
+            return Err(SettingsError::InvalidProfileId(profile.id.clone()));
+        }
+        if let Some(hook) = &self.before_save {
+            hook(previous, settings)?;
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = SettingsFile {
+            schema_version: migrations::SCHEMA_VERSION,
+            settings: settings.clone(),
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&file)?)?;
+        fs::rename(&tmp, &self.path)?;
+        *self.persisted.lock().unwrap() = true;
+        Ok(())
+    }
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tikker-settings-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        SettingsState::path(&dir)
+    }
+
+    #[test]
+    fn a_locked_secret_store_is_reported_like_the_kimai_commands_do() {
+        let error = serde_json::to_value(SettingsError::Secret(SecretError::Locked)).unwrap();
+        assert_eq!(error["kind"], "secret_locked");
+    }
+
+    #[test]
+    fn merge_patch_follows_rfc_7386() {
+        let mut target = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut target, &json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(target, json!({"a": "z", "c": {"d": "e"}}));
+    }
+
+    #[test]
+    fn patches_are_saved_and_reloaded() {
+        let path = temp_path("patch");
+        let state = SettingsState::load(path.clone());
+        assert_eq!(state.get().ui.language, "en");
+
+        let updated = state
+            .patch(&json!({"ui": {"language": "de", "trayBehavior": {"startMinimized": true}}}))
+            .unwrap();
+        assert!(updated.ui.tray_behavior.start_minimized);
+        assert!(updated.ui.tray_behavior.close_to_tray);
+
+        let reloaded = SettingsState::load(path.clone());
+        assert_eq!(reloaded.get().ui.language, "de");
+        assert!(reloaded.import_legacy(json!({})).unwrap().is_none());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn before_save_hook_rewrites_what_is_saved() {
+        let path = temp_path("hook");
+        let state = SettingsState::load(path.clone()).with_before_save(|_, next| {
+            next.ui.language = next.ui.language.to_lowercase();
+            Ok(())
+        });
+        assert!(state.resave().unwrap().is_none());
+
+        let updated = state.patch(&json!({"ui": {"language": "DE"}})).unwrap();
+        assert_eq!(updated.ui.language, "de");
+        assert_eq!(SettingsState::load(path.clone()).get().ui.language, "de");
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn invalid_patches_leave_settings_untouched() {
+        let path = temp_path("invalid");
+        let state = SettingsState::load(path.clone());
+        assert!(state.patch(&json!({"ui": {"theme": "sepia"}})).is_err());
+        assert_eq!(state.get().ui.theme, models::Theme::System);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn profile_ids_that_cannot_name_a_file_are_refused() {
+        let path = temp_path("profile-id");
+        let state = SettingsState::load(path.clone());
+        let profile = |id: &str| json!({"profiles": [{"id": id, "name": "Work", "auth": {"type": "api_token", "baseUrl": "https://kimai.example"}}]});
+        assert!(matches!(
+            state.patch(&profile("../../work")),
+            Err(SettingsError::InvalidProfileId(id)) if id == "../../work"
+        ));
+        assert!(!path.exists());
+        assert_eq!(state.patch(&profile("profile_1_a-b")).unwrap().profiles[0].id, "profile_1_a-b");
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn newer_schemas_are_read_only() {
+        let path = temp_path("newer");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, json!({"schemaVersion": 99, "ui": {"language": "fr"}}).to_string()).unwrap();
+
+        let state = SettingsState::load(path.clone());
+        assert_eq!(state.get().ui.language, "fr");
+        assert!(matches!(
+            state.patch(&json!({"ui": {"language": "de"}})),
+            Err(SettingsError::NewerSchema(99))
+        ));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
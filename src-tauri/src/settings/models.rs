@@ -0,0 +1,200 @@
+// Application settings
+// Mirrors the types in src/lib/types/settings.ts
+
+use serde::{Deserialize, Serialize};
+
+use crate::kimai::models::{KimaiProfile, SslSettings};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub profiles: Vec<KimaiProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_profile_id: Option<String>,
+    pub ui: UiSettings,
+    pub events: EventSettings,
+    pub auto_refresh: AutoRefreshSettings,
+    pub auto_stop: AutoStopSettings,
+    pub ssl: SslSettings,
+}
+
+impl AppSettings {
+    pub fn current_profile(&self) -> Option<&KimaiProfile> {
+        let id = self.current_profile_id.as_deref()?;
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UiSettings {
+    pub language: String,
+    pub geometry: WindowGeometry,
+    pub tray_behavior: TrayBehavior,
+    pub theme: Theme,
+    pub font_size: FontSize,
+    pub show_notifications: bool,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self {
+            language: "en".into(),
+            geometry: WindowGeometry::default(),
+            tray_behavior: TrayBehavior::default(),
+            theme: Theme::System,
+            font_size: FontSize::Medium,
+            show_notifications: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowGeometry {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub maximized: bool,
+}
+
+impl Default for WindowGeometry {
+    fn default() -> Self {
+        Self {
+            x: 100.0,
+            y: 100.0,
+            width: 400.0,
+            height: 600.0,
+            maximized: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrayBehavior {
+    pub minimize_to_tray: bool,
+    pub start_minimized: bool,
+    pub close_to_tray: bool,
+    pub show_tray_icon: bool,
+}
+
+impl Default for TrayBehavior {
+    fn default() -> Self {
+        Self {
+            minimize_to_tray: true,
+            start_minimized: false,
+            close_to_tray: true,
+            show_tray_icon: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    System,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FontSize {
+    Small,
+    Medium,
+    Large,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EventSettings {
+    pub enable_idle_detection: bool,
+    /// Minutes
+    pub idle_timeout: u32,
+    pub enable_lock_detection: bool,
+    pub auto_stop_on_idle: bool,
+    pub auto_stop_on_lock: bool,
+    pub show_idle_warning: bool,
+    /// Minutes
+    pub idle_warning_time: u32,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        Self {
+            enable_idle_detection: true,
+            idle_timeout: 5,
+            enable_lock_detection: true,
+            auto_stop_on_idle: true,
+            auto_stop_on_lock: true,
+            show_idle_warning: true,
+            idle_warning_time: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoRefreshSettings {
+    pub enabled: bool,
+    /// Seconds
+    pub interval: u32,
+    pub sync_on_startup: bool,
+    pub sync_on_resume: bool,
+}
+
+impl Default for AutoRefreshSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: 30,
+            sync_on_startup: true,
+            sync_on_resume: true,
+        }
+    }
+}
+
+/// What stops a timer left running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoStopTrigger {
+    ClockTime,
+    /// Kimai's business hours end, plus the grace period
+    #[default]
+    BusinessHoursEnd,
+    /// Worked time passing Kimai's day limit
+    DayLimit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoStopSettings {
+    pub enabled: bool,
+    pub trigger: AutoStopTrigger,
+    /// Local `HH:MM`; also used when the server has no business hours
+    pub clock_time: String,
+    /// Minutes after business hours end
+    pub grace_period: u32,
+    /// Hours, when the server has no day limit
+    pub day_limit: u32,
+    /// Minutes before the stop
+    pub warning_time: u32,
+    /// Books a timer that crosses midnight as one entry per day
+    pub split_at_midnight: bool,
+}
+
+impl Default for AutoStopSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trigger: AutoStopTrigger::default(),
+            clock_time: "20:00".into(),
+            grace_period: 30,
+            day_limit: 10,
+            warning_time: 10,
+            split_at_midnight: false,
+        }
+    }
+}
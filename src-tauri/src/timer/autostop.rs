@@ -0,0 +1,200 @@
+// Automatic stop
+// Stops a timer left running: at a clock time, when business hours end plus
+// a grace period, or once the worked time passes the day limit. A warning
+// goes out first, as `timer://auto-stop` like the stop itself.
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+
+use crate::kimai::models::KimaiCalendarConfig;
+use crate::settings::models::{AutoStopSettings, AutoStopTrigger};
+
+pub const TIMER_AUTO_STOP_EVENT: &str = "timer://auto-stop";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopPoint {
+    /// The first time the local clock shows this after the start
+    ClockTime(NaiveTime),
+    /// Worked time, pauses left out
+    WorkedTime(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoStop {
+    pub trigger: AutoStopTrigger,
+    pub point: StopPoint,
+    pub warning: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoStopNoticeKind {
+    Warning,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoStopNotice {
+    pub kind: AutoStopNoticeKind,
+    pub trigger: AutoStopTrigger,
+    /// When the timer stops, or stopped
+    pub at: DateTime<Utc>,
+}
+
+impl AutoStop {
+    /// Resolves the settings against the server's calendar. Without business
+    /// hours the clock time is used, without a day limit the local one.
+    pub fn from_settings(settings: &AutoStopSettings, calendar: Option<&KimaiCalendarConfig>) -> Option<Self> {
+        if !settings.enabled {
+            return None;
+        }
+        let clock_time = parse_clock_time(&settings.clock_time);
+        let point = match settings.trigger {
+            AutoStopTrigger::ClockTime => StopPoint::ClockTime(clock_time?),
+            AutoStopTrigger::BusinessHoursEnd => {
+                let business_hours_end = calendar
+                    .and_then(|calendar| calendar.business_hours_end.as_deref())
+                    .and_then(parse_clock_time);
+                match business_hours_end {
+                    Some(end) => StopPoint::ClockTime(end + Duration::minutes(settings.grace_period.into())),
+                    None => StopPoint::ClockTime(clock_time?),
+                }
+            }
+            AutoStopTrigger::DayLimit => {
+                let hours = calendar
+                    .and_then(|calendar| calendar.day_limit)
+                    .filter(|hours| *hours > 0)
+                    .unwrap_or(settings.day_limit);
+                StopPoint::WorkedTime(Duration::hours(hours.into()))
+            }
+        };
+        Some(Self {
+            trigger: settings.trigger,
+            point,
+            warning: Duration::minutes(settings.warning_time.into()),
+        })
+    }
+
+    /// When a timer started at `started_at`, having worked `elapsed` by
+    /// `now`, stops. A worked-time limit doesn't come closer while paused.
+    pub fn deadline<Tz: TimeZone>(
+        &self,
+        started_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        elapsed: Duration,
+        paused: bool,
+        zone: &Tz,
+    ) -> Option<DateTime<Utc>> {
+        match self.point {
+            StopPoint::ClockTime(time) => {
+                let start = started_at.with_timezone(zone).naive_local();
+                let mut stop = start.date().and_time(time);
+                if stop <= start {
+                    stop += Duration::days(1);
+                }
+                zone.from_local_datetime(&stop).earliest().map(|stop| stop.with_timezone(&Utc))
+            }
+            StopPoint::WorkedTime(_) if paused => None,
+            StopPoint::WorkedTime(limit) => Some(now + (limit - elapsed)),
+        }
+    }
+}
+
+fn parse_clock_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+/// Splits `begin..end` at each local midnight in between
+pub fn split_at_midnight<Tz: TimeZone>(
+    begin: DateTime<Utc>,
+    end: DateTime<Utc>,
+    zone: &Tz,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut spans = Vec::new();
+    let mut from = begin;
+    while from < end {
+        let next_midnight = from
+            .with_timezone(zone)
+            .date_naive()
+            .succ_opt()
+            .and_then(|day| zone.from_local_datetime(&day.and_time(NaiveTime::MIN)).earliest())
+            .map(|midnight| midnight.with_timezone(&Utc))
+            .filter(|midnight| *midnight > from)
+            .unwrap_or(end);
+        let to = next_midnight.min(end);
+        spans.push((from, to));
+        from = to;
+    }
+    if spans.is_empty() {
+        spans.push((begin, end));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn settings(trigger: AutoStopTrigger) -> AutoStopSettings {
+        AutoStopSettings {
+            enabled: true,
+            trigger,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn triggers_resolve_against_the_calendar() {
+        assert_eq!(AutoStop::from_settings(&AutoStopSettings::default(), None), None);
+
+        let calendar = KimaiCalendarConfig {
+            business_hours_end: Some("18:00".into()),
+            day_limit: Some(8),
+        };
+        let business = AutoStop::from_settings(&settings(AutoStopTrigger::BusinessHoursEnd), Some(&calendar)).unwrap();
+        assert_eq!(business.point, StopPoint::ClockTime(NaiveTime::from_hms_opt(18, 30, 0).unwrap()));
+        let fallback = AutoStop::from_settings(&settings(AutoStopTrigger::BusinessHoursEnd), None).unwrap();
+        assert_eq!(fallback.point, StopPoint::ClockTime(NaiveTime::from_hms_opt(20, 0, 0).unwrap()));
+        let limit = AutoStop::from_settings(&settings(AutoStopTrigger::DayLimit), Some(&calendar)).unwrap();
+        assert_eq!(limit.point, StopPoint::WorkedTime(Duration::hours(8)));
+    }
+
+    #[test]
+    fn deadlines_follow_the_local_clock_or_the_worked_time() {
+        let zone = FixedOffset::east_opt(2 * 3600).unwrap();
+        let auto_stop = AutoStop::from_settings(&settings(AutoStopTrigger::ClockTime), None).unwrap();
+        let now = at("2024-05-06T12:00:00Z");
+        let evening = auto_stop.deadline(at("2024-05-06T07:00:00Z"), now, Duration::zero(), false, &zone);
+        assert_eq!(evening, Some(at("2024-05-06T18:00:00Z")));
+        // Started after the stop time: the next day's
+        let late = auto_stop.deadline(at("2024-05-06T19:00:00Z"), now, Duration::zero(), true, &zone);
+        assert_eq!(late, Some(at("2024-05-07T18:00:00Z")));
+
+        let limit = AutoStop::from_settings(&settings(AutoStopTrigger::DayLimit), None).unwrap();
+        let worked = limit.deadline(at("2024-05-06T07:00:00Z"), now, Duration::hours(4), false, &zone);
+        assert_eq!(worked, Some(at("2024-05-06T18:00:00Z")));
+        assert_eq!(limit.deadline(at("2024-05-06T07:00:00Z"), now, Duration::hours(4), true, &zone), None);
+    }
+
+    #[test]
+    fn spans_split_at_local_midnight() {
+        let zone = FixedOffset::east_opt(2 * 3600).unwrap();
+        let spans = split_at_midnight(at("2024-05-06T20:00:00Z"), at("2024-05-08T01:00:00Z"), &zone);
+        assert_eq!(
+            spans,
+            [
+                (at("2024-05-06T20:00:00Z"), at("2024-05-06T22:00:00Z")),
+                (at("2024-05-06T22:00:00Z"), at("2024-05-07T22:00:00Z")),
+                (at("2024-05-07T22:00:00Z"), at("2024-05-08T01:00:00Z")),
+            ]
+        );
+        assert_eq!(split_at_midnight(at("2024-05-06T08:00:00Z"), at("2024-05-06T09:00:00Z"), &zone).len(), 1);
+    }
+}
@@ -0,0 +1,453 @@
+// Kimai bookings
+// A timer started on a Kimai timesheet keeps it in step. The engine queues
+// what its transitions book, rounded, and this sends it: an entry with an
+// id updates that timesheet, one without is created. A timesheet created
+// running is the one the timer runs on from then on. What Kimai couldn't
+// take, e.g. while offline, stays queued and is retried in the background.
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use super::models::TimerEntry;
+use super::rounding;
+use super::{TimerEngine, TimerError, TimerResult};
+use crate::kimai::cache::kimai_datetime;
+use crate::kimai::models::{KimaiTimeSheet, KimaiTimeSheetForm};
+use crate::kimai::{retry, KimaiClient, KimaiError, KimaiResult, KimaiState};
+
+/// Pause before each retry of the queued bookings
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What one transition books in the profile's Kimai
+#[derive(Debug, Clone, PartialEq)]
+pub struct KimaiBookings {
+    pub profile_id: String,
+    pub entries: Vec<TimerEntry>,
+}
+
+fn form(entry: &TimerEntry) -> KimaiTimeSheetForm {
+    let time = |value: &str| rounding::parse_time(value).map(|time| kimai_datetime(time.with_timezone(&Utc)));
+    KimaiTimeSheetForm {
+        begin: time(&entry.begin),
+        end: entry.end.as_deref().and_then(time),
+        project: entry.project,
+        activity: entry.activity,
+        description: entry.description.clone(),
+        tags: entry.tags.as_ref().map(|tags| tags.join(",")),
+        billable: Some(entry.billable),
+        ..Default::default()
+    }
+}
+
+/// Saves `entries` in order, stopping at the first Kimai refuses
+pub async fn submit(client: &KimaiClient, entries: &[TimerEntry]) -> KimaiResult<Vec<KimaiTimeSheet>> {
+    match submit_each(client, entries).await {
+        (saved, None) => Ok(saved),
+        (_, Some(err)) => Err(err),
+    }
+}
+
+/// Like `submit`, also returning what was saved before an error
+async fn submit_each(client: &KimaiClient, entries: &[TimerEntry]) -> (Vec<KimaiTimeSheet>, Option<KimaiError>) {
+    let mut saved = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let result = match entry.id {
+            Some(id) => client.update_timesheet(id, &form(entry)).await,
+            None => client.create_timesheet(&form(entry)).await,
+        };
+        match result {
+            Ok(timesheet) => saved.push(timesheet),
+            Err(err) => return (saved, Some(err)),
+        }
+    }
+    (saved, None)
+}
+
+/// Failures a later attempt may get past: no connection yet, the network
+/// and the server's own errors
+fn worth_retrying(err: &KimaiError) -> bool {
+    retry::is_retryable(err) || matches!(err, KimaiError::NotConnected | KimaiError::Server { code: 500.., .. })
+}
+
+/// Sends everything the engine queued. A profile's bookings go in order;
+/// from one Kimai couldn't take yet on, they are queued again for a retry.
+/// Refused bookings are dropped. The first error is returned, the others
+/// are logged.
+pub async fn submit_unsubmitted(engine: &TimerEngine, kimai: &KimaiState) -> TimerResult<()> {
+    let mut result = Ok(());
+    let mut held: Vec<KimaiBookings> = Vec::new();
+    for bookings in engine.take_unsubmitted() {
+        if held.iter().any(|held| held.profile_id == bookings.profile_id) {
+            held.push(bookings);
+            continue;
+        }
+        let (saved, error) = match kimai.client(&bookings.profile_id) {
+            Ok(client) => submit_each(&client, &bookings.entries).await,
+            Err(err) => (Vec::new(), Some(err)),
+        };
+        for (entry, saved) in bookings.entries.iter().zip(&saved) {
+            if entry.id.is_none() && entry.end.is_none() {
+                if let Err(err) = engine.link(&bookings.profile_id, saved.id) {
+                    eprintln!("Could not run the timer on timesheet {}: {err}", saved.id);
+                }
+            }
+        }
+
+        let Some(err) = error else {
+            continue;
+        };
+        let err = if worth_retrying(&err) {
+            held.push(KimaiBookings {
+                entries: bookings.entries[saved.len()..].to_vec(),
+                ..bookings
+            });
+            TimerError::BookingQueued(err)
+        } else {
+            TimerError::Booking(err)
+        };
+        match result {
+            Ok(()) => result = Err(err),
+            Err(_) => eprintln!("{err}"),
+        }
+    }
+    engine.requeue(held);
+    result
+}
+
+/// Sends the queued bookings every `RETRY_INTERVAL` until none are left,
+/// unless another retry is at it already
+pub async fn retry_unsubmitted(engine: &TimerEngine, kimai: &KimaiState) {
+    if !engine.start_retrying() {
+        return;
+    }
+    loop {
+        tokio::time::sleep(RETRY_INTERVAL).await;
+        if let Err(err) = submit_unsubmitted(engine, kimai).await {
+            eprintln!("{err}");
+        }
+        if !engine.keep_retrying() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::{DateTime, Duration, SubsecRound};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::kimai::models::{PausePolicy, RoundingMode, RoundingRule};
+    use crate::kimai::test_server::{self, Response};
+    use crate::timer::models::BookingRules;
+    use crate::timer::TimerJournal;
+
+    type Requests = Arc<Mutex<Vec<(String, String, Value)>>>;
+
+    /// A Kimai that saves every timesheet, numbering new ones from 8
+    fn serve_kimai() -> (KimaiState, Requests) {
+        let requests = Requests::default();
+        let seen = requests.clone();
+        let url = test_server::serve(move |request| {
+            if request.method == "GET" {
+                return Response::json("200 OK", "[]");
+            }
+            let body: Value = serde_json::from_str(&request.body).unwrap_or_default();
+            let mut seen = seen.lock().unwrap();
+            seen.push((request.method.clone(), request.target.clone(), body.clone()));
+            let id = match request.target.rsplit_once('/') {
+                Some((_, id)) if id.parse::<u64>().is_ok() => id.parse().unwrap(),
+                _ => 7 + seen.iter().filter(|(method, ..)| method == "POST").count() as u64,
+            };
+            let saved = json!({"id": id, "begin": body["begin"], "end": body["end"], "user": 1, "activity": 3, "project": 2});
+            Response::json("200 OK", saved.to_string())
+        });
+        let kimai = KimaiState::default();
+        kimai.insert("work".into(), test_server::client(&url));
+        (kimai, requests)
+    }
+
+    fn engine(name: &str) -> (TimerEngine, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("tikker-booking-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        (TimerEngine::new(TimerJournal::open(TimerJournal::path(&dir))), dir)
+    }
+
+    fn entry() -> TimerEntry {
+        TimerEntry {
+            id: Some(7),
+            description: Some("Review".into()),
+            customer: Some(1),
+            project: Some(2),
+            activity: Some(3),
+            billable: true,
+            tags: Some(vec!["meeting".into(), "client".into()]),
+            begin: String::new(),
+            end: None,
+            duration: 0,
+        }
+    }
+
+    fn quarter_hours() -> BookingRules {
+        let mut rules = BookingRules::default();
+        rules.rounding.rule = RoundingRule {
+            mode: RoundingMode::Default,
+            begin: 0,
+            end: 0,
+            duration: 15,
+        };
+        rules
+    }
+
+    #[tokio::test]
+    async fn a_stop_books_the_running_timesheet_rounded() {
+        let (kimai, requests) = serve_kimai();
+        let (engine, dir) = engine("stop");
+        let begin = Utc::now().trunc_subsecs(0) - Duration::minutes(20);
+        engine.start_at(Some(entry()), Some("work".into()), quarter_hours(), Some(begin), None, false).unwrap();
+        engine.stop().unwrap();
+
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (method, target, body) = &requests[0];
+        assert_eq!((method.as_str(), target.as_str()), ("PATCH", "/api/timesheets/7"));
+        assert_eq!(body["begin"], kimai_datetime(begin));
+        assert_eq!(body["end"], kimai_datetime(begin + Duration::minutes(30)));
+        assert_eq!(body["tags"], "meeting,client");
+        assert!(engine.take_unsubmitted().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_split_ends_the_timesheet_and_runs_on_a_new_one() {
+        let (kimai, requests) = serve_kimai();
+        let (engine, dir) = engine("split");
+        let begin = Utc::now().trunc_subsecs(0) - Duration::minutes(40);
+        engine.start_at(Some(entry()), Some("work".into()), BookingRules::default(), Some(begin), None, false).unwrap();
+        let at = begin + Duration::minutes(25);
+        let next = TimerEntry {
+            activity: Some(9),
+            ..entry()
+        };
+        engine.split(at, next).unwrap();
+
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            let summary: Vec<_> = requests.iter().map(|(method, target, _)| (method.as_str(), target.as_str())).collect();
+            assert_eq!(summary, [("PATCH", "/api/timesheets/7"), ("POST", "/api/timesheets")]);
+            assert_eq!(requests[0].2["end"], kimai_datetime(at));
+            assert_eq!(requests[1].2["begin"], kimai_datetime(at));
+            assert_eq!(requests[1].2["activity"], 9);
+            assert!(requests[1].2.get("end").is_none());
+        }
+        assert_eq!(engine.snapshot().current_entry.unwrap().id, Some(8));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn split_timers_end_the_timesheet_on_pause_and_start_the_next_on_resume() {
+        let (kimai, requests) = serve_kimai();
+        let (engine, dir) = engine("split-policy");
+        let rules = BookingRules {
+            pause_policy: PausePolicy::Split,
+            ..Default::default()
+        };
+        engine.start_at(Some(entry()), Some("work".into()), rules, None, None, false).unwrap();
+        engine.advance(Duration::minutes(20));
+        engine.pause().unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        engine.advance(Duration::minutes(5));
+        engine.resume().unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        assert_eq!(engine.snapshot().current_entry.unwrap().id, Some(8));
+        engine.advance(Duration::minutes(10));
+        engine.pause().unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        // Paused, the last timesheet already ended
+        let pauses = engine.snapshot().pauses;
+        engine.stop().unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+
+        let time = |at: DateTime<Utc>| kimai_datetime(at.trunc_subsecs(0));
+        let requests = requests.lock().unwrap();
+        let summary: Vec<_> = requests.iter().map(|(method, target, _)| (method.as_str(), target.as_str())).collect();
+        assert_eq!(
+            summary,
+            [("PATCH", "/api/timesheets/7"), ("POST", "/api/timesheets"), ("PATCH", "/api/timesheets/8")]
+        );
+        assert_eq!(requests[0].2["end"], time(pauses[0].start));
+        let resumed = &requests[1].2;
+        assert_eq!(resumed["begin"], time(pauses[0].end.unwrap()));
+        assert!(resumed.get("end").is_none());
+        assert_eq!(
+            (&resumed["project"], &resumed["activity"], &resumed["description"], &resumed["tags"]),
+            (&json!(2), &json!(3), &json!("Review"), &json!("meeting,client"))
+        );
+        assert_eq!(requests[2].2["begin"], time(pauses[0].end.unwrap()));
+        assert_eq!(requests[2].2["end"], time(pauses[1].start));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bookings_kimai_could_not_take_are_sent_on_the_next_try() {
+        let (kimai, requests) = serve_kimai();
+        let offline = KimaiState::default();
+        let (engine, dir) = engine("offline");
+        let rules = BookingRules {
+            pause_policy: PausePolicy::Split,
+            ..Default::default()
+        };
+        engine.start_at(Some(entry()), Some("work".into()), rules, None, None, false).unwrap();
+        engine.advance(Duration::minutes(20));
+        engine.pause().unwrap();
+        let err = submit_unsubmitted(&engine, &offline).await.unwrap_err();
+        assert!(matches!(err, TimerError::BookingQueued(KimaiError::NotConnected)));
+        engine.advance(Duration::minutes(5));
+        engine.resume().unwrap();
+        assert!(submit_unsubmitted(&engine, &offline).await.is_err());
+        engine.advance(Duration::minutes(10));
+        let pauses = engine.snapshot().pauses;
+        engine.stop().unwrap();
+
+        // The timesheet the resume asked for is left out: the stop creates its segment finished
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        let time = |at: DateTime<Utc>| kimai_datetime(at.trunc_subsecs(0));
+        let requests = requests.lock().unwrap();
+        let summary: Vec<_> = requests.iter().map(|(method, target, _)| (method.as_str(), target.as_str())).collect();
+        assert_eq!(summary, [("PATCH", "/api/timesheets/7"), ("POST", "/api/timesheets")]);
+        assert_eq!(requests[0].2["end"], time(pauses[0].start));
+        let resumed = pauses[0].end.unwrap();
+        assert_eq!(requests[1].2["begin"], time(resumed));
+        assert_eq!(requests[1].2["end"], time(resumed + Duration::minutes(10)));
+        assert!(engine.take_unsubmitted().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_timesheet_created_after_the_timer_moved_on_is_ended_by_the_queued_booking() {
+        let (engine, dir) = engine("in-flight");
+        let rules = BookingRules {
+            pause_policy: PausePolicy::Split,
+            ..Default::default()
+        };
+        engine.start_at(Some(entry()), Some("work".into()), rules, None, None, false).unwrap();
+        engine.advance(Duration::minutes(20));
+        engine.pause().unwrap();
+        engine.advance(Duration::minutes(5));
+        engine.resume().unwrap();
+        // Sent while the timer stops
+        let sending = engine.take_unsubmitted();
+        assert_eq!(sending.len(), 2);
+        engine.advance(Duration::minutes(10));
+        engine.stop().unwrap();
+
+        engine.link("work", 8).unwrap();
+        let queued = engine.take_unsubmitted();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].entries[0].id, Some(8));
+        assert!(queued[0].entries[0].end.is_some());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn single_timers_book_one_timesheet_without_the_pauses() {
+        let (kimai, requests) = serve_kimai();
+        let (engine, dir) = engine("single-policy");
+        let started = engine.start_at(Some(entry()), Some("work".into()), BookingRules::default(), None, None, false).unwrap();
+        engine.advance(Duration::minutes(20));
+        engine.pause().unwrap();
+        engine.advance(Duration::minutes(5));
+        engine.resume().unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+
+        engine.advance(Duration::minutes(10));
+        engine.stop().unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        let begin = started.start_time.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!((requests[0].0.as_str(), requests[0].1.as_str()), ("PATCH", "/api/timesheets/7"));
+        assert_eq!(requests[0].2["begin"], kimai_datetime(begin.trunc_subsecs(0)));
+        assert_eq!(requests[0].2["end"], kimai_datetime((begin + Duration::minutes(30)).trunc_subsecs(0)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn continued_and_late_timers_create_their_timesheet_and_run_on_it() {
+        let (kimai, requests) = serve_kimai();
+        let (engine, dir) = engine("continue");
+        engine.start_at(Some(entry()), Some("work".into()), BookingRules::default(), None, None, false).unwrap();
+        engine.advance(Duration::minutes(10));
+        let ended_at = engine.stop().unwrap().end_time.unwrap();
+        engine.advance(Duration::minutes(5));
+        engine.continue_last(BookingRules::default(), None).unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            let summary: Vec<_> = requests.iter().map(|(method, target, _)| (method.as_str(), target.as_str())).collect();
+            assert_eq!(summary, [("PATCH", "/api/timesheets/7"), ("POST", "/api/timesheets")]);
+            assert_eq!(requests[1].2["begin"], kimai_datetime(ended_at));
+            assert!(requests[1].2.get("end").is_none());
+        }
+        assert_eq!(engine.snapshot().current_entry.unwrap().id, Some(8));
+
+        engine.stop().unwrap();
+        let late = TimerEntry {
+            id: None,
+            activity: Some(9),
+            ..entry()
+        };
+        let begin = Utc::now() - Duration::hours(1);
+        engine.start_at(Some(late), Some("work".into()), BookingRules::default(), Some(begin), None, true).unwrap();
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        {
+            let requests = requests.lock().unwrap();
+            let (method, target, body) = requests.last().unwrap();
+            assert_eq!((method.as_str(), target.as_str()), ("POST", "/api/timesheets"));
+            assert_eq!(body["activity"], 9);
+        }
+        assert_eq!(engine.snapshot().current_entry.unwrap().id, Some(9));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn timers_not_on_a_timesheet_book_nothing() {
+        let (kimai, requests) = serve_kimai();
+        let (engine, dir) = engine("unlinked");
+        let unlinked = TimerEntry { id: None, ..entry() };
+        engine.start_at(Some(unlinked), Some("work".into()), BookingRules::default(), None, None, false).unwrap();
+        engine.stop().unwrap();
+
+        submit_unsubmitted(&engine, &kimai).await.unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn kimai_forms_take_local_times_without_an_offset() {
+        let booked = TimerEntry {
+            begin: "2024-05-06T07:00:00Z".into(),
+            end: Some("2024-05-06T09:15:00+0200".into()),
+            ..entry()
+        };
+        let form = form(&booked);
+        let utc = |value: &str| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc);
+        assert_eq!(form.begin, Some(kimai_datetime(utc("2024-05-06T07:00:00Z"))));
+        assert_eq!(form.end, Some(kimai_datetime(utc("2024-05-06T07:15:00Z"))));
+        assert_eq!((form.project, form.activity, form.billable), (Some(2), Some(3), Some(true)));
+    }
+}
@@ -0,0 +1,227 @@
+// Timer Tauri commands
+// Transitions go through the engine, which journals them and broadcasts
+// the new state as `timer://state`. What they book for a timer on a Kimai
+// timesheet is sent before the command returns.
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+use super::engine::{TimerSnapshot, TimerSplit};
+use super::autostop::AutoStop;
+use super::booking;
+use super::models::{BookingRules, RecoveredTimer, TimerEntry};
+use super::rounding::{self, RoundingPreview};
+use super::{TimerEngine, TimerJournal, TimerResult};
+use crate::kimai::cache::KimaiCache;
+use crate::kimai::commands::cache_path;
+use crate::kimai::models::KimaiTimeSheet;
+use crate::kimai::KimaiState;
+use crate::settings::SettingsState;
+
+/// How the profile books its timers; unknown profiles book them as tracked
+fn booking_rules(settings: &SettingsState, profile_id: Option<&str>) -> BookingRules {
+    let settings = settings.get();
+    let rules = settings
+        .profiles
+        .iter()
+        .find(|profile| Some(profile.id.as_str()) == profile_id)
+        .map(BookingRules::from)
+        .unwrap_or_default();
+    BookingRules {
+        split_at_midnight: settings.auto_stop.split_at_midnight,
+        ..rules
+    }
+}
+
+/// Sets up the automatic stop for a timer of the profile, with the
+/// calendar fetched when it connected
+fn schedule_auto_stop(engine: &TimerEngine, settings: &SettingsState, kimai: &KimaiState, profile_id: Option<&str>) {
+    let calendar = profile_id
+        .and_then(|profile_id| kimai.client(profile_id).ok())
+        .and_then(|client| client.calendar_config());
+    engine.schedule_auto_stop(AutoStop::from_settings(&settings.get().auto_stop, calendar.as_ref()));
+}
+
+#[tauri::command]
+pub fn timer_state(engine: State<'_, TimerEngine>) -> TimerSnapshot {
+    engine.snapshot()
+}
+
+/// Where the profile's last timesheet in the saved Kimai cache ends
+fn booked_until<R: Runtime>(app: &AppHandle<R>, profile_id: Option<&str>) -> Option<DateTime<Utc>> {
+    let path = cache_path(app, profile_id?).ok()?;
+    KimaiCache::load(&path)?.last_end()
+}
+
+/// Starts a timer, now or at `begin` for one started late; a begin in the
+/// past is moved up to where the profile's last entry stopped. With
+/// `create`, the entry is created in Kimai and the timer runs on it.
+#[tauri::command]
+pub async fn timer_start<R: Runtime>(
+    app: AppHandle<R>,
+    engine: State<'_, TimerEngine>,
+    settings: State<'_, SettingsState>,
+    entry: Option<TimerEntry>,
+    profile_id: Option<String>,
+    begin: Option<DateTime<Utc>>,
+    create: Option<bool>,
+) -> TimerResult<TimerSnapshot> {
+    let rules = booking_rules(&settings, profile_id.as_deref());
+    schedule_auto_stop(&engine, &settings, &app.state::<KimaiState>(), profile_id.as_deref());
+    let booked_until = booked_until(&app, profile_id.as_deref());
+    engine.start_at(entry, profile_id, rules, begin, booked_until, create.unwrap_or(false))?;
+    booked(&app).await
+}
+
+/// Starts the last stopped entry again from its end, on a new Kimai
+/// timesheet if it was booked there
+#[tauri::command]
+pub async fn timer_continue<R: Runtime>(
+    app: AppHandle<R>,
+    engine: State<'_, TimerEngine>,
+    journal: State<'_, TimerJournal>,
+    settings: State<'_, SettingsState>,
+    kimai: State<'_, KimaiState>,
+) -> TimerResult<TimerSnapshot> {
+    let profile_id = journal.last_stop().and_then(|stop| stop.profile_id);
+    schedule_auto_stop(&engine, &settings, &kimai, profile_id.as_deref());
+    let booked_until = booked_until(&app, profile_id.as_deref());
+    engine.continue_last(booking_rules(&settings, profile_id.as_deref()), booked_until)?;
+    booked(&app).await
+}
+
+/// Sends what the transition booked, then returns the state, which by then
+/// runs on any timesheet created for the timer
+async fn booked<R: Runtime>(app: &AppHandle<R>) -> TimerResult<TimerSnapshot> {
+    let engine = app.state::<TimerEngine>();
+    let result = booking::submit_unsubmitted(&engine, &app.state::<KimaiState>()).await;
+    spawn_booking_retry(app);
+    result.map(|()| engine.snapshot())
+}
+
+/// Keeps sending the bookings Kimai couldn't take yet, in the background
+pub fn spawn_booking_retry<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        booking::retry_unsubmitted(&app.state::<TimerEngine>(), &app.state::<KimaiState>()).await;
+    });
+}
+
+/// Pauses the timer; under the profile's split policy, its timesheet ends
+#[tauri::command]
+pub async fn timer_pause<R: Runtime>(app: AppHandle<R>, engine: State<'_, TimerEngine>) -> TimerResult<TimerSnapshot> {
+    engine.pause()?;
+    booked(&app).await
+}
+
+/// Resumes the timer; under the profile's split policy, a new timesheet
+/// starts with the same customer, project, activity, description and tags
+#[tauri::command]
+pub async fn timer_resume<R: Runtime>(app: AppHandle<R>, engine: State<'_, TimerEngine>) -> TimerResult<TimerSnapshot> {
+    engine.resume()?;
+    booked(&app).await
+}
+
+/// Stops the timer; the snapshot carries the finished entry and the
+/// timesheets it books, rounded
+#[tauri::command]
+pub async fn timer_stop<R: Runtime>(app: AppHandle<R>, engine: State<'_, TimerEngine>) -> TimerResult<TimerSnapshot> {
+    engine.stop()?;
+    booked(&app).await
+}
+
+#[tauri::command]
+pub fn timer_reset(engine: State<'_, TimerEngine>) -> TimerResult<TimerSnapshot> {
+    engine.reset()
+}
+
+#[tauri::command]
+pub fn timer_update_entry(engine: State<'_, TimerEngine>, entry: TimerEntry) -> TimerResult<TimerSnapshot> {
+    engine.update_entry(entry)
+}
+
+/// Ends the running entry at `at` and carries on with `entry`; the result
+/// carries the timesheets the part before books
+#[tauri::command]
+pub async fn timer_split<R: Runtime>(
+    app: AppHandle<R>,
+    engine: State<'_, TimerEngine>,
+    at: DateTime<Utc>,
+    entry: TimerEntry,
+) -> TimerResult<TimerSplit> {
+    let split = engine.split(at, entry)?;
+    Ok(TimerSplit {
+        state: booked(&app).await?,
+        ..split
+    })
+}
+
+/// The timesheets stopping now would book, as tracked and as rounded
+#[tauri::command]
+pub fn timer_preview_bookings(engine: State<'_, TimerEngine>) -> Vec<RoundingPreview> {
+    engine.preview_bookings()
+}
+
+/// How the profile's rounding books `entry`, for entries edited by hand
+#[tauri::command]
+pub fn timer_preview_rounding(
+    settings: State<'_, SettingsState>,
+    profile_id: Option<String>,
+    entry: TimerEntry,
+) -> RoundingPreview {
+    rounding::preview(&booking_rules(&settings, profile_id.as_deref()).rounding, &entry)
+}
+
+/// Saves entries finished outside the timer in Kimai, rounded like the
+/// timer's, e.g. a timesheet stopped by hand
+#[tauri::command]
+pub async fn timer_book(
+    settings: State<'_, SettingsState>,
+    kimai: State<'_, KimaiState>,
+    profile_id: String,
+    entries: Vec<TimerEntry>,
+) -> TimerResult<Vec<KimaiTimeSheet>> {
+    let rules = booking_rules(&settings, Some(&profile_id));
+    let booked: Vec<TimerEntry> = rounding::previews(&rules.rounding, &entries, None)
+        .into_iter()
+        .map(|preview| preview.booked)
+        .collect();
+    Ok(booking::submit(&*kimai.client(&profile_id)?, &booked).await?)
+}
+
+/// The timer the previous session left running, if any
+#[tauri::command]
+pub fn timer_recover(journal: State<'_, TimerJournal>) -> Option<RecoveredTimer> {
+    journal.recover()
+}
+
+/// Carries on with the recovered timer in the engine
+#[tauri::command]
+pub fn timer_recovery_resume(
+    engine: State<'_, TimerEngine>,
+    journal: State<'_, TimerJournal>,
+    settings: State<'_, SettingsState>,
+    kimai: State<'_, KimaiState>,
+) -> TimerResult<TimerSnapshot> {
+    let profile_id = journal.recover().and_then(|recovered| recovered.profile_id);
+    schedule_auto_stop(&engine, &settings, &kimai, profile_id.as_deref());
+    engine.resume_recovered(booking_rules(&settings, profile_id.as_deref()))
+}
+
+/// Stops the recovered timer at its last-known-alive time and returns the
+/// finished entry, rounded and ready to be saved.
+#[tauri::command]
+pub fn timer_recovery_stop(
+    journal: State<'_, TimerJournal>,
+    settings: State<'_, SettingsState>,
+) -> TimerResult<Option<TimerEntry>> {
+    let profile_id = journal.recover().and_then(|recovered| recovered.profile_id);
+    let rules = booking_rules(&settings, profile_id.as_deref());
+    let entry = journal.stop_recovered()?;
+    Ok(entry.map(|entry| rounding::preview(&rules.rounding, &entry).booked))
+}
+
+#[tauri::command]
+pub fn timer_recovery_discard(journal: State<'_, TimerJournal>) -> TimerResult<()> {
+    journal.discard()
+}
@@ -0,0 +1,1217 @@
+// Timer engine
+// Owns the timer state machine. Time comes from a monotonic clock anchored
+// to the wall clock, so it neither drifts with a throttled webview nor jumps
+// when the system clock is adjusted. Every transition is journaled and
+// announced as `timer://state`; while running, `timer://tick` fires on each
+// elapsed second. The ticker also carries out the automatic stop. Timers
+// started on a Kimai timesheet queue what they book for Kimai as they go:
+// under the split policy a pause ends the timesheet and a resume starts the
+// next, otherwise the stop books the one timesheet without the pauses.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{DateTime, Duration, Local, SecondsFormat, Utc};
+use serde::Serialize;
+
+use super::autostop::{self, AutoStop, AutoStopNotice, AutoStopNoticeKind};
+use super::booking::KimaiBookings;
+use super::models::{BookingRules, JournalEvent, PauseSegment, TimerEntry, TimerTransition};
+use super::rounding::{self, RoundingPreview};
+use super::{TimerError, TimerJournal, TimerResult};
+use crate::kimai::models::PausePolicy;
+
+pub const TIMER_STATE_EVENT: &str = "timer://state";
+pub const TIMER_TICK_EVENT: &str = "timer://tick";
+
+/// A wall clock running ahead of ours by more than this means the machine
+/// was suspended; the time asleep counts, as it does for Kimai.
+const SUSPEND_THRESHOLD: Duration = Duration::seconds(30);
+const IDLE_POLL: StdDuration = StdDuration::from_millis(250);
+
+type Listener<T> = Arc<dyn Fn(&T) + Send + Sync>;
+
+/// Wall-clock time derived from a monotonic instant
+struct Clock {
+    anchor: Instant,
+    wall: DateTime<Utc>,
+}
+
+impl Clock {
+    fn new() -> Self {
+        Self {
+            anchor: Instant::now(),
+            wall: Utc::now(),
+        }
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.wall + Duration::from_std(self.anchor.elapsed()).unwrap_or_default()
+    }
+
+    /// Re-anchors after a suspend, which the monotonic clock doesn't count.
+    /// Smaller differences and backward jumps are clock corrections and ignored.
+    fn resync(&mut self) {
+        if Utc::now() - self.now() > SUSPEND_THRESHOLD {
+            *self = Self::new();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerStatus {
+    Idle,
+    Running,
+    Paused,
+    Stopped,
+}
+
+/// A started timer
+#[derive(Debug, Clone)]
+struct Session {
+    /// As started; bookings take their begin, end and duration from the segments
+    entry: Option<TimerEntry>,
+    profile_id: Option<String>,
+    rules: BookingRules,
+    started_at: DateTime<Utc>,
+    pauses: Vec<PauseSegment>,
+    /// Books in Kimai as it goes, having started on a timesheet. The entry's
+    /// id is the timesheet it runs on, if there is one at the moment.
+    on_kimai: bool,
+    /// Rounded end of what was booked before, where bookings begin at the earliest
+    not_before: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    fn paused_at(&self) -> Option<DateTime<Utc>> {
+        self.pauses.last().filter(|pause| pause.end.is_none()).map(|pause| pause.start)
+    }
+
+    /// Time worked, pauses left out
+    fn elapsed(&self, now: DateTime<Utc>) -> Duration {
+        self.work_segments(now)
+            .iter()
+            .map(|(begin, end)| (*end - *begin).max(Duration::zero()))
+            .fold(Duration::zero(), |total, segment| total + segment)
+    }
+
+    /// Stretches of work between the pauses, the last one up to `now` unless paused
+    fn work_segments(&self, now: DateTime<Utc>) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let mut segments = Vec::new();
+        let mut begin = self.started_at;
+        for pause in &self.pauses {
+            segments.push((begin, pause.start));
+            match pause.end {
+                Some(end) => begin = end,
+                None => return segments,
+            }
+        }
+        segments.push((begin, now.max(begin)));
+        segments
+    }
+
+    /// Forgets what happened after `end`, for a stop that was due earlier
+    fn truncate(&mut self, end: DateTime<Utc>) {
+        self.pauses.retain(|pause| pause.start < end);
+        if let Some(pause) = self.pauses.last_mut() {
+            if pause.end.is_some_and(|resumed| resumed > end) {
+                pause.end = None;
+            }
+        }
+    }
+
+    /// The timesheets this timer maps onto, as tracked. Split timers book every closed
+    /// segment; a single timesheet is only known once stopped, ending as much
+    /// earlier as the pauses lasted.
+    fn bookings(&self, now: DateTime<Utc>, stopped: bool) -> Vec<TimerEntry> {
+        let id = self.entry.as_ref().and_then(|entry| entry.id);
+        match self.rules.pause_policy {
+            PausePolicy::Single if stopped => {
+                self.entries(vec![(self.started_at, self.started_at + self.elapsed(now))], id)
+            }
+            PausePolicy::Single => Vec::new(),
+            PausePolicy::Split => {
+                let mut segments = self.work_segments(now);
+                // The timesheet the timer runs on is the running segment's
+                if !stopped && self.paused_at().is_none() {
+                    segments.pop();
+                    return self.entries(segments, None);
+                }
+                self.entries(segments, id)
+            }
+        }
+    }
+
+    /// What the stop books in Kimai, rounded. Pauses of a split timer
+    /// already booked all but the running segment, which is rounded after them.
+    fn stop_bookings(&self, now: DateTime<Utc>) -> Vec<TimerEntry> {
+        match self.rules.pause_policy {
+            PausePolicy::Single => self.rounded(self.bookings(now, true)),
+            PausePolicy::Split if self.paused_at().is_some() => Vec::new(),
+            PausePolicy::Split => {
+                let mut segments = self.work_segments(now);
+                let running = segments.pop();
+                let booked = self.entries(segments.clone(), None).len();
+                segments.extend(running);
+                let id = self.entry.as_ref().and_then(|entry| entry.id);
+                self.rounded(self.entries(segments, id)).split_off(booked)
+            }
+        }
+    }
+
+    /// Where a timesheet started at `at` begins: after the rounded end of
+    /// what the timer booked up to then
+    fn next_begin(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        self.rounded(self.bookings(at, true))
+            .last()
+            .and_then(|booked| booked.end.as_deref().and_then(rounding::parse_time))
+            .map(|end| end.with_timezone(&Utc))
+            .into_iter()
+            .chain(self.not_before)
+            .fold(at, DateTime::max)
+    }
+
+    /// Entries for stretches of work, each split at midnight if so
+    /// configured. The first part of the last one is timesheet `id`.
+    fn entries(&self, spans: Vec<(DateTime<Utc>, DateTime<Utc>)>, id: Option<u64>) -> Vec<TimerEntry> {
+        let Some(entry) = &self.entry else {
+            return Vec::new();
+        };
+        let last = spans.len().saturating_sub(1);
+        spans
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, (begin, end))| {
+                let parts = match self.rules.split_at_midnight {
+                    true => autostop::split_at_midnight(begin, end, &Local),
+                    false => vec![(begin, end)],
+                };
+                parts
+                    .into_iter()
+                    .filter(|(begin, end)| end > begin)
+                    .enumerate()
+                    .map(move |(part, (begin, end))| TimerEntry {
+                        id: if index == last && part == 0 { id } else { None },
+                        begin: timestamp(begin),
+                        end: Some(timestamp(end)),
+                        duration: (end - begin).num_seconds(),
+                        ..entry.clone()
+                    })
+            })
+            .collect()
+    }
+
+    fn rounded(&self, entries: Vec<TimerEntry>) -> Vec<TimerEntry> {
+        rounding::previews(&self.rules.rounding, &entries, self.not_before)
+            .into_iter()
+            .map(|preview| preview.booked)
+            .collect()
+    }
+
+    /// Bookings if the timer stopped now, tracked and rounded
+    fn previews(&self, now: DateTime<Utc>) -> Vec<RoundingPreview> {
+        rounding::previews(&self.rules.rounding, &self.bookings(now, true), self.not_before)
+    }
+
+    /// The entry as it stands: under the split policy, the current segment's.
+    /// Its id stays the Kimai timesheet the timer runs on.
+    fn current_entry(&self, now: DateTime<Utc>) -> Option<TimerEntry> {
+        let mut entry = self.entry.clone()?;
+        entry.duration = self.elapsed(now).num_seconds();
+        if self.rules.pause_policy == PausePolicy::Split {
+            let (begin, end) = *self.work_segments(now).last()?;
+            entry.begin = timestamp(begin);
+            entry.duration = (end - begin).num_seconds();
+        }
+        Some(entry)
+    }
+
+    /// `entries` for Kimai, if the timer books there
+    fn for_kimai(&self, entries: Vec<TimerEntry>) -> Option<KimaiBookings> {
+        if !self.on_kimai {
+            return None;
+        }
+        let profile_id = self.profile_id.clone()?;
+        (!entries.is_empty()).then_some(KimaiBookings { profile_id, entries })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Phase {
+    Idle,
+    Active(Session),
+    Stopped {
+        ended_at: DateTime<Utc>,
+        entry: Option<TimerEntry>,
+        bookings: Vec<RoundingPreview>,
+    },
+}
+
+struct EngineState {
+    clock: Clock,
+    phase: Phase,
+    auto_stop: Option<AutoStop>,
+    /// Start of the timer the automatic stop was announced for
+    warned: Option<DateTime<Utc>>,
+    /// Bookings waiting to be sent to Kimai, oldest first
+    unsubmitted: Vec<KimaiBookings>,
+    /// A background retry of the bookings is under way
+    retrying: bool,
+}
+
+impl EngineState {
+    fn snapshot(&self, now: DateTime<Utc>) -> TimerSnapshot {
+        TimerSnapshot {
+            auto_stop_at: self.auto_stop_at(now),
+            ..self.phase.snapshot(now)
+        }
+    }
+
+    fn auto_stop_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let (Some(auto_stop), Phase::Active(session)) = (&self.auto_stop, &self.phase) else {
+            return None;
+        };
+        let paused = session.paused_at().is_some();
+        auto_stop.deadline(session.started_at, now, session.elapsed(now), paused, &Local)
+    }
+
+    /// The automatic stop's warning, given once per timer, or the stop itself
+    fn auto_stop_due(&mut self, now: DateTime<Utc>) -> Option<AutoStopNotice> {
+        let auto_stop = self.auto_stop?;
+        let at = self.auto_stop_at(now)?;
+        let Phase::Active(session) = &self.phase else {
+            return None;
+        };
+        let kind = if at <= now {
+            AutoStopNoticeKind::Stopped
+        } else if self.warned != Some(session.started_at) && at - auto_stop.warning <= now {
+            AutoStopNoticeKind::Warning
+        } else {
+            return None;
+        };
+        self.warned = Some(session.started_at);
+        Some(AutoStopNotice {
+            kind,
+            trigger: auto_stop.trigger,
+            at,
+        })
+    }
+}
+
+/// What every window, the tray and notifications show
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerSnapshot {
+    pub status: TimerStatus,
+    pub is_running: bool,
+    pub is_paused: bool,
+    pub start_time: Option<DateTime<Utc>>,
+    pub pause_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Seconds worked, pauses left out
+    pub elapsed_time: f64,
+    /// Seconds since the start, pauses included
+    pub total_elapsed_time: f64,
+    pub current_entry: Option<TimerEntry>,
+    pub profile_id: Option<String>,
+    pub pause_policy: PausePolicy,
+    pub pauses: Vec<PauseSegment>,
+    /// Finished timesheets to save in Kimai, rounded; grows with each pause
+    /// of a split timer
+    pub bookings: Vec<TimerEntry>,
+    /// When the running timer stops by itself
+    pub auto_stop_at: Option<DateTime<Utc>>,
+    pub can_start: bool,
+    pub can_pause: bool,
+    pub can_stop: bool,
+    pub can_resume: bool,
+}
+
+/// A timer split in two
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerSplit {
+    /// Finished timesheets for the part before the split, rounded, that are
+    /// left to save; a timer on a Kimai timesheet queued them itself
+    pub bookings: Vec<TimerEntry>,
+    /// The timer carrying on with the new entry
+    pub state: TimerSnapshot,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerTick {
+    /// Whole seconds worked
+    pub elapsed: i64,
+    /// Whole seconds since the start
+    pub total_elapsed: i64,
+    pub at: DateTime<Utc>,
+}
+
+impl Phase {
+    fn snapshot(&self, now: DateTime<Utc>) -> TimerSnapshot {
+        let idle = TimerSnapshot {
+            status: TimerStatus::Idle,
+            is_running: false,
+            is_paused: false,
+            start_time: None,
+            pause_time: None,
+            end_time: None,
+            elapsed_time: 0.0,
+            total_elapsed_time: 0.0,
+            current_entry: None,
+            profile_id: None,
+            pause_policy: PausePolicy::default(),
+            pauses: Vec::new(),
+            bookings: Vec::new(),
+            auto_stop_at: None,
+            can_start: true,
+            can_pause: false,
+            can_stop: false,
+            can_resume: false,
+        };
+
+        match self {
+            Phase::Idle => idle,
+            Phase::Stopped {
+                ended_at,
+                entry,
+                bookings,
+            } => TimerSnapshot {
+                status: TimerStatus::Stopped,
+                end_time: Some(*ended_at),
+                current_entry: entry.clone(),
+                bookings: bookings.iter().map(|booking| booking.booked.clone()).collect(),
+                ..idle
+            },
+            Phase::Active(session) => {
+                let paused = session.paused_at().is_some();
+                let total = (now - session.started_at).max(Duration::zero());
+                TimerSnapshot {
+                    status: if paused { TimerStatus::Paused } else { TimerStatus::Running },
+                    is_running: true,
+                    is_paused: paused,
+                    start_time: Some(session.started_at),
+                    pause_time: session.paused_at(),
+                    end_time: None,
+                    elapsed_time: seconds(session.elapsed(now)),
+                    total_elapsed_time: seconds(total),
+                    current_entry: session.current_entry(now),
+                    profile_id: session.profile_id.clone(),
+                    pause_policy: session.rules.pause_policy,
+                    pauses: session.pauses.clone(),
+                    bookings: session.rounded(session.bookings(now, false)),
+                    auto_stop_at: None,
+                    can_start: false,
+                    can_pause: !paused,
+                    can_stop: true,
+                    can_resume: paused,
+                }
+            }
+        }
+    }
+
+    fn session(&mut self) -> TimerResult<&mut Session> {
+        match self {
+            Phase::Active(session) => Ok(session),
+            _ => Err(TimerError::Transition("The timer is not running")),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimerEngine {
+    journal: TimerJournal,
+    state: Arc<Mutex<EngineState>>,
+    on_change: Option<Listener<TimerSnapshot>>,
+    on_auto_stop: Option<Listener<AutoStopNotice>>,
+}
+
+impl TimerEngine {
+    pub fn new(journal: TimerJournal) -> Self {
+        Self {
+            journal,
+            state: Arc::new(Mutex::new(EngineState {
+                clock: Clock::new(),
+                phase: Phase::Idle,
+                auto_stop: None,
+                warned: None,
+                unsubmitted: Vec::new(),
+                retrying: false,
+            })),
+            on_change: None,
+            on_auto_stop: None,
+        }
+    }
+
+    /// Called with the new state after every transition
+    pub fn on_change(mut self, listener: impl Fn(&TimerSnapshot) + Send + Sync + 'static) -> Self {
+        self.on_change = Some(Arc::new(listener));
+        self
+    }
+
+    /// Called with the automatic stop's warning and with the stop
+    pub fn on_auto_stop(mut self, listener: impl Fn(&AutoStopNotice) + Send + Sync + 'static) -> Self {
+        self.on_auto_stop = Some(Arc::new(listener));
+        self
+    }
+
+    pub fn snapshot(&self) -> TimerSnapshot {
+        let state = self.state.lock().unwrap();
+        state.snapshot(state.clock.now())
+    }
+
+    /// Sets when timers stop by themselves; `None` lets them run
+    pub fn schedule_auto_stop(&self, auto_stop: Option<AutoStop>) {
+        self.state.lock().unwrap().auto_stop = auto_stop;
+    }
+
+    /// Starts a new timer; one already running must be stopped first.
+    /// `begin` backdates it, for one started late. It reaches back no
+    /// further than the rounded end of the profile's last entry, or
+    /// `booked_until`, where its last timesheet in Kimai ends, nor into the
+    /// future. With `create`, an entry without an id is created in Kimai,
+    /// running, and the timer runs on that timesheet once it exists.
+    pub fn start_at(
+        &self,
+        entry: Option<TimerEntry>,
+        profile_id: Option<String>,
+        rules: BookingRules,
+        begin: Option<DateTime<Utc>>,
+        booked_until: Option<DateTime<Utc>>,
+        create: bool,
+    ) -> TimerResult<TimerSnapshot> {
+        let previous_end = self
+            .journal
+            .last_stop()
+            .filter(|stop| stop.profile_id == profile_id)
+            .map(|stop| {
+                stop.entry
+                    .and_then(|entry| rounding::preview(&rules.rounding, &entry).booked.end)
+                    .as_deref()
+                    .and_then(rounding::parse_time)
+                    .map_or(stop.at, |end| end.with_timezone(&Utc))
+            })
+            .into_iter()
+            .chain(booked_until)
+            .max();
+        let mut unsubmitted = None;
+        let snapshot = self.transition(|phase, now| {
+            if matches!(phase, Phase::Active(_)) {
+                return Err(TimerError::Transition("A timer is already running"));
+            }
+            let begin = match begin {
+                Some(begin) => previous_end.map_or(begin, |end| begin.max(end)).min(now),
+                None => now,
+            };
+            let entry = entry.map(|entry| TimerEntry {
+                begin: timestamp(begin),
+                end: None,
+                duration: 0,
+                ..entry
+            });
+            let create = create && profile_id.is_some() && entry.as_ref().is_some_and(|entry| entry.id.is_none());
+            let session = Session {
+                on_kimai: create || entry.as_ref().is_some_and(|entry| entry.id.is_some()),
+                entry: entry.clone(),
+                profile_id: profile_id.clone(),
+                rules,
+                started_at: begin,
+                pauses: Vec::new(),
+                not_before: previous_end,
+            };
+            if create {
+                let running = entry.clone().map(|entry| TimerEntry {
+                    begin: timestamp(session.next_begin(begin)),
+                    ..entry
+                });
+                unsubmitted = session.for_kimai(running.into_iter().collect());
+            }
+            *phase = Phase::Active(session);
+            Ok(vec![journal_event(TimerTransition::Start, begin, entry, profile_id)])
+        })?;
+        self.queue(unsubmitted);
+        Ok(snapshot)
+    }
+
+    /// Starts the last stopped entry again, from where it ended. One booked
+    /// in Kimai carries on there, on a new timesheet.
+    pub fn continue_last(&self, rules: BookingRules, booked_until: Option<DateTime<Utc>>) -> TimerResult<TimerSnapshot> {
+        let stop = self
+            .journal
+            .last_stop()
+            .ok_or(TimerError::Transition("There is no stopped timer to continue"))?;
+        let on_kimai = stop.entry.as_ref().is_some_and(|entry| entry.id.is_some());
+        let entry = stop.entry.map(|entry| TimerEntry { id: None, ..entry });
+        self.start_at(entry, stop.profile_id, rules, Some(stop.at), booked_until, on_kimai)
+    }
+
+    /// Pauses the timer; under the split policy this ends its timesheet
+    pub fn pause(&self) -> TimerResult<TimerSnapshot> {
+        let mut unsubmitted = None;
+        let snapshot = self.transition(|phase, now| {
+            let session = phase.session()?;
+            if session.paused_at().is_some() {
+                return Err(TimerError::Transition("The timer is already paused"));
+            }
+            if session.rules.pause_policy == PausePolicy::Split {
+                unsubmitted = session.for_kimai(session.stop_bookings(now));
+            }
+            session.pauses.push(PauseSegment { start: now, end: None });
+            Ok(vec![journal_event(TimerTransition::Pause, now, None, session.profile_id.clone())])
+        })?;
+        self.queue(unsubmitted);
+        Ok(snapshot)
+    }
+
+    /// Resumes the timer; under the split policy this starts a timesheet
+    /// like the last, which the timer runs on once created
+    pub fn resume(&self) -> TimerResult<TimerSnapshot> {
+        let mut unsubmitted = None;
+        let snapshot = self.transition(|phase, now| {
+            let session = phase.session()?;
+            if session.paused_at().is_none() {
+                return Err(TimerError::Transition("The timer is not paused"));
+            }
+            if let Some(pause) = session.pauses.last_mut() {
+                pause.end = Some(now.max(pause.start));
+            }
+            let mut events = vec![journal_event(TimerTransition::Resume, now, None, session.profile_id.clone())];
+            if session.rules.pause_policy == PausePolicy::Split {
+                let begin = session.next_begin(now);
+                let next = session.entry.clone().map(|entry| TimerEntry {
+                    id: None,
+                    begin: timestamp(begin),
+                    end: None,
+                    duration: 0,
+                    ..entry
+                });
+                unsubmitted = session.for_kimai(next.into_iter().collect());
+            }
+            // The paused timesheet is done; until the next one is created, a
+            // pause or stop creates the segment's timesheet instead
+            if let (Some(_), Some(entry)) = (&unsubmitted, &mut session.entry) {
+                entry.id = None;
+                events.push(journal_event(
+                    TimerTransition::Update,
+                    now,
+                    session.entry.clone(),
+                    session.profile_id.clone(),
+                ));
+            }
+            Ok(events)
+        })?;
+        self.queue(unsubmitted);
+        Ok(snapshot)
+    }
+
+    /// Changes the entry being timed; its begin is kept
+    pub fn update_entry(&self, entry: TimerEntry) -> TimerResult<TimerSnapshot> {
+        self.transition(|phase, now| {
+            let session = phase.session()?;
+            let begin = timestamp(session.started_at);
+            session.entry = Some(TimerEntry { begin, ..entry });
+            Ok(vec![journal_event(
+                TimerTransition::Update,
+                now,
+                session.entry.clone(),
+                session.profile_id.clone(),
+            )])
+        })
+    }
+
+    /// Stops the timer. The snapshot's entry is finished, ready to be saved.
+    pub fn stop(&self) -> TimerResult<TimerSnapshot> {
+        self.stop_at(None)
+    }
+
+    /// Stops at `at` if it already passed, e.g. when the machine slept
+    /// through an automatic stop
+    fn stop_at(&self, at: Option<DateTime<Utc>>) -> TimerResult<TimerSnapshot> {
+        let mut unsubmitted = None;
+        let snapshot = self.transition(|phase, now| {
+            let mut session = phase.session()?.clone();
+            let now = at.map_or(now, |at| at.min(now).max(session.started_at));
+            session.truncate(now);
+            let ended_at = session.paused_at().unwrap_or(now);
+            let bookings = session.previews(now);
+            unsubmitted = session.for_kimai(session.stop_bookings(now));
+            let entry = session.entry.clone().map(|entry| TimerEntry {
+                end: Some(timestamp(ended_at)),
+                duration: session.elapsed(now).num_seconds(),
+                ..entry
+            });
+            *phase = Phase::Stopped {
+                ended_at,
+                entry: entry.clone(),
+                bookings,
+            };
+            Ok(vec![journal_event(TimerTransition::Stop, ended_at, entry, session.profile_id)])
+        })?;
+        self.queue(unsubmitted);
+        Ok(snapshot)
+    }
+
+    /// Ends the running entry at `at` and carries on with `entry` from there,
+    /// with the pauses after `at`. The part before is finished as by a stop.
+    /// A timer on a Kimai timesheet queues its bookings, and a new timesheet
+    /// for the rest; any other returns them to be saved.
+    pub fn split(&self, at: DateTime<Utc>, entry: TimerEntry) -> TimerResult<TimerSplit> {
+        let mut bookings = Vec::new();
+        let mut unsubmitted = None;
+        let state = self.transition(|phase, now| {
+            let session = phase.session()?;
+            if at <= session.started_at || at >= now {
+                return Err(TimerError::Transition("The split must lie between the start and now"));
+            }
+            let mut before = session.clone();
+            before.truncate(at);
+            if !session.on_kimai {
+                bookings = before.previews(at);
+            }
+
+            let pauses: Vec<PauseSegment> = session
+                .pauses
+                .iter()
+                .filter(|pause| pause.end.is_none_or(|end| end > at))
+                .map(|pause| PauseSegment {
+                    start: pause.start.max(at),
+                    end: pause.end,
+                })
+                .collect();
+            // The next timesheet begins where the rounded part before ends
+            let begin = before.next_begin(at);
+            let entry = TimerEntry {
+                id: None,
+                begin: timestamp(begin),
+                end: None,
+                duration: 0,
+                ..entry
+            };
+            let profile_id = session.profile_id.clone();
+            // A split timer paused at the split starts the next timesheet on resume
+            let mut booked = before.stop_bookings(at);
+            if session.rules.pause_policy == PausePolicy::Single || before.paused_at().is_none() {
+                booked.push(entry.clone());
+            }
+            unsubmitted = session.for_kimai(booked);
+
+            // A start replaces the journal, so the part before leaves it
+            let mut events = vec![journal_event(TimerTransition::Start, at, Some(entry.clone()), profile_id.clone())];
+            for pause in &pauses {
+                events.push(journal_event(TimerTransition::Pause, pause.start, None, profile_id.clone()));
+                if let Some(end) = pause.end {
+                    events.push(journal_event(TimerTransition::Resume, end, None, profile_id.clone()));
+                }
+            }
+            session.entry = Some(entry);
+            session.started_at = at;
+            session.pauses = pauses;
+            session.not_before = Some(begin);
+            Ok(events)
+        })?;
+        self.queue(unsubmitted);
+        Ok(TimerSplit {
+            bookings: bookings.into_iter().map(|booking| booking.booked).collect(),
+            state,
+        })
+    }
+
+    /// Books the running timer on Kimai timesheet `id` from now on, e.g. one
+    /// created for it. If the timer went on to book its segment while the
+    /// timesheet was being created, that queued booking ends it instead.
+    pub fn link(&self, profile_id: &str, id: u64) -> TimerResult<TimerSnapshot> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let queued = state
+                .unsubmitted
+                .iter_mut()
+                .filter(|bookings| bookings.profile_id == profile_id)
+                .flat_map(|bookings| bookings.entries.iter_mut())
+                .find(|entry| entry.id.is_none() && entry.end.is_some());
+            if let Some(entry) = queued {
+                entry.id = Some(id);
+                return Ok(state.snapshot(state.clock.now()));
+            }
+        }
+        self.transition(|phase, now| {
+            let session = phase.session()?;
+            let entry = session.entry.as_mut().ok_or(TimerError::Transition("The timer has no entry"))?;
+            entry.id = Some(id);
+            Ok(vec![journal_event(
+                TimerTransition::Update,
+                now,
+                session.entry.clone(),
+                session.profile_id.clone(),
+            )])
+        })
+    }
+
+    /// Moves the clock on, as if that much time passed
+    #[cfg(test)]
+    pub fn advance(&self, by: Duration) {
+        self.state.lock().unwrap().clock.wall += by;
+    }
+
+    /// Takes the bookings waiting to be sent to Kimai
+    pub fn take_unsubmitted(&self) -> Vec<KimaiBookings> {
+        std::mem::take(&mut self.state.lock().unwrap().unsubmitted)
+    }
+
+    /// Puts back bookings Kimai couldn't take yet, ahead of those queued since
+    pub fn requeue(&self, bookings: Vec<KimaiBookings>) {
+        let mut state = self.state.lock().unwrap();
+        let newer = std::mem::take(&mut state.unsubmitted);
+        for bookings in bookings.into_iter().chain(newer) {
+            enqueue(&mut state.unsubmitted, bookings);
+        }
+    }
+
+    fn queue(&self, bookings: Option<KimaiBookings>) {
+        if let Some(bookings) = bookings {
+            enqueue(&mut self.state.lock().unwrap().unsubmitted, bookings);
+        }
+    }
+
+    /// Claims the background retry of queued bookings; false when there are
+    /// none or another retry has them
+    pub fn start_retrying(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let start = !state.retrying && !state.unsubmitted.is_empty();
+        state.retrying |= start;
+        start
+    }
+
+    /// Whether the retry has bookings left; it is released when not
+    pub fn keep_retrying(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.retrying = !state.unsubmitted.is_empty();
+        state.retrying
+    }
+
+    /// What stopping would book, or what the last stop booked
+    pub fn preview_bookings(&self) -> Vec<RoundingPreview> {
+        let state = self.state.lock().unwrap();
+        match &state.phase {
+            Phase::Active(session) => session.previews(state.clock.now()),
+            Phase::Stopped { bookings, .. } => bookings.clone(),
+            Phase::Idle => Vec::new(),
+        }
+    }
+
+    /// Back to idle, dropping a running timer without a finished entry
+    pub fn reset(&self) -> TimerResult<TimerSnapshot> {
+        self.transition(|phase, now| {
+            let event = match phase {
+                Phase::Active(session) => {
+                    Some(journal_event(TimerTransition::Stop, now, None, session.profile_id.clone()))
+                }
+                _ => None,
+            };
+            *phase = Phase::Idle;
+            Ok(event.into_iter().collect())
+        })
+    }
+
+    /// Carries on with the timer the journal recovered; a pause ends now
+    pub fn resume_recovered(&self, rules: BookingRules) -> TimerResult<TimerSnapshot> {
+        let recovered = self
+            .journal
+            .recover()
+            .ok_or(TimerError::Transition("There is no timer to recover"))?;
+        self.transition(|phase, now| {
+            if matches!(phase, Phase::Active(_)) {
+                return Err(TimerError::Transition("A timer is already running"));
+            }
+            let mut pauses = recovered.pauses.clone();
+            if let Some(pause) = pauses.last_mut().filter(|pause| pause.end.is_none()) {
+                pause.end = Some(now.max(pause.start));
+            }
+            *phase = Phase::Active(Session {
+                on_kimai: recovered.entry.as_ref().is_some_and(|entry| entry.id.is_some()),
+                entry: recovered.entry.clone(),
+                profile_id: recovered.profile_id.clone(),
+                rules,
+                started_at: recovered.started_at,
+                pauses,
+                not_before: None,
+            });
+            Ok(vec![journal_event(TimerTransition::Resume, now, None, recovered.profile_id.clone())])
+        })
+    }
+
+    /// Emits a tick on every elapsed second while the timer runs, on a
+    /// thread of its own so it keeps going whatever the webview does.
+    pub fn spawn_ticker(&self, on_tick: impl Fn(&TimerTick) + Send + 'static) {
+        let engine = self.clone();
+        thread::spawn(move || {
+            let mut last = None;
+            loop {
+                let (tick, due, wait) = {
+                    let mut state = engine.state.lock().unwrap();
+                    state.clock.resync();
+                    let now = state.clock.now();
+                    let due = state.auto_stop_due(now);
+                    let (tick, wait) = match &state.phase {
+                        Phase::Active(session) if session.paused_at().is_none() => {
+                            let elapsed = session.elapsed(now);
+                            let into_second = elapsed.num_milliseconds().rem_euclid(1000) as u64;
+                            let tick = TimerTick {
+                                elapsed: elapsed.num_seconds(),
+                                total_elapsed: (now - session.started_at).num_seconds(),
+                                at: now,
+                            };
+                            (Some(tick), StdDuration::from_millis(1000 - into_second))
+                        }
+                        _ => (None, IDLE_POLL),
+                    };
+                    (tick, due, wait)
+                };
+
+                if let Some(notice) = due {
+                    engine.auto_stop(notice);
+                }
+                if let Some(tick) = tick {
+                    if last != Some(tick.elapsed) {
+                        last = Some(tick.elapsed);
+                        on_tick(&tick);
+                    }
+                } else {
+                    last = None;
+                }
+                thread::sleep(wait);
+            }
+        });
+    }
+
+    fn auto_stop(&self, notice: AutoStopNotice) {
+        if notice.kind == AutoStopNoticeKind::Stopped {
+            if let Err(err) = self.stop_at(Some(notice.at)) {
+                eprintln!("Could not stop the timer automatically: {err}");
+                return;
+            }
+        }
+        if let Some(listener) = &self.on_auto_stop {
+            listener(&notice);
+        }
+    }
+
+    /// Applies a change at the current time, journals it and notifies
+    fn transition(
+        &self,
+        change: impl FnOnce(&mut Phase, DateTime<Utc>) -> TimerResult<Vec<JournalEvent>>,
+    ) -> TimerResult<TimerSnapshot> {
+        let mut state = self.state.lock().unwrap();
+        state.clock.resync();
+        let now = state.clock.now();
+
+        let mut phase = state.phase.clone();
+        // Nothing changes unless the transition is durable
+        for event in change(&mut phase, now)? {
+            self.journal.record(&event)?;
+        }
+        state.phase = phase;
+
+        let snapshot = state.snapshot(now);
+        drop(state);
+        if let Some(listener) = &self.on_change {
+            listener(&snapshot);
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Queues `bookings` for their profile. A timesheet of the profile still to
+/// be created running is dropped: the timer went on without it, and the new
+/// bookings create its segment finished.
+fn enqueue(queue: &mut Vec<KimaiBookings>, bookings: KimaiBookings) {
+    for queued in queue.iter_mut().filter(|queued| queued.profile_id == bookings.profile_id) {
+        queued.entries.retain(|entry| entry.id.is_some() || entry.end.is_some());
+    }
+    queue.retain(|queued| !queued.entries.is_empty());
+    queue.push(bookings);
+}
+
+fn journal_event(
+    kind: TimerTransition,
+    at: DateTime<Utc>,
+    entry: Option<TimerEntry>,
+    profile_id: Option<String>,
+) -> JournalEvent {
+    JournalEvent {
+        kind,
+        at,
+        entry,
+        profile_id,
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{SubsecRound, TimeZone};
+
+    use super::*;
+    use crate::kimai::models::{RoundingMode, RoundingRule};
+    use crate::settings::models::AutoStopTrigger;
+    use crate::timer::autostop::StopPoint;
+
+    /// `minute` minutes after 09:00
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 6, 9, 0, 0).unwrap() + Duration::minutes(minute.into())
+    }
+
+    fn session() -> Session {
+        Session {
+            entry: Some(TimerEntry {
+                id: Some(7),
+                description: Some("Review".into()),
+                customer: Some(1),
+                project: Some(2),
+                activity: Some(3),
+                billable: true,
+                tags: Some(vec!["meeting".into()]),
+                begin: timestamp(at(0)),
+                end: None,
+                duration: 0,
+            }),
+            profile_id: Some("work".into()),
+            rules: BookingRules::default(),
+            started_at: at(0),
+            pauses: vec![PauseSegment {
+                start: at(10),
+                end: Some(at(15)),
+            }],
+            on_kimai: true,
+            not_before: None,
+        }
+    }
+
+    fn split_rules() -> BookingRules {
+        BookingRules {
+            pause_policy: PausePolicy::Split,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn snapshots_leave_pauses_out_of_the_worked_time() {
+        let snapshot = Phase::Active(session()).snapshot(at(30));
+        assert_eq!(snapshot.status, TimerStatus::Running);
+        assert_eq!((snapshot.elapsed_time, snapshot.total_elapsed_time), (1500.0, 1800.0));
+        assert_eq!(snapshot.current_entry.unwrap().duration, 1500);
+        assert!(snapshot.bookings.is_empty());
+        assert!(snapshot.can_pause && !snapshot.can_resume);
+
+        let mut paused = session();
+        paused.pauses.push(PauseSegment { start: at(20), end: None });
+        let paused = Phase::Active(paused).snapshot(at(30));
+        assert_eq!(paused.status, TimerStatus::Paused);
+        assert_eq!(paused.pause_time, Some(at(20)));
+        assert_eq!(paused.elapsed_time, 900.0);
+        assert!(paused.can_resume && paused.can_stop);
+    }
+
+    #[test]
+    fn pause_policies_book_one_or_several_timesheets() {
+        let single = session().bookings(at(30), true);
+        assert_eq!(single.len(), 1);
+        assert_eq!((single[0].id, single[0].duration), (Some(7), 1500));
+        assert_eq!(single[0].begin, timestamp(at(0)));
+        assert_eq!(single[0].end, Some(timestamp(at(25))));
+
+        let split = Session {
+            rules: split_rules(),
+            ..session()
+        };
+        let current = split.current_entry(at(30)).unwrap();
+        assert_eq!((current.id, current.duration), (Some(7), 900));
+        assert_eq!(current.begin, timestamp(at(15)));
+        assert_eq!(split.bookings(at(30), false).len(), 1);
+
+        // The pause ended the first timesheet; the timer runs on the second
+        let booked = split.bookings(at(30), true);
+        assert_eq!(booked.len(), 2);
+        assert_eq!((booked[0].id, booked[0].duration), (None, 600));
+        assert_eq!(booked[0].end, Some(timestamp(at(10))));
+        assert_eq!((booked[1].id, booked[1].duration), (Some(7), 900));
+        assert_eq!(booked[1].tags, booked[0].tags);
+        assert_eq!(booked[1].activity, Some(3));
+    }
+
+    #[test]
+    fn bookings_are_rounded_but_the_tracked_time_is_kept() {
+        let mut rules = split_rules();
+        rules.rounding.rule = RoundingRule {
+            mode: RoundingMode::Default,
+            begin: 0,
+            end: 0,
+            duration: 15,
+        };
+        let session = Session { rules, ..session() };
+
+        let snapshot = Phase::Active(session.clone()).snapshot(at(30));
+        assert_eq!(snapshot.elapsed_time, 1500.0);
+        assert_eq!(snapshot.bookings[0].duration, 900);
+
+        let previews = session.previews(at(30));
+        assert_eq!(previews.len(), 2);
+        assert_eq!((previews[0].tracked.duration, previews[0].booked.duration), (600, 900));
+        assert_eq!(previews[0].booked.end, Some(timestamp(at(15))));
+        assert_eq!(previews[1].booked.duration, 900);
+    }
+
+    #[test]
+    fn rounded_segments_do_not_overlap() {
+        let mut rules = split_rules();
+        rules.rounding.rule = RoundingRule {
+            mode: RoundingMode::Default,
+            begin: 15,
+            end: 15,
+            duration: 0,
+        };
+        // 09:07-10:02 rounds to 09:00-10:15, so the segment from 10:05 starts at 10:15
+        let session = Session {
+            rules,
+            started_at: at(7),
+            pauses: vec![PauseSegment {
+                start: at(62),
+                end: Some(at(65)),
+            }],
+            ..session()
+        };
+
+        let previews = session.previews(at(100));
+        assert_eq!(previews[0].booked.begin, timestamp(at(0)));
+        assert_eq!(previews[0].booked.end, Some(timestamp(at(75))));
+        assert_eq!(previews[1].tracked.begin, timestamp(at(65)));
+        assert_eq!(previews[1].booked.begin, timestamp(at(75)));
+        assert_eq!(previews[1].booked.duration, 1800);
+
+        assert_eq!(session.next_begin(at(65)), at(75));
+        let stopped = session.stop_bookings(at(100));
+        assert_eq!(stopped.len(), 1);
+        assert_eq!((stopped[0].id, stopped[0].duration), (Some(7), 1800));
+        assert_eq!(stopped[0].begin, timestamp(at(75)));
+        assert_eq!(stopped[0].end, Some(timestamp(at(105))));
+    }
+
+    #[test]
+    fn automatic_stops_warn_once_and_stop_when_due() {
+        let mut state = EngineState {
+            clock: Clock::new(),
+            phase: Phase::Active(session()),
+            auto_stop: Some(AutoStop {
+                trigger: AutoStopTrigger::DayLimit,
+                point: StopPoint::WorkedTime(Duration::minutes(20)),
+                warning: Duration::minutes(5),
+            }),
+            warned: None,
+            unsubmitted: Vec::new(),
+            retrying: false,
+        };
+        assert!(state.auto_stop_due(at(18)).is_none());
+        let warning = state.auto_stop_due(at(21)).unwrap();
+        assert_eq!((warning.kind, warning.at), (AutoStopNoticeKind::Warning, at(25)));
+        assert!(state.auto_stop_due(at(22)).is_none());
+        // Noticed late, e.g. after a suspend: the stop still lands on time
+        let stop = state.auto_stop_due(at(40)).unwrap();
+        assert_eq!((stop.kind, stop.at), (AutoStopNoticeKind::Stopped, at(25)));
+
+        let mut stopped_in_pause = session();
+        stopped_in_pause.truncate(at(12));
+        assert_eq!(stopped_in_pause.paused_at(), Some(at(10)));
+        assert_eq!(stopped_in_pause.elapsed(at(12)), Duration::minutes(10));
+    }
+
+    #[test]
+    fn transitions_are_journaled_and_checked() {
+        let dir = std::env::temp_dir().join(format!("tikker-timer-engine-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let journal = TimerJournal::open(TimerJournal::path(&dir));
+        let engine = TimerEngine::new(journal.clone());
+
+        assert!(matches!(engine.pause(), Err(TimerError::Transition(_))));
+        let started = engine.start_at(session().entry, Some("work".into()), split_rules(), None, None, false).unwrap();
+        assert_eq!(started.status, TimerStatus::Running);
+        assert!(engine.start_at(None, None, BookingRules::default(), None, None, false).is_err());
+
+        engine.pause().unwrap();
+        assert!(engine.pause().is_err());
+        assert_eq!(journal.recover().unwrap().profile_id.as_deref(), Some("work"));
+        engine.resume().unwrap();
+
+        assert_eq!(engine.snapshot().pauses.len(), 1);
+
+        let stopped = engine.stop().unwrap();
+        assert_eq!(stopped.status, TimerStatus::Stopped);
+        let entry = stopped.current_entry.unwrap();
+        assert!(entry.end.is_some());
+        assert!(journal.recover().is_none());
+        assert!(engine.snapshot().can_start);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn late_starts_splits_and_continuations_leave_no_overlap() {
+        let dir = std::env::temp_dir().join(format!("tikker-timer-split-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let engine = TimerEngine::new(TimerJournal::open(TimerJournal::path(&dir)));
+        let work = Some("work".to_string());
+        // Not on a Kimai timesheet, so the split hands back what to save
+        let local = TimerEntry {
+            id: None,
+            ..session().entry.unwrap()
+        };
+
+        engine.start_at(Some(local.clone()), work.clone(), BookingRules::default(), None, None, false).unwrap();
+        let stopped_at = engine.stop().unwrap().end_time.unwrap();
+        let booked_end = stopped_at.trunc_subsecs(0);
+        let late = engine
+            .start_at(Some(local.clone()), work.clone(), BookingRules::default(), Some(stopped_at - Duration::minutes(20)), None, false)
+            .unwrap();
+        assert_eq!(late.start_time, Some(booked_end));
+        engine.reset().unwrap();
+
+        let in_kimai = Utc::now() - Duration::minutes(40);
+        let begin = Utc::now() - Duration::minutes(50);
+        let late = engine.start_at(Some(local.clone()), work.clone(), BookingRules::default(), Some(begin), Some(in_kimai), false).unwrap();
+        assert_eq!(late.start_time, Some(in_kimai));
+        engine.reset().unwrap();
+
+        let begin = Utc::now() - Duration::minutes(30);
+        let started = engine.start_at(Some(local.clone()), work, BookingRules::default(), Some(begin), None, false).unwrap();
+        assert_eq!(started.start_time, Some(begin));
+        let next = TimerEntry {
+            activity: Some(9),
+            ..local
+        };
+        assert!(engine.split(Utc::now() + Duration::minutes(1), next.clone()).is_err());
+        let split_at = begin + Duration::minutes(20);
+        let split = engine.split(split_at, next).unwrap();
+        assert_eq!(split.bookings.len(), 1);
+        assert_eq!(split.bookings[0].activity, Some(3));
+        assert_eq!(split.bookings[0].end, Some(timestamp(split_at)));
+        assert_eq!(split.state.start_time, Some(split_at));
+        let current = split.state.current_entry.unwrap();
+        assert_eq!((current.id, current.activity), (None, Some(9)));
+
+        let ended_at = engine.stop().unwrap().end_time.unwrap();
+        let continued = engine.continue_last(BookingRules::default(), None).unwrap();
+        assert_eq!(continued.start_time, Some(ended_at));
+        assert_eq!(continued.current_entry.unwrap().activity, Some(9));
+        assert!(engine.take_unsubmitted().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+// Timer journal
+// Append-only JSON lines, one per transition, fsync'd before a command
+// returns. A start replaces the file, so it only ever holds the current
+// session. While a timer runs, a heartbeat file records the last time the
+// app was alive, which is where a recovered timer can be stopped.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use super::models::{JournalEvent, PauseSegment, RecoveredTimer, TimerEntry, TimerTransition};
+use super::TimerResult;
+use crate::profiles::remove_securely;
+
+const JOURNAL_FILE: &str = "timer-journal.jsonl";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+struct Inner {
+    path: PathBuf,
+    alive_path: PathBuf,
+    /// Serializes writers and remembers whether a timer is running
+    running: Mutex<bool>,
+}
+
+/// Cheap to clone; clones share the same files
+#[derive(Clone)]
+pub struct TimerJournal {
+    inner: Arc<Inner>,
+}
+
+impl TimerJournal {
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(JOURNAL_FILE)
+    }
+
+    pub fn open(path: PathBuf) -> Self {
+        let running = replay(&read_events(&path)).is_some();
+        Self {
+            inner: Arc::new(Inner {
+                alive_path: path.with_extension("alive"),
+                path,
+                running: Mutex::new(running),
+            }),
+        }
+    }
+
+    /// Durably records a transition. A start begins a new journal.
+    pub fn record(&self, event: &JournalEvent) -> TimerResult<()> {
+        let mut running = self.inner.running.lock().unwrap();
+        let line = format!("{}\n", serde_json::to_string(event)?);
+
+        if event.kind == TimerTransition::Start {
+            write_atomic(&self.inner.path, line.as_bytes())?;
+        } else {
+            let mut file = OpenOptions::new().create(true).append(true).open(&self.inner.path)?;
+            // Starts a fresh line after one torn by a crash, so this one stays readable
+            if !ends_with_newline(&self.inner.path) {
+                file.write_all(b"\n")?;
+            }
+            file.write_all(line.as_bytes())?;
+            file.sync_data()?;
+        }
+
+        *running = event.kind != TimerTransition::Stop;
+        if *running {
+            write_atomic(&self.inner.alive_path, event.at.to_rfc3339().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// The timer left running by the previous session, if any
+    pub fn recover(&self) -> Option<RecoveredTimer> {
+        let _guard = self.inner.running.lock().unwrap();
+        let mut recovered = replay(&read_events(&self.inner.path))?;
+        if let Some(alive) = read_alive(&self.inner.alive_path) {
+            recovered.last_alive = recovered.last_alive.max(alive);
+        }
+        Some(recovered)
+    }
+
+    /// The stop that ended the last timer with an entry, unless a timer
+    /// started since or the last one was dropped
+    pub fn last_stop(&self) -> Option<JournalEvent> {
+        let _guard = self.inner.running.lock().unwrap();
+        read_events(&self.inner.path)
+            .pop()
+            .filter(|event| event.kind == TimerTransition::Stop && event.entry.is_some())
+    }
+
+    /// Stops the recovered timer where it was last known to run and returns
+    /// the finished entry.
+    pub fn stop_recovered(&self) -> TimerResult<Option<TimerEntry>> {
+        let Some(recovered) = self.recover() else {
+            return Ok(None);
+        };
+
+        let end = recovered.stop_time();
+        let entry = recovered.entry.clone().map(|mut entry| {
+            let tracked = end - recovered.started_at;
+            entry.end = Some(end.to_rfc3339());
+            entry.duration = (tracked.num_seconds() - recovered.paused_seconds).max(0);
+            entry
+        });
+        self.record(&JournalEvent {
+            kind: TimerTransition::Stop,
+            at: end,
+            entry: entry.clone(),
+            profile_id: recovered.profile_id,
+        })?;
+        Ok(entry)
+    }
+
+    /// Forgets the recovered timer without recording anything
+    pub fn discard(&self) -> TimerResult<()> {
+        let mut running = self.inner.running.lock().unwrap();
+        for path in [&self.inner.path, &self.inner.alive_path] {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        *running = false;
+        Ok(())
+    }
+
+    /// Securely removes the journal if any of it belongs to `profile_id`;
+    /// even a stopped session keeps the last entry's details.
+    pub fn purge_profile(&self, profile_id: &str) -> TimerResult<bool> {
+        let mut running = self.inner.running.lock().unwrap();
+        let events = read_events(&self.inner.path);
+        if !events.iter().any(|event| event.profile_id.as_deref() == Some(profile_id)) {
+            return Ok(false);
+        }
+        for path in [&self.inner.path, &self.inner.alive_path] {
+            remove_securely(path)?;
+        }
+        *running = false;
+        Ok(true)
+    }
+
+    /// Refreshes the heartbeat while a timer runs, on a thread of its own so
+    /// it keeps going whatever the webview does.
+    pub fn spawn_heartbeat(&self) {
+        let inner = Arc::clone(&self.inner);
+        thread::spawn(move || loop {
+            thread::sleep(HEARTBEAT_INTERVAL);
+            let running = inner.running.lock().unwrap();
+            if *running {
+                if let Err(err) = write_atomic(&inner.alive_path, Utc::now().to_rfc3339().as_bytes()) {
+                    eprintln!("Could not write the timer heartbeat: {err}");
+                }
+            }
+        });
+    }
+}
+
+/// Folds the journal into the timer it describes; `None` once stopped.
+fn replay(events: &[JournalEvent]) -> Option<RecoveredTimer> {
+    let mut timer: Option<RecoveredTimer> = None;
+    for event in events {
+        match event.kind {
+            TimerTransition::Start => {
+                timer = Some(RecoveredTimer {
+                    entry: event.entry.clone(),
+                    profile_id: event.profile_id.clone(),
+                    started_at: event.at,
+                    paused_at: None,
+                    paused_seconds: 0,
+                    pauses: Vec::new(),
+                    last_alive: event.at,
+                })
+            }
+            TimerTransition::Stop => timer = None,
+            _ => {
+                let Some(timer) = timer.as_mut() else {
+                    continue;
+                };
+                match event.kind {
+                    TimerTransition::Pause if timer.paused_at.is_none() => {
+                        timer.paused_at = Some(event.at);
+                        timer.pauses.push(PauseSegment {
+                            start: event.at,
+                            end: None,
+                        });
+                    }
+                    TimerTransition::Resume => {
+                        if let Some(paused_at) = timer.paused_at.take() {
+                            timer.paused_seconds += (event.at - paused_at).num_seconds().max(0);
+                            if let Some(pause) = timer.pauses.last_mut() {
+                                pause.end = Some(event.at);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if event.entry.is_some() {
+                    timer.entry = event.entry.clone();
+                }
+                timer.last_alive = timer.last_alive.max(event.at);
+            }
+        }
+    }
+    timer
+}
+
+/// Lines that don't parse, like one torn by a crash mid-write, are skipped
+fn read_events(path: &Path) -> Vec<JournalEvent> {
+    let Ok(data) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    data.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+fn ends_with_newline(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return true;
+    };
+    match file.seek(SeekFrom::End(-1)) {
+        Ok(_) => {
+            let mut last = [0u8; 1];
+            file.read_exact(&mut last).is_ok_and(|_| last[0] == b'\n')
+        }
+        // Empty file
+        Err(_) => true,
+    }
+}
+
+fn read_alive(path: &Path) -> Option<DateTime<Utc>> {
+    let data = fs::read_to_string(path).ok()?;
+    DateTime::parse_from_rfc3339(data.trim()).ok().map(|time| time.with_timezone(&Utc))
+}
+
+/// Writes and fsyncs a temporary file, then renames it into place, so the
+/// file is either the old or the new version after a crash.
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    // Persists the rename itself; directories can't be opened on Windows
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 6, 9, minute, 0).unwrap()
+    }
+
+    fn entry() -> TimerEntry {
+        TimerEntry {
+            id: None,
+            description: Some("Review".into()),
+            customer: Some(1),
+            project: Some(2),
+            activity: Some(3),
+            billable: true,
+            tags: None,
+            begin: at(0).to_rfc3339(),
+            end: None,
+            duration: 0,
+        }
+    }
+
+    fn event(kind: TimerTransition, minute: u32) -> JournalEvent {
+        JournalEvent {
+            kind,
+            at: at(minute),
+            entry: (kind == TimerTransition::Start).then(entry),
+            profile_id: Some("work".into()),
+        }
+    }
+
+    #[test]
+    fn replay_tracks_pauses_and_stops() {
+        let timer = replay(&[
+            event(TimerTransition::Start, 0),
+            event(TimerTransition::Pause, 10),
+            event(TimerTransition::Resume, 15),
+            event(TimerTransition::Pause, 20),
+        ])
+        .unwrap();
+        assert_eq!(timer.paused_seconds, 300);
+        assert_eq!(timer.pauses.len(), 2);
+        assert_eq!(timer.pauses[0].end, Some(at(15)));
+        assert_eq!(timer.stop_time(), at(20));
+
+        assert!(replay(&[event(TimerTransition::Start, 0), event(TimerTransition::Stop, 5)]).is_none());
+    }
+
+    #[test]
+    fn recovered_timers_stop_at_the_last_heartbeat() {
+        let dir = std::env::temp_dir().join(format!("tikker-timer-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = TimerJournal::path(&dir);
+
+        let journal = TimerJournal::open(path.clone());
+        journal.record(&event(TimerTransition::Start, 0)).unwrap();
+        journal.record(&event(TimerTransition::Pause, 10)).unwrap();
+        journal.record(&event(TimerTransition::Resume, 12)).unwrap();
+        write_atomic(&path.with_extension("alive"), at(30).to_rfc3339().as_bytes()).unwrap();
+
+        // A crash mid-write leaves a torn last line behind
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"kind\":\"pau").unwrap();
+
+        let journal = TimerJournal::open(path.clone());
+        assert_eq!(journal.recover().unwrap().last_alive, at(30));
+
+        let stopped = journal.stop_recovered().unwrap().unwrap();
+        assert_eq!(stopped.end.as_deref(), Some(at(30).to_rfc3339().as_str()));
+        assert_eq!(stopped.duration, 28 * 60);
+        assert!(journal.recover().is_none());
+
+        assert!(!journal.purge_profile("home").unwrap());
+        assert!(journal.purge_profile("work").unwrap());
+        assert!(!path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+// Timer
+// The running timer is journaled by Rust so a crash, a webview reload or a
+// forced quit can't lose it; the next launch offers to resume or stop it.
+
+pub mod autostop;
+pub mod booking;
+pub mod commands;
+pub mod engine;
+pub mod journal;
+pub mod models;
+pub mod rounding;
+
+use serde::{Serialize, Serializer};
+
+use crate::kimai::KimaiError;
+
+pub use engine::TimerEngine;
+pub use journal::TimerJournal;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TimerError {
+    #[error("Could not access the timer journal: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid timer event: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("{0}")]
+    Transition(&'static str),
+    #[error("Could not book the timer in Kimai: {0}")]
+    Booking(#[from] KimaiError),
+    #[error("Not booked in Kimai yet, retrying in the background: {0}")]
+    BookingQueued(KimaiError),
+}
+
+impl TimerError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TimerError::Io(_) => "io",
+            TimerError::Invalid(_) => "invalid",
+            TimerError::Transition(_) => "transition",
+            TimerError::Booking(_) | TimerError::BookingQueued(_) => "booking",
+        }
+    }
+}
+
+impl Serialize for TimerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ErrorPayload {
+            kind: &'static str,
+            message: String,
+        }
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+pub type TimerResult<T> = Result<T, TimerError>;
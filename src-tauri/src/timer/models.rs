@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::kimai::models::{KimaiProfile, PausePolicy, RoundingSettings};
+
+/// The entry being timed, as the timer store keeps it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Unset while the entry is still being filled in
+    #[serde(default)]
+    pub customer: Option<u64>,
+    #[serde(default)]
+    pub project: Option<u64>,
+    #[serde(default)]
+    pub activity: Option<u64>,
+    #[serde(default)]
+    pub billable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    pub begin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// Seconds
+    #[serde(default)]
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerTransition {
+    Start,
+    Pause,
+    Resume,
+    Stop,
+    /// The entry was edited while the timer ran
+    Update,
+}
+
+/// One line of the journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEvent {
+    pub kind: TimerTransition,
+    pub at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<TimerEntry>,
+    /// Profile the timer runs for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_id: Option<String>,
+}
+
+/// A pause of the timer; `end` is unset while it lasts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PauseSegment {
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// A timer that was still running (or paused) when the app went away
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredTimer {
+    pub entry: Option<TimerEntry>,
+    pub profile_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub paused_at: Option<DateTime<Utc>>,
+    /// Seconds spent paused before the last resume
+    pub paused_seconds: i64,
+    pub pauses: Vec<PauseSegment>,
+    /// Last moment the app was known to be running
+    pub last_alive: DateTime<Utc>,
+}
+
+impl RecoveredTimer {
+    /// Where a stop without the user's input ends the entry: the pause, or
+    /// the last sign of life
+    pub fn stop_time(&self) -> DateTime<Utc> {
+        self.paused_at.unwrap_or(self.last_alive).max(self.started_at)
+    }
+}
+
+/// How a profile's timers become Kimai timesheets
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookingRules {
+    pub pause_policy: PausePolicy,
+    pub rounding: RoundingSettings,
+    /// One booking per day for timers that cross midnight
+    pub split_at_midnight: bool,
+}
+
+impl From<&KimaiProfile> for BookingRules {
+    fn from(profile: &KimaiProfile) -> Self {
+        Self {
+            pause_policy: profile.pause_policy,
+            rounding: profile.rounding.clone(),
+            split_at_midnight: false,
+        }
+    }
+}
@@ -0,0 +1,215 @@
+// Time rounding
+// Kimai's rounding modes applied to bookings before they are submitted, so
+// what the app shows is what ends up in Kimai. Steps line up with the wall
+// clock of each timestamp's own offset.
+
+use chrono::{DateTime, Duration, FixedOffset, SecondsFormat, Utc};
+use serde::Serialize;
+
+use super::models::TimerEntry;
+use crate::kimai::models::{RoundingMode, RoundingRule, RoundingSettings};
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Down,
+    Up,
+    Closest,
+}
+
+/// An entry as tracked and as it will be booked
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundingPreview {
+    pub rule: RoundingRule,
+    pub tracked: TimerEntry,
+    pub booked: TimerEntry,
+}
+
+/// Rounds `entry` with the rule of its customer, or the profile's
+pub fn preview(settings: &RoundingSettings, entry: &TimerEntry) -> RoundingPreview {
+    let rule = settings.rule_for(entry.customer);
+    RoundingPreview {
+        rule,
+        tracked: entry.clone(),
+        booked: round(&rule, entry),
+    }
+}
+
+/// Rounds `entries`, booked one after the other. A rounded begin would
+/// overlap the rounded end before it, so it starts there instead, as does
+/// one before `not_before`.
+pub fn previews(
+    settings: &RoundingSettings,
+    entries: &[TimerEntry],
+    not_before: Option<DateTime<Utc>>,
+) -> Vec<RoundingPreview> {
+    let mut floor = not_before.map(|time| time.fixed_offset());
+    entries
+        .iter()
+        .map(|entry| {
+            let mut preview = preview(settings, entry);
+            floor = start_after(&mut preview.booked, floor);
+            preview
+        })
+        .collect()
+}
+
+/// Moves the begin of `entry` up to `floor`, keeping its end unless that
+/// lies before; returns where the next entry may begin
+fn start_after(entry: &mut TimerEntry, floor: Option<DateTime<FixedOffset>>) -> Option<DateTime<FixedOffset>> {
+    let Some(begin) = parse_time(&entry.begin) else {
+        return floor;
+    };
+    let end = entry.end.as_deref().and_then(parse_time);
+    let Some(later) = floor.filter(|floor| *floor > begin) else {
+        return end.or(floor);
+    };
+    let begin = later.with_timezone(begin.offset());
+    entry.begin = format(begin);
+    let Some(end) = end else {
+        return Some(begin);
+    };
+    let end = end.max(begin);
+    entry.end = Some(format(end));
+    entry.duration = (end - begin).num_seconds();
+    Some(end)
+}
+
+/// Rounds begin and end, then the duration. The end moves with the rounded
+/// duration, as Kimai books begin and end. Without an end, only the begin
+/// is rounded.
+pub fn round(rule: &RoundingRule, entry: &TimerEntry) -> TimerEntry {
+    let mut rounded = entry.clone();
+    if !rule.is_active() {
+        return rounded;
+    }
+    let Some(begin) = parse_time(&entry.begin) else {
+        return rounded;
+    };
+    let [to_begin, to_end, to_duration] = directions(rule.mode);
+    let begin = round_time(begin, rule.begin, to_begin);
+    rounded.begin = format(begin);
+
+    let Some(end) = entry.end.as_deref().and_then(parse_time) else {
+        return rounded;
+    };
+    let end = round_time(end, rule.end, to_end).max(begin);
+    let duration = round_seconds((end - begin).num_seconds(), rule.duration, to_duration);
+    rounded.end = Some(format(begin + Duration::seconds(duration)));
+    rounded.duration = duration;
+    rounded
+}
+
+/// Reads RFC 3339 timestamps, and Kimai's `2019-08-28T13:20:00+0200`
+pub fn parse_time(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%z"))
+        .ok()
+}
+
+/// Directions for begin, end and duration
+fn directions(mode: RoundingMode) -> [Direction; 3] {
+    match mode {
+        RoundingMode::Default => [Direction::Down, Direction::Up, Direction::Up],
+        RoundingMode::Closest => [Direction::Closest; 3],
+        RoundingMode::Floor => [Direction::Down; 3],
+        RoundingMode::Ceil => [Direction::Up; 3],
+    }
+}
+
+fn round_time(time: DateTime<FixedOffset>, minutes: u32, direction: Direction) -> DateTime<FixedOffset> {
+    let local = time.naive_local().and_utc().timestamp();
+    time + Duration::seconds(round_seconds(local, minutes, direction) - local)
+}
+
+/// Like Kimai, the closest step is the lower one when halfway
+fn round_seconds(seconds: i64, minutes: u32, direction: Direction) -> i64 {
+    let step = i64::from(minutes) * 60;
+    if step == 0 {
+        return seconds;
+    }
+    let rest = seconds.rem_euclid(step);
+    if rest == 0 {
+        return seconds;
+    }
+    match direction {
+        Direction::Down => seconds - rest,
+        Direction::Closest if rest <= step / 2 => seconds - rest,
+        Direction::Up | Direction::Closest => seconds - rest + step,
+    }
+}
+
+fn format(time: DateTime<FixedOffset>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(begin: &str, end: Option<&str>) -> TimerEntry {
+        TimerEntry {
+            id: None,
+            description: None,
+            customer: Some(4),
+            project: Some(2),
+            activity: Some(3),
+            billable: true,
+            tags: None,
+            begin: begin.into(),
+            end: end.map(Into::into),
+            duration: 0,
+        }
+    }
+
+    fn rule(mode: RoundingMode, begin: u32, end: u32, duration: u32) -> RoundingRule {
+        RoundingRule {
+            mode,
+            begin,
+            end,
+            duration,
+        }
+    }
+
+    #[test]
+    fn modes_round_begin_end_and_duration() {
+        let tracked = entry("2024-05-06T09:07:30+02:00", Some("2024-05-06T10:08:00+02:00"));
+
+        let default = round(&rule(RoundingMode::Default, 15, 15, 0), &tracked);
+        assert_eq!(default.begin, "2024-05-06T09:00:00+02:00");
+        assert_eq!(default.end.as_deref(), Some("2024-05-06T10:15:00+02:00"));
+        assert_eq!(default.duration, 4500);
+
+        let closest = round(&rule(RoundingMode::Closest, 15, 15, 0), &tracked);
+        assert_eq!(closest.begin, "2024-05-06T09:00:00+02:00");
+        assert_eq!(closest.end.as_deref(), Some("2024-05-06T10:15:00+02:00"));
+
+        let floor = round(&rule(RoundingMode::Floor, 0, 0, 15), &tracked);
+        assert_eq!(floor.begin, "2024-05-06T09:07:30+02:00");
+        assert_eq!(floor.end.as_deref(), Some("2024-05-06T10:07:30+02:00"));
+        assert_eq!(floor.duration, 3600);
+
+        let ceil = round(&rule(RoundingMode::Ceil, 0, 0, 15), &tracked);
+        assert_eq!(ceil.duration, 4500);
+
+        let running = round(&rule(RoundingMode::Ceil, 15, 15, 15), &entry("2024-05-06T09:07:30Z", None));
+        assert_eq!((running.begin.as_str(), running.end), ("2024-05-06T09:15:00Z", None));
+
+        let from_kimai = round(&rule(RoundingMode::Floor, 15, 15, 0), &entry("2024-05-06T09:07:30+0200", None));
+        assert_eq!(from_kimai.begin, "2024-05-06T09:00:00+02:00");
+    }
+
+    #[test]
+    fn customer_rules_take_precedence() {
+        let mut settings = RoundingSettings {
+            rule: rule(RoundingMode::Default, 0, 0, 30),
+            ..Default::default()
+        };
+        let tracked = entry("2024-05-06T09:00:00Z", Some("2024-05-06T09:20:00Z"));
+        assert_eq!(preview(&settings, &tracked).booked.duration, 1800);
+
+        settings.customers.insert(4, rule(RoundingMode::Default, 0, 0, 0));
+        let unrounded = preview(&settings, &tracked);
+        assert_eq!(unrounded.booked, unrounded.tracked);
+    }
+}
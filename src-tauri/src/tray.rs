@@ -46,4 +46,12 @@ pub fn init_macos_menu_extra<R: Runtime>(app: &tauri::AppHandle<R>) -> tauri::Re
         .build(app);
 
     Ok(())
-} 
\ No newline at end of file
+} 
+/// Shows the time worked next to the menu bar icon, or nothing when idle
+pub fn show_elapsed<R: Runtime>(app: &tauri::AppHandle<R>, elapsed: Option<i64>) {
+    let Some(tray) = app.tray_by_id("menu_extra") else {
+        return;
+    };
+    let title = elapsed.map(|seconds| format!("{}:{:02}", seconds / 3600, seconds / 60 % 60));
+    let _ = tray.set_title(title);
+}
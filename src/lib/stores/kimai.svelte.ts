@@ -10,12 +10,19 @@ import type {
     KimaiTask,
     KimaiVersion,
     KimaiConfig,
-    KimaiAuthConfig,
+    KimaiProfile,
     KimaiConnectionState,
-    KimaiCache
+    KimaiCache,
+    KimaiPolicyViolation,
+    KimaiSwitchOutcome,
+    KimaiFeature,
+    KimaiTimeSheetPageEvent,
+    KimaiTimeSheetWalkResult
 } from '$lib/types/kimai.js';
-import { KimaiApiClient, createKimaiClient, validateAuthConfig } from '$lib/utils/kimai-api.js';
+import { KimaiApiClient, createKimaiClient, partiallySaved, validateAuthConfig } from '$lib/utils/kimai-api.js';
 import settingsStore from './settings.svelte.js';
+import { recordEvent } from '$lib/utils/history.js';
+import type { TaskEvent } from '$lib/types/task.js';
 
 // Kimai state
 let connectionState = $state<KimaiConnectionState>({
@@ -49,6 +56,17 @@ let isLoading = $state({
 // Error state
 let error = $state<string | null>(null);
 
+// Task changes go to the history database as TaskEvents
+function recordTaskEvent(type: TaskEvent['type'], taskId: number) {
+    recordEvent({
+        source: 'task',
+        type,
+        taskId,
+        timestamp: new Date().toISOString(),
+        profileId: apiClient?.profileId
+    });
+}
+
 // Kimai store functions
 export const kimaiStore = {
     // Connection Management
@@ -68,27 +86,35 @@ export const kimaiStore = {
         return connectionState.isConnecting;
     },
 
-    async connect(authConfig?: KimaiAuthConfig): Promise<KimaiConnectionState> {
-        const config = authConfig || settingsStore.currentProfile?.auth;
+    // Whether the connected server offers `feature`, as probed on connect
+    supports(feature: KimaiFeature): boolean {
+        return connectionState.capabilities?.features.includes(feature) ?? false;
+    },
+
+    async connect(profile?: KimaiProfile): Promise<KimaiConnectionState> {
+        const target = profile || settingsStore.currentProfile;
 
-        if (!config) {
+        if (!target) {
             throw new Error('No authentication configuration provided');
         }
 
-        if (!validateAuthConfig(config)) {
+        if (!validateAuthConfig(target.auth)) {
             throw new Error('Invalid authentication configuration');
         }
 
         try {
             error = null;
-            apiClient = createKimaiClient(config);
+            apiClient = createKimaiClient(target);
+
+            // The saved cache fills the UI while the server is still being asked
+            const saved = await apiClient.loadCache().catch(() => null);
+            if (saved) cache = saved;
+            else this.clearCache();
+
             connectionState = await apiClient.connect();
-            currentUser = await apiClient.getCurrentUser();
+            currentUser = connectionState.user ?? null;
 
-            // Load initial data
-            await this.loadCustomers();
-            await this.loadProjects();
-            await this.loadActivities();
+            this.refreshCache().catch(err => console.error('Cache refresh failed:', err));
 
             return connectionState;
         } catch (err) {
@@ -103,6 +129,7 @@ export const kimaiStore = {
     },
 
     disconnect() {
+        apiClient?.disconnect().catch(() => {});
         connectionState = {
             isConnected: false,
             isConnecting: false
@@ -315,10 +342,10 @@ export const kimaiStore = {
 
         try {
             isLoading.timeSheets = true;
-            const response = await apiClient.getTimeSheets(params);
-            cache.timeSheets = response.data;
+            const timeSheets = await apiClient.getTimeSheets(params);
+            cache.timeSheets = timeSheets;
             cache.lastUpdated.timeSheets = new Date().toISOString();
-            return response.data;
+            return timeSheets;
         } catch (err) {
             error = err instanceof Error ? err.message : 'Failed to load time sheets';
             throw err;
@@ -327,6 +354,21 @@ export const kimaiStore = {
         }
     },
 
+    // Long ranges for the history view; the results stay out of the cache
+    async walkTimeSheets(
+        walkId: string,
+        query: { begin?: string; end?: string },
+        onPage?: (page: KimaiTimeSheetPageEvent) => void
+    ): Promise<KimaiTimeSheetWalkResult> {
+        if (!apiClient) throw new Error('Not connected to Kimai');
+        return apiClient.walkTimeSheets(walkId, query, onPage);
+    },
+
+    async cancelTimeSheetWalk(walkId: string): Promise<boolean> {
+        if (!apiClient) return false;
+        return apiClient.cancelTimeSheetWalk(walkId);
+    },
+
     async loadActiveTimeSheets(): Promise<KimaiTimeSheet[]> {
         if (!apiClient) throw new Error('Not connected to Kimai');
 
@@ -345,6 +387,12 @@ export const kimaiStore = {
         }
     },
 
+    // Violations of the server's timesheet rules, found before submitting
+    async validateTimeSheet(timeSheet: Partial<KimaiTimeSheet>, id?: number): Promise<KimaiPolicyViolation[]> {
+        if (!apiClient) throw new Error('Not connected to Kimai');
+        return apiClient.validateTimeSheet(timeSheet, id);
+    },
+
     async createTimeSheet(timeSheet: Partial<KimaiTimeSheet>): Promise<KimaiTimeSheet> {
         if (!apiClient) throw new Error('Not connected to Kimai');
 
@@ -353,6 +401,9 @@ export const kimaiStore = {
             cache.timeSheets = [newTimeSheet, ...cache.timeSheets];
             return newTimeSheet;
         } catch (err) {
+            // It exists on the server even though a custom field failed
+            const saved = partiallySaved<KimaiTimeSheet>(err);
+            if (saved) cache.timeSheets = [saved, ...cache.timeSheets];
             error = err instanceof Error ? err.message : 'Failed to create time sheet';
             throw err;
         }
@@ -368,11 +419,45 @@ export const kimaiStore = {
             );
             return updatedTimeSheet;
         } catch (err) {
+            const saved = partiallySaved<KimaiTimeSheet>(err);
+            if (saved) {
+                cache.timeSheets = cache.timeSheets.map(timeSheet => (timeSheet.id === id ? saved : timeSheet));
+            }
             error = err instanceof Error ? err.message : 'Failed to update time sheet';
             throw err;
         }
     },
 
+    // Stops timesheet `id` and starts `next` where it ends, in one step
+    async switchTimeSheet(id: number, next: Partial<KimaiTimeSheet>): Promise<KimaiSwitchOutcome> {
+        if (!apiClient) throw new Error('Not connected to Kimai');
+
+        try {
+            const result = await apiClient.switchTimeSheet(id, next);
+            const current = result.outcome === 'rolled_back' ? result.running : result.stopped;
+            cache.timeSheets = cache.timeSheets.map(timeSheet =>
+                timeSheet.id === id ? current : timeSheet
+            );
+            if (result.outcome === 'switched') {
+                cache.timeSheets = [result.started, ...cache.timeSheets];
+            }
+            return result;
+        } catch (err) {
+            error = err instanceof Error ? err.message : 'Failed to switch time sheet';
+            throw err;
+        }
+    },
+
+    async cancelQueuedStart(): Promise<boolean> {
+        if (!apiClient) return false;
+        return apiClient.cancelQueuedStart();
+    },
+
+    // A queued start that landed after all
+    addTimeSheet(timeSheet: KimaiTimeSheet): void {
+        cache.timeSheets = [timeSheet, ...cache.timeSheets.filter(existing => existing.id !== timeSheet.id)];
+    },
+
     async deleteTimeSheet(id: number): Promise<void> {
         if (!apiClient) throw new Error('Not connected to Kimai');
 
@@ -448,6 +533,7 @@ export const kimaiStore = {
 
         try {
             const newTask = await apiClient.createTask(task);
+            recordTaskEvent('created', newTask.id);
             cache.tasks = [newTask, ...cache.tasks];
             return newTask;
         } catch (err) {
@@ -461,6 +547,7 @@ export const kimaiStore = {
 
         try {
             const updatedTask = await apiClient.updateTask(id, updates);
+            recordTaskEvent('updated', id);
             cache.tasks = cache.tasks.map(task =>
                 task.id === id ? updatedTask : task
             );
@@ -476,6 +563,7 @@ export const kimaiStore = {
 
         try {
             await apiClient.deleteTask(id);
+            recordTaskEvent('deleted', id);
             cache.tasks = cache.tasks.filter(task => task.id !== id);
         } catch (err) {
             error = err instanceof Error ? err.message : 'Failed to delete task';
@@ -489,6 +577,7 @@ export const kimaiStore = {
 
         try {
             const updatedTask = await apiClient.startTask(id);
+            recordTaskEvent('started', id);
             cache.tasks = cache.tasks.map(task =>
                 task.id === id ? updatedTask : task
             );
@@ -504,6 +593,7 @@ export const kimaiStore = {
 
         try {
             const updatedTask = await apiClient.stopTask(id);
+            recordTaskEvent('stopped', id);
             cache.tasks = cache.tasks.map(task =>
                 task.id === id ? updatedTask : task
             );
@@ -519,6 +609,7 @@ export const kimaiStore = {
 
         try {
             const updatedTask = await apiClient.closeTask(id);
+            recordTaskEvent('closed', id);
             cache.tasks = cache.tasks.map(task =>
                 task.id === id ? updatedTask : task
             );
@@ -591,16 +682,24 @@ export const kimaiStore = {
         };
     },
 
-    async refreshCache() {
-        if (!connectionState.isConnected) return;
+    // Refreshes the saved cache in Rust, which only fetches what changed or
+    // went stale, and keeps it on disk for the next start
+    async refreshCache(force = false) {
+        if (!apiClient || !connectionState.isConnected) return;
 
-        await Promise.all([
-            this.loadCustomers(),
-            this.loadProjects(),
-            this.loadActivities(),
-            this.loadTimeSheets(),
-            this.loadTasks()
-        ]);
+        const client = apiClient;
+        const keys = ['customers', 'projects', 'activities', 'timeSheets', 'tasks'] as const;
+        keys.forEach(key => (isLoading[key] = true));
+        try {
+            const refreshed = await client.refreshCache(force);
+            // A profile switched meanwhile has its own cache by now
+            if (apiClient === client) cache = refreshed;
+        } catch (err) {
+            error = err instanceof Error ? err.message : 'Failed to refresh the cache';
+            throw err;
+        } finally {
+            keys.forEach(key => (isLoading[key] = false));
+        }
     },
 
     // Loading States
@@ -12,6 +12,7 @@ import type { KimaiUser, KimaiAuthConfig } from '$lib/types/kimai.js';
 import { DEFAULT_SESSION } from '$lib/types/session.js';
 import settingsStore from './settings.svelte.js';
 import kimaiStore from './kimai.svelte.js';
+import { recordEvent as recordHistoryEvent } from '$lib/utils/history.js';
 
 // Session state
 let sessionState = $state<SessionState>({
@@ -121,8 +122,9 @@ export const sessionStore = {
             // Logout current session
             await this.logout();
 
-            // Switch profile
+            // Switch profile, and to the session data kept for it
             settingsStore.setCurrentProfile(profileId);
+            this.loadSession();
 
             // Auto-login if enabled
             if (sessionState.autoLogin) {
@@ -205,6 +207,13 @@ export const sessionStore = {
         };
 
         sessionEvents = [event, ...sessionEvents.slice(0, 99)]; // Keep last 100 events
+        recordHistoryEvent({
+            source: 'session',
+            type,
+            timestamp: event.timestamp,
+            profileId: settingsStore.currentProfile?.id,
+            details
+        });
     },
 
     get sessionEvents() {
@@ -294,7 +303,7 @@ export const sessionStore = {
                 rememberCredentials: sessionState.rememberCredentials
             };
 
-            localStorage.setItem('tikker-session', JSON.stringify(sessionData));
+            localStorage.setItem(sessionKey(), JSON.stringify(sessionData));
         } catch (error) {
             console.error('Failed to save session:', error);
         }
@@ -302,11 +311,14 @@ export const sessionStore = {
 
     loadSession(): void {
         try {
-            const stored = localStorage.getItem('tikker-session');
-            if (stored) {
-                const sessionData = JSON.parse(stored);
-                sessionState = { ...sessionState, ...sessionData };
-            }
+            const stored = localStorage.getItem(sessionKey());
+            sessionState = {
+                currentSession: { ...DEFAULT_SESSION },
+                recentSessions: [],
+                autoLogin: false,
+                rememberCredentials: false,
+                ...(stored ? JSON.parse(stored) : {})
+            };
         } catch (error) {
             console.error('Failed to load session:', error);
         }
@@ -314,7 +326,7 @@ export const sessionStore = {
 
     clearSession(): void {
         try {
-            localStorage.removeItem('tikker-session');
+            localStorage.removeItem(sessionKey());
             sessionState = {
                 currentSession: { ...DEFAULT_SESSION },
                 recentSessions: [],
@@ -346,7 +358,13 @@ export const sessionStore = {
     }
 };
 
-// Initialize session on load
+// Session data is kept per profile so servers and clients never mix
+function sessionKey(): string {
+    return `tikker-session:${settingsStore.currentProfile?.id ?? 'none'}`;
+}
+
+// Initialize session on load; the old global key mixed every profile's data
+localStorage.removeItem('tikker-session');
 sessionStore.loadSession();
 
 // Export the store
@@ -1,12 +1,29 @@
 // Settings Store
-// Manages application settings with localStorage persistence
+// Mirrors the settings file owned by the Rust side (settings_get / settings_patch)
 
-import type { AppSettings } from '$lib/types/settings.js';
-import type { KimaiProfile, KimaiAuthConfig } from '$lib/types/kimai.js';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import type {
+    AppSettings,
+    BundleImportMode,
+    BundleImportResult,
+    KemaiImportResult,
+    KemaiPreview
+} from '$lib/types/settings.js';
+import type { KimaiProfile, KimaiAuthConfig, KimaiSecretStatus } from '$lib/types/kimai.js';
 import { DEFAULT_SETTINGS } from '$lib/types/settings.js';
 
+const LEGACY_STORAGE_KEY = 'tikker-settings';
+
 // Settings state
-let settings = $state<AppSettings>(loadSettings());
+let settings = $state<AppSettings>({ ...DEFAULT_SETTINGS });
+
+loadSettings();
+
+// Other windows, or Rust, changed the settings
+listen<AppSettings>('settings://changed', (event) => {
+    settings = mergeWithDefaults(event.payload);
+});
 
 // Settings store functions
 export const settingsStore = {
@@ -21,6 +38,19 @@ export const settingsStore = {
         saveSettings(settings);
     },
 
+    // Credentials are kept by Rust; the encrypted-file fallback needs a passphrase
+    async secretStatus(): Promise<KimaiSecretStatus> {
+        return invoke<KimaiSecretStatus>('secrets_status');
+    },
+
+    async unlockSecrets(passphrase: string): Promise<KimaiSecretStatus> {
+        return invoke<KimaiSecretStatus>('secrets_unlock', { passphrase });
+    },
+
+    async lockSecrets(): Promise<KimaiSecretStatus> {
+        return invoke<KimaiSecretStatus>('secrets_lock');
+    },
+
     // Profile Management
     get profiles() {
         return settings.profiles;
@@ -48,12 +78,19 @@ export const settingsStore = {
         saveSettings(settings);
     },
 
-    deleteProfile(id: string) {
+    // Rust removes the profile and securely purges its cache, history,
+    // timer journal and credential
+    async deleteProfile(id: string) {
         settings.profiles = settings.profiles.filter(profile => profile.id !== id);
         if (settings.currentProfileId === id) {
             settings.currentProfileId = settings.profiles[0]?.id;
         }
-        saveSettings(settings);
+        localStorage.removeItem(`tikker-session:${id}`);
+        try {
+            settings = mergeWithDefaults(await invoke<AppSettings>('profiles_delete', { profileId: id }));
+        } catch (error) {
+            console.error('Failed to delete profile data:', error);
+        }
     },
 
     setCurrentProfile(id: string) {
@@ -114,48 +151,91 @@ export const settingsStore = {
         saveSettings(settings);
     },
 
-    // Export/Import
-    export() {
-        return JSON.stringify(settings, null, 2);
+    // Export/Import bundles, built by Rust; credentials are only included
+    // sealed with the given passphrase
+    async exportBundle(passphrase?: string): Promise<string> {
+        return invoke<string>('settings_export_bundle', { passphrase: passphrase || null });
     },
 
-    import(settingsJson: string) {
-        try {
-            const importedSettings = JSON.parse(settingsJson) as AppSettings;
-            // Validate imported settings
-            if (isValidSettings(importedSettings)) {
-                settings = importedSettings;
-                saveSettings(settings);
-                return true;
-            }
-            return false;
-        } catch (error) {
-            console.error('Failed to import settings:', error);
-            return false;
-        }
+    async bundleHasSecrets(bundle: string): Promise<boolean> {
+        return invoke<boolean>('settings_bundle_has_secrets', { bundle });
+    },
+
+    async importBundle(
+        bundle: string,
+        mode: BundleImportMode,
+        passphrase?: string
+    ): Promise<BundleImportResult> {
+        const result = await invoke<BundleImportResult>('settings_import_bundle', {
+            bundle,
+            mode,
+            passphrase: passphrase || null
+        });
+        settings = mergeWithDefaults(result.settings);
+        return result;
+    },
+
+    // Kemai import, read by Rust from its settings file (default location without a path)
+    async kemaiDefaultPath(): Promise<string | null> {
+        return invoke<string | null>('settings_kemai_default_path');
+    },
+
+    async previewKemai(path?: string): Promise<KemaiPreview> {
+        return invoke<KemaiPreview>('settings_kemai_preview', { path: path || null });
+    },
+
+    async importKemai(path?: string): Promise<KemaiImportResult> {
+        const result = await invoke<KemaiImportResult>('settings_kemai_import', { path: path || null });
+        settings = mergeWithDefaults(result.settings);
+        return result;
     }
 };
 
-// Local Storage Functions
-function loadSettings(): AppSettings {
+// Persistence
+async function loadSettings() {
     try {
-        const stored = localStorage.getItem('tikker-settings');
-        if (stored) {
-            const parsed = JSON.parse(stored) as AppSettings;
-            // Merge with defaults to handle missing properties
-            return mergeWithDefaults(parsed);
+        // Settings from before the settings file are imported once
+        const legacy = localStorage.getItem(LEGACY_STORAGE_KEY);
+        if (legacy) {
+            await invoke<AppSettings | null>('settings_import_local_storage', {
+                legacy: JSON.parse(legacy)
+            });
+            localStorage.removeItem(LEGACY_STORAGE_KEY);
         }
+
+        settings = mergeWithDefaults(await invoke<AppSettings>('settings_get'));
     } catch (error) {
         console.error('Failed to load settings:', error);
     }
-    return { ...DEFAULT_SETTINGS };
 }
 
 function saveSettings(settings: AppSettings) {
+    // Missing keys are left alone by the merge patch; null clears them
+    const patch = { ...settings, currentProfileId: settings.currentProfileId ?? null };
+    invoke('settings_patch', { patch }).catch(async (error) => {
+        // New credentials can't be stored while the secrets file is locked
+        if (error?.kind === 'secret_locked' && (await promptUnlock())) {
+            return saveSettings(settings);
+        }
+        console.error('Failed to save settings:', error);
+    });
+}
+
+async function promptUnlock(): Promise<boolean> {
+    const status = await invoke<KimaiSecretStatus>('secrets_status');
+    const passphrase = prompt(
+        status.initialized
+            ? 'Enter the passphrase that protects your Kimai credentials'
+            : 'No system keyring was found. Choose a passphrase to encrypt your Kimai credentials'
+    );
+    if (!passphrase) return false;
+
     try {
-        localStorage.setItem('tikker-settings', JSON.stringify(settings));
+        await invoke('secrets_unlock', { passphrase });
+        return true;
     } catch (error) {
-        console.error('Failed to save settings:', error);
+        alert((error as { message?: string })?.message ?? 'Could not unlock the credentials');
+        return false;
     }
 }
 
@@ -166,22 +246,11 @@ function mergeWithDefaults(partial: Partial<AppSettings>): AppSettings {
         ui: { ...DEFAULT_SETTINGS.ui, ...partial.ui },
         events: { ...DEFAULT_SETTINGS.events, ...partial.events },
         autoRefresh: { ...DEFAULT_SETTINGS.autoRefresh, ...partial.autoRefresh },
+        autoStop: { ...DEFAULT_SETTINGS.autoStop, ...partial.autoStop },
         ssl: { ...DEFAULT_SETTINGS.ssl, ...partial.ssl }
     };
 }
 
-function isValidSettings(settings: any): settings is AppSettings {
-    return (
-        settings &&
-        typeof settings === 'object' &&
-        Array.isArray(settings.profiles) &&
-        typeof settings.ui === 'object' &&
-        typeof settings.events === 'object' &&
-        typeof settings.autoRefresh === 'object' &&
-        typeof settings.ssl === 'object'
-    );
-}
-
 function generateProfileId(): string {
     return `profile_${Date.now()}_${Math.random().toString(36).substr(2, 9)}`;
 }
@@ -1,21 +1,64 @@
 // Timer Store
 // Manages timer state, controls, and persistence
 
-import type { TimerState, TimerEntry, TimerSettings, TimerEvent, TimerHistory } from '$lib/types/timer.js';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import type {
+    TimerState,
+    TimerEntry,
+    TimerSettings,
+    TimerEvent,
+    TimerHistory,
+    TimerTick,
+    RecoveredTimer,
+    RoundingPreview,
+    TimerSplit,
+    AutoStopNotice
+} from '$lib/types/timer.js';
 import type { KimaiTimeSheet } from '$lib/types/kimai.js';
 import { kimaiStore } from './index.js';
 import { settingsStore } from './index.js';
 import { DEFAULT_TIMER_STATE, DEFAULT_TIMER_SETTINGS } from '$lib/types/timer.js';
+import * as history from '$lib/utils/history.js';
 
-// Timer state
-let timerState = $state<TimerState>(loadTimerState());
+// How far back the in-memory history reaches; older entries stay in the database
+const HISTORY_DAYS = 31;
+
+// Timer state, owned by the Rust engine and mirrored from its events
+let timerState = $state<TimerState>({ ...DEFAULT_TIMER_STATE });
 let timerSettings = $state<TimerSettings>(loadTimerSettings());
 let timerHistory = $state<TimerHistory>({ entries: [], totalTime: 0, billableTime: 0 });
+let recoveredTimer = $state<RecoveredTimer | null>(null);
+// Why the last transition's bookings didn't reach Kimai, if they didn't
+let bookingError = $state<string | null>(null);
 
-// Timer interval for updates
-let timerInterval: ReturnType<typeof setInterval> | null = null;
 let notificationInterval: ReturnType<typeof setInterval> | null = null;
 
+// Every window receives the same transitions and ticks
+listen<TimerState>('timer://state', (event) => applyState(event.payload));
+listen<AutoStopNotice>('timer://auto-stop', (event) => {
+    const notice = event.payload;
+    const at = new Date(notice.at).toLocaleTimeString([], { hour: '2-digit', minute: '2-digit' });
+    if (notice.kind === 'warning') {
+        notify('Timer stops soon', `The running timer will stop automatically at ${at}.`);
+    } else {
+        notify('Timer stopped', `The timer was stopped automatically at ${at}.`);
+        dispatchTimerEvent('stop', {
+            duration: timerState.currentEntry?.duration ?? 0,
+            entry: timerState.currentEntry,
+            bookings: timerState.bookings,
+            autoStop: notice.trigger
+        });
+    }
+});
+listen<TimerTick>('timer://tick', (event) => {
+    if (!timerState.isRunning || timerState.isPaused) return;
+    timerState.elapsedTime = event.payload.elapsed;
+    timerState.totalElapsedTime = event.payload.totalElapsed;
+    if (timerState.currentEntry) timerState.currentEntry.duration = event.payload.elapsed;
+    dispatchTimerEvent('tick', { elapsed: event.payload.elapsed });
+});
+
 // Timer store functions
 export const timerStore = {
     // Get current timer state
@@ -33,106 +76,149 @@ export const timerStore = {
         return timerHistory;
     },
 
+    // Timer left running by a crash or forced quit, until the user decides
+    get recovered() {
+        return recoveredTimer;
+    },
+
+    get bookingError() {
+        return bookingError;
+    },
+
+    // Whether the timer runs on Kimai time sheet `id`, which it then keeps in step
+    bookedOn(id: number): boolean {
+        return timerState.isRunning && timerState.currentEntry?.id === id;
+    },
+
     // Timer Controls
-    start(entry?: Partial<TimerEntry>) {
+    // `begin` backdates the start, though not past where the last entry stopped.
+    // With `create`, the timer creates `entry` as a Kimai time sheet and runs on it.
+    async start(entry?: Partial<TimerEntry>, begin?: Date, create = false): Promise<boolean> {
         if (!timerState.canStart) return false;
+        const state = await transition('timer_start', {
+            entry: entry ? { ...entry, begin: new Date().toISOString(), duration: 0 } : null,
+            profileId: settingsStore.currentProfile?.id ?? null,
+            begin: begin?.toISOString() ?? null,
+            create
+        });
+        if (!state) return false;
+        dispatchTimerEvent('start', { entry: state.currentEntry });
+        return true;
+    },
 
-        const now = new Date();
-        timerState = {
-            ...timerState,
-            isRunning: true,
-            isPaused: false,
-            status: 'running',
-            startTime: now,
-            pauseTime: null,
-            endTime: null,
-            elapsedTime: 0,
-            totalElapsedTime: 0,
-            currentEntry: entry ? {
-                ...entry,
-                begin: now.toISOString(),
-                duration: 0
-            } as TimerEntry : undefined,
-            canStart: false,
-            canPause: false,
-            canStop: true,
-            canResume: false
-        };
-
-        startTimerInterval();
-        startNotificationInterval();
-        saveTimerState();
-        dispatchTimerEvent('start', { entry: timerState.currentEntry });
+    // Starts the last stopped entry again from its end, on a new Kimai time
+    // sheet if it was booked there
+    async continueLast(): Promise<boolean> {
+        if (!timerState.canStart) return false;
+        const state = await transition('timer_continue');
+        if (!state) return false;
+        dispatchTimerEvent('start', { entry: state.currentEntry, continued: true });
         return true;
     },
 
-    stop() {
-        if (!timerState.canStop) return false;
-
-        const now = new Date();
-        const finalDuration = timerState.totalElapsedTime;
-
-        // Create time sheet entry if we have current entry
-        // if (timerState.currentEntry) {
-        //     const timeSheet: Partial<KimaiTimeSheet> = {
-        //         begin: timerState.currentEntry.begin,
-        //         end: now.toISOString(),
-        //         duration: Math.round(finalDuration),
-        //         description: timerState.currentEntry.description,
-        //         billable: timerState.currentEntry.billable,
-        //         activity: timerState.currentEntry.activity,
-        //         project: timerState.currentEntry.project,
-        //         customer: timerState.currentEntry.customer
-        //     };
-
-        //     // Save to Kimai
-        //     kimaiStore.createTimeSheet(timeSheet as KimaiTimeSheet).then(() => {
-        //         // Update history
-        //         updateTimerHistory();
-        //     }).catch(error => {
-        //         console.error('Failed to save time sheet:', error);
-        //     });
-        // }
-
-        timerState = {
-            ...DEFAULT_TIMER_STATE,
-            endTime: now
-        };
+    // Ends the running entry at `at` and carries on with `entry`. Returns the
+    // timesheets left to book for the part before the split: none for a
+    // timer on a Kimai time sheet, which books them itself.
+    async split(at: Date, entry: Partial<TimerEntry>): Promise<TimerEntry[]> {
+        if (!timerState.isRunning || !timerState.currentEntry) return [];
+        bookingError = null;
+        const previousId = timerState.currentEntry?.id;
+        try {
+            const result = await invoke<TimerSplit>('timer_split', {
+                at: at.toISOString(),
+                entry: { ...timerState.currentEntry, ...entry }
+            });
+            applyState(result.state);
+            dispatchTimerEvent('split', { entry: result.state.currentEntry, bookings: result.bookings, previousId });
+            return result.bookings;
+        } catch (error) {
+            if (await failedBooking(error)) return [];
+            console.error('Timer timer_split failed:', error);
+            return [];
+        }
+    },
 
-        stopTimerInterval();
-        stopNotificationInterval();
-        saveTimerState();
-        dispatchTimerEvent('stop', { duration: finalDuration });
-        return true;
+    // Under the profile's split policy a pause ends the timer's Kimai time
+    // sheet, and a resume starts the next one, which `entry` then carries
+    async pause(): Promise<boolean> {
+        if (!timerState.canPause) return false;
+        const state = await transition('timer_pause');
+        if (state) dispatchTimerEvent('pause', { elapsed: state.elapsedTime, entry: state.currentEntry });
+        return state !== null;
     },
 
-    reset() {
-        timerState = { ...DEFAULT_TIMER_STATE };
-        stopTimerInterval();
-        stopNotificationInterval();
-        saveTimerState();
-        dispatchTimerEvent('stop', { duration: 0 });
+    async resume(): Promise<boolean> {
+        if (!timerState.canResume) return false;
+        const previousId = timerState.currentEntry?.id;
+        const state = await transition('timer_resume');
+        if (state) dispatchTimerEvent('resume', { entry: state.currentEntry, previousId });
+        return state !== null;
     },
 
-    // Timer Updates
-    updateElapsedTime() {
-        if (!timerState.isRunning || !timerState.startTime) return;
+    // The returned entry is finished (end and duration set), ready to be saved
+    // Returns the timesheets to book: one, or one per worked segment when
+    // the profile splits on pause
+    async stop(): Promise<TimerEntry[]> {
+        if (!timerState.canStop) return [];
+        const state = await transition('timer_stop');
+        if (!state) return [];
+        dispatchTimerEvent('stop', {
+            duration: state.currentEntry?.duration ?? 0,
+            entry: state.currentEntry,
+            bookings: state.bookings
+        });
+        return state.bookings;
+    },
 
-        const now = new Date();
-        const totalElapsed = (now.getTime() - timerState.startTime.getTime()) / 1000;
+    async reset() {
+        if (await transition('timer_reset')) dispatchTimerEvent('stop', { duration: 0 });
+    },
 
-        timerState = {
-            ...timerState,
-            elapsedTime: totalElapsed,
-            totalElapsedTime: totalElapsed
-        };
+    // Rounding Previews
+    // What stopping now would book, next to the tracked times
+    async previewBookings(): Promise<RoundingPreview[]> {
+        return invoke<RoundingPreview[]>('timer_preview_bookings');
+    },
 
-        // Update current entry duration
-        if (timerState.currentEntry) {
-            timerState.currentEntry.duration = Math.round(timerState.totalElapsedTime);
-        }
+    async previewRounding(entry: TimerEntry, profileId?: string): Promise<RoundingPreview> {
+        return invoke<RoundingPreview>('timer_preview_rounding', {
+            entry,
+            profileId: profileId ?? settingsStore.currentProfile?.id ?? null
+        });
+    },
+
+    // Saves entries finished outside the timer in Kimai, rounded like the
+    // timer's: one with an id updates that time sheet
+    async book(entries: TimerEntry[]): Promise<KimaiTimeSheet[]> {
+        const timeSheets = await invoke<KimaiTimeSheet[]>('timer_book', {
+            profileId: settingsStore.currentProfile?.id,
+            entries
+        });
+        timeSheets.forEach((timeSheet) => kimaiStore.addTimeSheet(timeSheet));
+        return timeSheets;
+    },
+
+    // Crash Recovery
+    // Picks the recovered timer up again in the engine
+    async resumeRecovered() {
+        if (!recoveredTimer) return;
+        const state = await invoke<TimerState>('timer_recovery_resume');
+        recoveredTimer = null;
+        applyState(state);
+        dispatchTimerEvent('resume', { entry: state.currentEntry, recovered: true });
+    },
+
+    // Ends the recovered timer at its last-known-alive time
+    async stopRecovered(): Promise<TimerEntry | null> {
+        const entry = await invoke<TimerEntry | null>('timer_recovery_stop');
+        recoveredTimer = null;
+        dispatchTimerEvent('stop', { duration: entry?.duration ?? 0, entry, recovered: true });
+        return entry;
+    },
 
-        dispatchTimerEvent('tick', { elapsed: timerState.totalElapsedTime });
+    async discardRecovered() {
+        await invoke('timer_recovery_discard');
+        recoveredTimer = null;
     },
 
     // Settings Management
@@ -142,10 +228,9 @@ export const timerStore = {
     },
 
     // Entry Management
-    updateCurrentEntry(updates: Partial<TimerEntry>) {
-        if (timerState.currentEntry) {
-            timerState.currentEntry = { ...timerState.currentEntry, ...updates };
-            saveTimerState();
+    async updateCurrentEntry(updates: Partial<TimerEntry>) {
+        if (timerState.isRunning && timerState.currentEntry) {
+            await transition('timer_update_entry', { entry: { ...timerState.currentEntry, ...updates } });
         }
     },
 
@@ -154,9 +239,9 @@ export const timerStore = {
         updateTimerHistory();
     },
 
+    // Clears the in-memory view; the history database is left alone
     clearHistory() {
         timerHistory = { entries: [], totalTime: 0, billableTime: 0 };
-        saveTimerHistory();
     },
 
     // Utility Functions
@@ -173,7 +258,7 @@ export const timerStore = {
     },
 
     getCurrentDuration(): number {
-        return timerState.totalElapsedTime;
+        return timerState.elapsedTime;
     },
 
     isIdle(): boolean {
@@ -182,19 +267,38 @@ export const timerStore = {
     }
 };
 
-// Timer Interval Management
-function startTimerInterval() {
-    if (timerInterval) clearInterval(timerInterval);
-    timerInterval = setInterval(() => {
-        timerStore.updateElapsedTime();
-    }, 100); // Update more frequently for smoother display
+// Engine Calls
+// Runs a transition in Rust; the `timer://state` event updates every window,
+// the returned state updates this one right away
+async function transition(command: string, args?: Record<string, unknown>): Promise<TimerState | null> {
+    bookingError = null;
+    try {
+        const state = await invoke<TimerState>(command, args);
+        applyState(state);
+        return state;
+    } catch (error) {
+        if (await failedBooking(error)) return timerState;
+        console.error(`Timer ${command} failed:`, error);
+        return null;
+    }
 }
 
-function stopTimerInterval() {
-    if (timerInterval) {
-        clearInterval(timerInterval);
-        timerInterval = null;
-    }
+// The transition itself went through when only sending it to Kimai failed;
+// the state is read again, as the command didn't return it
+async function failedBooking(error: unknown): Promise<boolean> {
+    const payload = error as { kind?: string; message?: string } | null;
+    if (payload?.kind !== 'booking') return false;
+    bookingError = payload.message ?? 'Could not book the timer in Kimai';
+    applyState(await invoke<TimerState>('timer_state'));
+    return true;
+}
+
+function applyState(state: TimerState) {
+    const wasRunning = timerState.isRunning && !timerState.isPaused;
+    timerState = state;
+    const isRunning = state.isRunning && !state.isPaused;
+    if (isRunning && !wasRunning) startNotificationInterval();
+    if (!isRunning && wasRunning) stopNotificationInterval();
 }
 
 function startNotificationInterval() {
@@ -220,10 +324,11 @@ function showTimerNotification() {
     if (!timerSettings.showNotifications) return;
 
     const duration = timerStore.formatTime(timerState.totalElapsedTime);
-    const title = 'Tikker Timer';
-    const message = `Timer running for ${duration}`;
+    notify('Tikker Timer', `Timer running for ${duration}`);
+}
 
-    // Use browser notifications if available
+// Use browser notifications if available
+function notify(title: string, message: string) {
     if ('Notification' in window && Notification.permission === 'granted') {
         new Notification(title, {
             body: message,
@@ -242,13 +347,39 @@ function dispatchTimerEvent(type: TimerEvent['type'], details?: any) {
 
     // Dispatch custom event for components to listen to
     window.dispatchEvent(new CustomEvent('timer-event', { detail: event }));
+
+    // Ticks are far too frequent to be worth keeping
+    if (type !== 'tick') {
+        history.recordEvent({
+            source: 'timer',
+            type,
+            timestamp: event.timestamp,
+            profileId: settingsStore.currentProfile?.id,
+            details
+        });
+    }
 }
 
 // History Management
+// Timesheets are kept in the Rust history database; the store only holds the
+// recent window the UI shows
 async function updateTimerHistory() {
+    const profileId = settingsStore.currentProfile?.id;
+    if (!profileId) return;
+
     try {
         const timeSheets = await kimaiStore.timeSheets;
-        const entries = timeSheets.map((ts: KimaiTimeSheet) => ({
+        if (timeSheets.length > 0) {
+            await history.saveTimeSheets(profileId, timeSheets);
+        }
+
+        const to = new Date();
+        const from = new Date(to.getTime() - HISTORY_DAYS * 24 * 60 * 60 * 1000);
+        const [recent, summary] = await Promise.all([
+            history.queryTimeSheets(profileId, from, to),
+            history.querySummary(profileId, from, to)
+        ]);
+        const entries = recent.map((ts: KimaiTimeSheet) => ({
             id: ts.id,
             description: ts.description,
             customer: ts.customer,
@@ -261,60 +392,27 @@ async function updateTimerHistory() {
             duration: ts.duration || 0
         }));
 
-        const totalTime = entries.reduce((sum: number, entry: any) => sum + entry.duration, 0);
-        const billableTime = entries
-            .filter((entry: any) => entry.billable)
-            .reduce((sum: number, entry: any) => sum + entry.duration, 0);
-
         timerHistory = {
             entries,
-            totalTime,
-            billableTime,
-            lastEntry: entries[entries.length - 1]
+            totalTime: summary.totalTime,
+            billableTime: summary.billableTime,
+            lastEntry: entries[0]
         };
-
-        saveTimerHistory();
     } catch (error) {
         console.error('Failed to update timer history:', error);
     }
 }
 
-// Persistence Functions
-function loadTimerState(): TimerState {
-    try {
-        const stored = localStorage.getItem('tikker-timer-state');
-        if (stored) {
-            const parsed = JSON.parse(stored);
-            // Reset running state on load to prevent issues
-            const state = {
-                ...parsed,
-                isRunning: false,
-                isPaused: false,
-                status: 'idle',
-                canStart: true,
-                canPause: false,
-                canStop: false,
-                canResume: false,
-                startTime: null,
-                pauseTime: null,
-                endTime: null
-            };
-            return state;
-        }
-    } catch (error) {
-        console.error('Failed to load timer state:', error);
-    }
-    return { ...DEFAULT_TIMER_STATE };
-}
-
-function saveTimerState() {
+// Crash Recovery
+async function checkRecovery() {
     try {
-        localStorage.setItem('tikker-timer-state', JSON.stringify(timerState));
+        recoveredTimer = await invoke<RecoveredTimer | null>('timer_recover');
     } catch (error) {
-        console.error('Failed to save timer state:', error);
+        console.error('Failed to read the timer journal:', error);
     }
 }
 
+// Persistence Functions
 function loadTimerSettings(): TimerSettings {
     try {
         const stored = localStorage.getItem('tikker-timer-settings');
@@ -335,16 +433,15 @@ function saveTimerSettings() {
     }
 }
 
-function saveTimerHistory() {
-    try {
-        localStorage.setItem('tikker-timer-history', JSON.stringify(timerHistory));
-    } catch (error) {
-        console.error('Failed to save timer history:', error);
-    }
-}
-
-// Initialize timer history on load
+// Initialize timer history on load; the old localStorage copies are derived
+// from Kimai timesheets or superseded by the engine, so they are dropped
+localStorage.removeItem('tikker-timer-history');
+localStorage.removeItem('tikker-timer-state');
 updateTimerHistory();
+checkRecovery();
+invoke<TimerState>('timer_state').then(applyState).catch((error) => {
+    console.error('Failed to read the timer state:', error);
+});
 
 // Export the store
 export default timerStore; 
\ No newline at end of file
@@ -0,0 +1,22 @@
+// History Types
+// Records kept in the Rust-side SQLite history database
+
+export type HistoryEventSource = 'timer' | 'session' | 'task';
+
+// A TimerEvent, SessionEvent or TaskEvent as stored
+export interface HistoryEvent {
+    id?: number;
+    source: HistoryEventSource;
+    type: string;
+    timestamp: string;
+    profileId?: string;
+    taskId?: number;
+    details?: any;
+}
+
+// Totals over a date range, in seconds
+export interface HistorySummary {
+    count: number;
+    totalTime: number;
+    billableTime: number;
+}
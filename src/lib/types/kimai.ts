@@ -1,6 +1,8 @@
 // Kimai API Types
 // Based on Kemai's API integration and Kimai's REST API
 
+import type { SSLSettings } from './settings.js';
+
 export interface KimaiUser {
     id: number;
     username: string;
@@ -147,11 +149,18 @@ export interface KimaiConfig {
 }
 
 // Authentication Types
+// bearer: Kimai 2 API tokens, x_auth: X-AUTH-USER/X-AUTH-TOKEN headers of older installs
+export type KimaiAuthScheme = 'bearer' | 'x_auth';
+
 export interface KimaiAuthConfig {
     type: 'api_token' | 'legacy';
+    scheme?: KimaiAuthScheme; // detected on connect
     username?: string;
+    // Only set while editing; once saved, the credential moves to the secret
+    // store and settings keep just its reference
     password?: string;
     apiToken?: string;
+    secretRef?: string;
     baseUrl: string;
 }
 
@@ -177,6 +186,77 @@ export interface KimaiPaginationResponse<T> {
     pages: number;
 }
 
+// Payload of the `kimai://request-attempt` event, one per HTTP attempt
+export interface KimaiRequestAttempt {
+    profileId: string;
+    method: string;
+    path: string;
+    attempt: number;
+    maxAttempts: number;
+    succeeded: boolean;
+    errorKind?: KimaiErrorKind;
+    error?: string;
+    retryInMs?: number;
+}
+
+// Timesheet rules from /api/config/timesheet, as kimai_get_timesheet_config returns them
+export interface KimaiTimesheetConfig {
+    allowFutureTimes: boolean;
+    allowOverlapping: boolean;
+    lockdownPeriodStart: string | null; // PHP relative date, e.g. "first day of last month"
+    lockdownPeriodEnd: string | null;
+    lockdownGracePeriod: string | null; // e.g. "+10 days"
+}
+
+export type KimaiPolicyViolationKind =
+    | 'invalid_time'
+    | 'end_before_begin'
+    | 'future_time'
+    | 'overlap'
+    | 'lockdown'
+    | 'project_not_started'
+    | 'project_ended';
+
+// A rule a timesheet would break, found before submitting it
+export interface KimaiPolicyViolation {
+    kind: KimaiPolicyViolationKind;
+    field: 'begin' | 'end' | 'project';
+    message: string;
+    timesheetId?: number; // the cached timesheet it overlaps
+}
+
+// Result of kimai_switch_timesheet: the running timesheet stops and the next
+// one starts at the same second
+export type KimaiSwitchOutcome =
+    | { outcome: 'switched'; stopped: KimaiTimeSheet; started: KimaiTimeSheet }
+    | { outcome: 'rolled_back'; running: KimaiTimeSheet; error: KimaiCommandError } // the start was refused
+    | { outcome: 'queued'; stopped: KimaiTimeSheet; begin: string; error: KimaiCommandError } // retried in the background
+    | { outcome: 'failed'; stopped: KimaiTimeSheet; error: KimaiCommandError; rollbackError: KimaiCommandError };
+
+// Payload of the `timesheets://switch` event, once a queued start landed or was given up
+export interface KimaiQueuedStartResult {
+    profileId: string;
+    begin: string;
+    started?: KimaiTimeSheet;
+    error?: KimaiCommandError;
+}
+
+// Payload of the `timesheets://page` event emitted by kimai_walk_timesheets
+export interface KimaiTimeSheetPageEvent {
+    walkId: string;
+    page: number;
+    totalPages: number;
+    totalCount: number;
+    timesheets: KimaiTimeSheet[];
+}
+
+export interface KimaiTimeSheetWalkResult {
+    walkId: string;
+    timesheets: KimaiTimeSheet[];
+    totalCount: number;
+    cancelled: boolean;
+}
+
 // Error Types
 export interface KimaiApiError {
     code: number;
@@ -184,6 +264,89 @@ export interface KimaiApiError {
     details?: any;
 }
 
+// Errors returned by the Rust Kimai commands
+export type KimaiErrorKind =
+    | 'not_connected'
+    | 'invalid_config'
+    | 'certificate'
+    | 'client_certificate'
+    | 'unauthorized'
+    | 'forbidden'
+    | 'not_found'
+    | 'validation'
+    | 'lockdown'
+    | 'tls'
+    | 'timeout'
+    | 'offline'
+    | 'server'
+    | 'invalid_response'
+    | 'unsupported'
+    | 'network'
+    | 'storage'
+    | 'partial_save' // saved, but a custom field failed; `saved` holds the entity
+    | 'secret'
+    | 'secret_locked'; // the encrypted secrets file needs its passphrase
+
+export interface KimaiCommandError {
+    kind: KimaiErrorKind;
+    message: string;
+    // Form errors keyed by field path, e.g. "name" or "metaFields.ticket"
+    field_errors: Record<string, string[]> | null;
+    fingerprint?: string; // only for 'tls'
+    saved?: unknown; // only for 'partial_save'
+}
+
+// Custom meta fields, from the custom-fields plugin
+export type KimaiMetaFieldEntity = 'timesheet' | 'customer' | 'project' | 'activity';
+
+export type KimaiMetaFieldType =
+    | 'string'
+    | 'textarea'
+    | 'integer'
+    | 'number'
+    | 'money'
+    | 'duration'
+    | 'boolean'
+    | 'choice'
+    | 'choice-multiple'
+    | 'date'
+    | 'datetime'
+    | 'email'
+    | 'url'
+    | 'color'
+    | 'country'
+    | 'currency'
+    | 'language'
+    | 'other';
+
+export interface KimaiMetaFieldDefinition {
+    name: string;
+    label?: string;
+    entity: KimaiMetaFieldEntity;
+    type: KimaiMetaFieldType;
+    required: boolean;
+    visible: boolean;
+    defaultValue?: string;
+    help?: string;
+    choices: string[];
+}
+
+// Where credentials are kept: the OS secret service, or a passphrase
+// encrypted file where none is available
+export interface KimaiSecretStatus {
+    backend: 'keyring' | 'encrypted_file';
+    locked: boolean;
+    initialized: boolean;
+}
+
+// Server capabilities, probed on connect
+export type KimaiFeature = 'tasks' | 'bearer_auth' | 'modified_after' | 'meta_fields';
+
+export interface KimaiCapabilities {
+    version: { major: number; minor: number; patch: number } | null;
+    features: KimaiFeature[];
+}
+
 // Connection State
 export interface KimaiConnectionState {
     isConnected: boolean;
@@ -191,15 +354,68 @@ export interface KimaiConnectionState {
     lastConnected?: string;
     error?: string;
     version?: KimaiVersion;
+    user?: KimaiUser;
+    authScheme?: KimaiAuthScheme;
+    capabilities?: KimaiCapabilities;
+}
+
+// Client certificate for servers that require mutual TLS.
+// `path` is a PKCS#12 bundle or a PEM chain; PEM keys come from `keyPath` or `path`.
+export interface KimaiClientCertificate {
+    path: string;
+    keyPath?: string;
+    password?: string;
+}
+
+// Certificate the server presented, from kimai_inspect_certificate
+export interface KimaiPeerCertificate {
+    fingerprint: string; // SHA-256, colon separated uppercase hex
+    pem: string;
+    trusted: boolean;
+    error?: string;
 }
 
 // Profile Management
+// Timeouts and retries; only GET requests are retried
+export interface KimaiRequestSettings {
+    connectTimeoutSecs: number;
+    timeoutSecs: number;
+    maxRetries: number;
+    retryBaseDelayMs: number;
+    retryMaxDelayMs: number;
+}
+
 export interface KimaiProfile {
     id: string;
     name: string;
     auth: KimaiAuthConfig;
+    ssl?: SSLSettings;
+    clientCertificate?: KimaiClientCertificate;
+    requests?: KimaiRequestSettings;
     autoConnect: boolean;
     lastUsed?: string;
+    pausePolicy?: PausePolicy;
+    rounding?: RoundingSettings;
+}
+
+// 'single' keeps one timesheet with the pauses taken off its duration;
+// 'split' closes the timesheet on pause and starts a new one on resume
+export type PausePolicy = 'single' | 'split';
+
+// Kimai's rounding modes; 'default' rounds begin down, end and duration up
+export type RoundingMode = 'default' | 'closest' | 'floor' | 'ceil';
+
+// Steps in minutes, 0 leaves the value as tracked
+export interface RoundingRule {
+    mode: RoundingMode;
+    begin: number;
+    end: number;
+    duration: number;
+}
+
+export interface RoundingSettings {
+    rule: RoundingRule;
+    customers: Record<string, RoundingRule>; // by customer id
 }
 
 // Cache Types
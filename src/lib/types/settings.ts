@@ -15,7 +15,11 @@ export interface AppSettings {
     // Auto-refresh Settings
     autoRefresh: AutoRefreshSettings;
 
-    // SSL Settings
+    // Stopping timers left running
+    autoStop: AutoStopSettings;
+
+    // SSL settings from before they were per profile; the settings
+    // migration copies them into each profile, which is what the client reads
     ssl: SSLSettings;
 }
 
@@ -60,13 +64,66 @@ export interface AutoRefreshSettings {
     syncOnResume: boolean;
 }
 
+export type AutoStopTrigger = 'clock_time' | 'business_hours_end' | 'day_limit';
+
+export interface AutoStopSettings {
+    enabled: boolean;
+    trigger: AutoStopTrigger;
+    clockTime: string; // local HH:MM, also used when the server has no business hours
+    gracePeriod: number; // minutes after business hours end
+    dayLimit: number; // hours, when the server has no day limit
+    warningTime: number; // minutes before the stop
+    splitAtMidnight: boolean; // one entry per day for timers crossing midnight
+}
+
 export interface SSLSettings {
     ignoreSslErrors: boolean;
-    trustedCertificates: string[];
+    trustedCertificates: string[]; // PEM file paths, PEM blocks or SHA-256 fingerprints
     verifyHostname: boolean;
 }
 
 // Default Settings
+// Settings bundles: merge adds the bundle's profiles, replace swaps everything
+export type BundleImportMode = 'merge' | 'replace';
+
+export interface BundleImportResult {
+    settings: AppSettings;
+    importedProfiles: number;
+    importedCredentials: number;
+    // New profiles that came without a credential
+    missingCredentials: string[];
+}
+
+// Kemai import preview; tokens stay on the Rust side
+export interface KemaiProfilePreview {
+    id: string;
+    name: string;
+    baseUrl: string;
+    username?: string;
+    authType: 'api_token' | 'legacy';
+    hasToken: boolean;
+    // Set when the profile is already configured and will be skipped
+    existingProfileId?: string;
+}
+
+export interface KemaiPreview {
+    path: string;
+    profiles: KemaiProfilePreview[];
+    trustedCertificates: number;
+    language?: string;
+    stopOnLock?: boolean;
+    stopOnIdle?: boolean;
+    idleDelay?: number;
+    closeToTray?: boolean;
+    minimizeToTray?: boolean;
+    warnings: string[];
+}
+
+export interface KemaiImportResult {
+    settings: AppSettings;
+    addedProfileIds: string[];
+}
+
 export const DEFAULT_SETTINGS: AppSettings = {
     profiles: [],
     ui: {
@@ -103,6 +160,15 @@ export const DEFAULT_SETTINGS: AppSettings = {
         syncOnStartup: true,
         syncOnResume: true
     },
+    autoStop: {
+        enabled: false,
+        trigger: 'business_hours_end',
+        clockTime: '20:00',
+        gracePeriod: 30,
+        dayLimit: 10,
+        warningTime: 10,
+        splitAtMidnight: false
+    },
     ssl: {
         ignoreSslErrors: false,
         trustedCertificates: [],
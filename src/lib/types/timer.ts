@@ -1,20 +1,30 @@
 // Timer Types
 
+import type { PausePolicy, RoundingRule } from './kimai.js';
+import type { AutoStopTrigger } from './settings.js';
+
 export interface TimerState {
     // Timer Status
     isRunning: boolean;
     isPaused: boolean;
     status: 'idle' | 'running' | 'paused' | 'stopped';
 
-    // Time Tracking
-    startTime: Date | null;
-    pauseTime: Date | null;
-    endTime: Date | null;
-    elapsedTime: number; // in seconds
+    // Time Tracking, measured by the Rust engine
+    startTime: string | null;
+    pauseTime: string | null;
+    endTime: string | null;
+    elapsedTime: number; // in seconds, pauses left out
     totalElapsedTime: number; // including paused time
 
-    // Current Entry
-    currentEntry?: TimerEntry;
+    // Current Entry; finished (end and duration set) once stopped
+    currentEntry?: TimerEntry | null;
+    profileId?: string | null;
+
+    // Pauses and the timesheets they map onto under the profile's pause policy
+    pausePolicy: PausePolicy;
+    pauses: PauseSegment[];
+    bookings: TimerEntry[];
+    autoStopAt?: string | null; // when the running timer stops by itself
 
     // Timer Controls
     canStart: boolean;
@@ -23,6 +33,11 @@ export interface TimerState {
     canResume: boolean;
 }
 
+export interface PauseSegment {
+    start: string;
+    end: string | null;
+}
+
 export interface TimerEntry {
     id?: number;
     description?: string;
@@ -36,6 +51,37 @@ export interface TimerEntry {
     duration: number;
 }
 
+// Sent as `timer://tick` on each elapsed second while running
+export interface TimerTick {
+    elapsed: number; // whole seconds worked
+    totalElapsed: number; // whole seconds since the start
+    at: string;
+}
+
+// An entry as tracked and as the profile's rounding books it
+export interface RoundingPreview {
+    rule: RoundingRule;
+    tracked: TimerEntry;
+    booked: TimerEntry;
+}
+
+// Result of timer_split: the part before the split, and the timer carrying on
+export interface TimerSplit {
+    bookings: TimerEntry[]; // finished and rounded, ready to be saved
+    state: TimerState;
+}
+
+// A timer the previous session left running, replayed from the Rust journal
+export interface RecoveredTimer {
+    entry: TimerEntry | null;
+    profileId: string | null;
+    startedAt: string;
+    pausedAt: string | null;
+    pausedSeconds: number;
+    pauses: PauseSegment[];
+    lastAlive: string; // last moment the app was known to be running
+}
+
 export interface TimerSettings {
     // Timer Behavior
     autoStart: boolean;
@@ -57,8 +103,15 @@ export interface TimerSettings {
     showIdleWarning: boolean;
 }
 
+// Payload of `timer://auto-stop`: the warning ahead of an automatic stop, then the stop
+export interface AutoStopNotice {
+    kind: 'warning' | 'stopped';
+    trigger: AutoStopTrigger;
+    at: string;
+}
+
 export interface TimerEvent {
-    type: 'start' | 'pause' | 'resume' | 'stop' | 'tick' | 'idle' | 'lock';
+    type: 'start' | 'pause' | 'resume' | 'stop' | 'split' | 'tick' | 'idle' | 'lock';
     timestamp: string;
     duration?: number;
     details?: any;
@@ -81,6 +134,9 @@ export const DEFAULT_TIMER_STATE: TimerState = {
     endTime: null,
     elapsedTime: 0,
     totalElapsedTime: 0,
+    pausePolicy: 'single',
+    pauses: [],
+    bookings: [],
     canStart: true,
     canPause: false,
     canStop: false,
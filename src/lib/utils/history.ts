@@ -0,0 +1,48 @@
+// History database
+// Wrappers around the history commands. Ranges are half-open: [from, to).
+
+import { invoke } from '@tauri-apps/api/core';
+import type { KimaiTimeSheet } from '$lib/types/kimai.js';
+import type { HistoryEvent, HistoryEventSource, HistorySummary } from '$lib/types/history.js';
+
+export function saveTimeSheets(profileId: string, timesheets: KimaiTimeSheet[]): Promise<number> {
+    return invoke<number>('history_upsert_timesheets', { profileId, timesheets });
+}
+
+export function deleteTimeSheets(profileId: string, ids: number[]): Promise<number> {
+    return invoke<number>('history_delete_timesheets', { profileId, ids });
+}
+
+export function queryTimeSheets(profileId: string, from: Date, to: Date, limit?: number): Promise<KimaiTimeSheet[]> {
+    return invoke<KimaiTimeSheet[]>('history_timesheets', {
+        profileId,
+        from: from.toISOString(),
+        to: to.toISOString(),
+        limit: limit ?? null
+    });
+}
+
+export function querySummary(profileId: string, from: Date, to: Date): Promise<HistorySummary> {
+    return invoke<HistorySummary>('history_summary', { profileId, from: from.toISOString(), to: to.toISOString() });
+}
+
+export function queryEvents(
+    from: Date,
+    to: Date,
+    source?: HistoryEventSource,
+    profileId?: string
+): Promise<HistoryEvent[]> {
+    return invoke<HistoryEvent[]>('history_events', {
+        from: from.toISOString(),
+        to: to.toISOString(),
+        source: source ?? null,
+        profileId: profileId ?? null
+    });
+}
+
+// Fire and forget; a lost event record must never break the caller
+export function recordEvent(event: HistoryEvent): void {
+    invoke('history_record_event', { event }).catch((error) => {
+        console.error('Failed to record history event:', error);
+    });
+}
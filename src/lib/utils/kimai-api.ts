@@ -1,6 +1,10 @@
 // Kimai API Client
-// Handles all API operations to Kimai server instances
+// Thin wrapper around the Rust Kimai commands. Requests are made by the Rust
+// side, which reads credentials from the secret store; the webview only ever
+// sees the profile id.
 
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import type {
     KimaiUser,
     KimaiCustomer,
@@ -9,45 +13,45 @@ import type {
     KimaiTimeSheet,
     KimaiTask,
     KimaiVersion,
-    KimaiConfig,
     KimaiAuthConfig,
-    KimaiApiResponse,
-    KimaiPaginationResponse,
-    KimaiConnectionState
+    KimaiCache,
+    KimaiCommandError,
+    KimaiConnectionState,
+    KimaiErrorKind,
+    KimaiPeerCertificate,
+    KimaiProfile,
+    KimaiPolicyViolation,
+    KimaiSwitchOutcome,
+    KimaiTimeSheetPageEvent,
+    KimaiTimeSheetWalkResult,
+    KimaiTimesheetConfig
 } from '$lib/types/kimai.js';
 
+type KimaiTaskStatus = 'open' | 'closed' | 'pending' | 'progress';
+type KimaiTaskAction = 'start' | 'stop' | 'close' | 'reopen' | 'assign' | 'unassign';
+
 export class KimaiApiClient {
-    private baseUrl: string;
-    private authConfig: KimaiAuthConfig;
+    private profile: KimaiProfile;
     private connectionState: KimaiConnectionState;
 
-    constructor(authConfig: KimaiAuthConfig) {
-        this.authConfig = authConfig;
-        this.baseUrl = authConfig.baseUrl.replace(/\/$/, ''); // Remove trailing slash
+    constructor(profile: KimaiProfile) {
+        this.profile = profile;
         this.connectionState = {
             isConnected: false,
             isConnecting: false
         };
     }
 
+    get profileId(): string {
+        return this.profile.id;
+    }
+
     // Connection Management
     async connect(): Promise<KimaiConnectionState> {
         this.connectionState.isConnecting = true;
 
         try {
-            // Check version and compatibility
-            const version = await this.getVersion();
-
-            // Test authentication
-            const user = await this.getCurrentUser();
-
-            this.connectionState = {
-                isConnected: true,
-                isConnecting: false,
-                lastConnected: new Date().toISOString(),
-                version
-            };
-
+            this.connectionState = await call<KimaiConnectionState>('kimai_connect', { profile: this.profile });
             return this.connectionState;
         } catch (error) {
             this.connectionState = {
@@ -59,119 +63,100 @@ export class KimaiApiClient {
         }
     }
 
+    async disconnect(): Promise<void> {
+        await this.invoke<void>('kimai_disconnect');
+    }
+
     getConnectionState(): KimaiConnectionState {
         return { ...this.connectionState };
     }
 
-    // Version and Configuration
+    // Version
     async getVersion(): Promise<KimaiVersion> {
-        return this.request<KimaiVersion>('/api/version');
-    }
-
-    async getTimesheetConfig(): Promise<any> {
-        return this.request<any>('/api/config/timesheet');
-    }
-
-    async getColorsConfig(): Promise<any> {
-        return this.request<any>('/api/config/colors');
+        return this.invoke<KimaiVersion>('kimai_get_version');
     }
 
     // Authentication
     async getCurrentUser(): Promise<KimaiUser> {
-        return this.request<KimaiUser>('/api/users/me');
+        return this.invoke<KimaiUser>('kimai_get_current_user');
     }
 
     // Customer Management
     async getCustomers(): Promise<KimaiCustomer[]> {
-        return this.request<KimaiCustomer[]>('/api/customers');
+        return this.invoke<KimaiCustomer[]>('kimai_list_customers');
     }
 
     async getCustomer(id: number): Promise<KimaiCustomer> {
-        return this.request<KimaiCustomer>(`/api/customers/${id}`);
+        return this.invoke<KimaiCustomer>('kimai_get_customer', { id });
     }
 
     async createCustomer(customer: Partial<KimaiCustomer>): Promise<KimaiCustomer> {
-        return this.request<KimaiCustomer>('/api/customers', {
-            method: 'POST',
-            body: JSON.stringify(customer)
-        });
+        return this.invoke<KimaiCustomer>('kimai_create_customer', { customer });
     }
 
     async updateCustomer(id: number, customer: Partial<KimaiCustomer>): Promise<KimaiCustomer> {
-        return this.request<KimaiCustomer>(`/api/customers/${id}`, {
-            method: 'PATCH',
-            body: JSON.stringify(customer)
-        });
+        return this.invoke<KimaiCustomer>('kimai_update_customer', { id, customer });
     }
 
     async deleteCustomer(id: number): Promise<void> {
-        return this.request<void>(`/api/customers/${id}`, {
-            method: 'DELETE'
-        });
+        return this.invoke<void>('kimai_delete_customer', { id });
     }
 
     // Project Management
     async getProjects(customerId?: number): Promise<KimaiProject[]> {
-        const params = customerId ? `?customer=${customerId}` : '';
-        return this.request<KimaiProject[]>(`/api/projects${params}`);
+        return this.invoke<KimaiProject[]>('kimai_list_projects', { customer: customerId ?? null });
     }
 
     async getProject(id: number): Promise<KimaiProject> {
-        return this.request<KimaiProject>(`/api/projects/${id}`);
+        return this.invoke<KimaiProject>('kimai_get_project', { id });
     }
 
     async createProject(project: Partial<KimaiProject>): Promise<KimaiProject> {
-        return this.request<KimaiProject>('/api/projects', {
-            method: 'POST',
-            body: JSON.stringify(project)
-        });
+        return this.invoke<KimaiProject>('kimai_create_project', { project });
     }
 
     async updateProject(id: number, project: Partial<KimaiProject>): Promise<KimaiProject> {
-        return this.request<KimaiProject>(`/api/projects/${id}`, {
-            method: 'PATCH',
-            body: JSON.stringify(project)
-        });
+        return this.invoke<KimaiProject>('kimai_update_project', { id, project });
     }
 
     async deleteProject(id: number): Promise<void> {
-        return this.request<void>(`/api/projects/${id}`, {
-            method: 'DELETE'
-        });
+        return this.invoke<void>('kimai_delete_project', { id });
     }
 
     // Activity Management
     async getActivities(projectId?: number): Promise<KimaiActivity[]> {
-        const params = projectId ? `?project=${projectId}` : '';
-        return this.request<KimaiActivity[]>(`/api/activities${params}`);
+        return this.invoke<KimaiActivity[]>('kimai_list_activities', { project: projectId ?? null });
     }
 
     async getActivity(id: number): Promise<KimaiActivity> {
-        return this.request<KimaiActivity>(`/api/activities/${id}`);
+        return this.invoke<KimaiActivity>('kimai_get_activity', { id });
     }
 
     async createActivity(activity: Partial<KimaiActivity>): Promise<KimaiActivity> {
-        return this.request<KimaiActivity>('/api/activities', {
-            method: 'POST',
-            body: JSON.stringify(activity)
-        });
+        return this.invoke<KimaiActivity>('kimai_create_activity', { activity });
     }
 
     async updateActivity(id: number, activity: Partial<KimaiActivity>): Promise<KimaiActivity> {
-        return this.request<KimaiActivity>(`/api/activities/${id}`, {
-            method: 'PATCH',
-            body: JSON.stringify(activity)
-        });
+        return this.invoke<KimaiActivity>('kimai_update_activity', { id, activity });
     }
 
     async deleteActivity(id: number): Promise<void> {
-        return this.request<void>(`/api/activities/${id}`, {
-            method: 'DELETE'
-        });
+        return this.invoke<void>('kimai_delete_activity', { id });
+    }
+
+    // Entity Cache
+    // The cache saved for this profile, without asking the server
+    async loadCache(): Promise<KimaiCache | null> {
+        return this.invoke<KimaiCache | null>('kimai_load_cache');
+    }
+
+    // Brings the saved cache up to date and returns it; `force` reloads everything
+    async refreshCache(force = false): Promise<KimaiCache> {
+        return this.invoke<KimaiCache>('kimai_refresh_cache', { force });
     }
 
     // Time Sheet Management
-    async getTimeSheets(params?: {
+    async getTimeSheets(query?: {
         user?: number;
         customer?: number;
         project?: number;
@@ -180,47 +165,69 @@ export class KimaiApiClient {
         end?: string;
         page?: number;
         size?: number;
-    }): Promise<KimaiPaginationResponse<KimaiTimeSheet>> {
-        const queryParams = new URLSearchParams();
-        if (params) {
-            Object.entries(params).forEach(([key, value]) => {
-                if (value !== undefined) {
-                    queryParams.append(key, value.toString());
-                }
-            });
+    }): Promise<KimaiTimeSheet[]> {
+        return this.invoke<KimaiTimeSheet[]>('kimai_list_timesheets', { query: query ?? null });
+    }
+
+    // Loads every page matching `query`, handing each to `onPage` as it
+    // arrives. cancelTimeSheetWalk(walkId) stops it with what was loaded.
+    async walkTimeSheets(
+        walkId: string,
+        query: { begin?: string; end?: string; project?: number; activity?: number },
+        onPage?: (page: KimaiTimeSheetPageEvent) => void
+    ): Promise<KimaiTimeSheetWalkResult> {
+        const unlisten = await listen<KimaiTimeSheetPageEvent>('timesheets://page', (event) => {
+            if (event.payload.walkId === walkId) onPage?.(event.payload);
+        });
+        try {
+            return await this.invoke<KimaiTimeSheetWalkResult>('kimai_walk_timesheets', { walkId, query });
+        } finally {
+            unlisten();
         }
+    }
 
-        const queryString = queryParams.toString();
-        const url = `/api/timesheets${queryString ? `?${queryString}` : ''}`;
-        return this.request<KimaiPaginationResponse<KimaiTimeSheet>>(url);
+    async cancelTimeSheetWalk(walkId: string): Promise<boolean> {
+        return this.invoke<boolean>('kimai_cancel_timesheet_walk', { walkId });
     }
 
     async getActiveTimeSheets(): Promise<KimaiTimeSheet[]> {
-        return this.request<KimaiTimeSheet[]>('/api/timesheets/active');
+        return this.invoke<KimaiTimeSheet[]>('kimai_list_active_timesheets');
     }
 
     async getTimeSheet(id: number): Promise<KimaiTimeSheet> {
-        return this.request<KimaiTimeSheet>(`/api/timesheets/${id}`);
+        return this.invoke<KimaiTimeSheet>('kimai_get_timesheet', { id });
     }
 
-    async createTimeSheet(timeSheet: Partial<KimaiTimeSheet>): Promise<KimaiTimeSheet> {
-        return this.request<KimaiTimeSheet>('/api/timesheets', {
-            method: 'POST',
-            body: JSON.stringify(timeSheet)
-        });
+    async getTimesheetConfig(): Promise<KimaiTimesheetConfig> {
+        return this.invoke<KimaiTimesheetConfig>('kimai_get_timesheet_config');
     }
 
-    async updateTimeSheet(id: number, timeSheet: Partial<KimaiTimeSheet>): Promise<KimaiTimeSheet> {
-        return this.request<KimaiTimeSheet>(`/api/timesheets/${id}`, {
-            method: 'PATCH',
-            body: JSON.stringify(timeSheet)
-        });
+    // Checks a new timesheet, or the one with `id`, against the server's
+    // timesheet rules and the cached timesheets; empty when nothing is known
+    // to stand in the way
+    async validateTimeSheet(timesheet: Partial<KimaiTimeSheet>, id?: number): Promise<KimaiPolicyViolation[]> {
+        return this.invoke<KimaiPolicyViolation[]>('kimai_validate_timesheet', { id: id ?? null, timesheet });
+    }
+
+    async createTimeSheet(timesheet: Partial<KimaiTimeSheet>): Promise<KimaiTimeSheet> {
+        return this.invoke<KimaiTimeSheet>('kimai_create_timesheet', { timesheet });
+    }
+
+    async updateTimeSheet(id: number, timesheet: Partial<KimaiTimeSheet>): Promise<KimaiTimeSheet> {
+        return this.invoke<KimaiTimeSheet>('kimai_update_timesheet', { id, timesheet });
+    }
+
+    // Stops timesheet `id` and starts `timesheet` at the same second
+    async switchTimeSheet(id: number, timesheet: Partial<KimaiTimeSheet>): Promise<KimaiSwitchOutcome> {
+        return this.invoke<KimaiSwitchOutcome>('kimai_switch_timesheet', { id, timesheet });
+    }
+
+    async cancelQueuedStart(): Promise<boolean> {
+        return this.invoke<boolean>('kimai_cancel_queued_start');
     }
 
     async deleteTimeSheet(id: number): Promise<void> {
-        return this.request<void>(`/api/timesheets/${id}`, {
-            method: 'DELETE'
-        });
+        return this.invoke<void>('kimai_delete_timesheet', { id });
     }
 
     // Task Management
@@ -229,193 +236,132 @@ export class KimaiApiClient {
         customer?: number;
         project?: number;
         activity?: number;
-        status?: 'open' | 'closed' | 'pending' | 'progress' | ('open' | 'closed' | 'pending' | 'progress')[];
+        status?: KimaiTaskStatus | KimaiTaskStatus[];
         page?: number;
         size?: number;
     }): Promise<KimaiTask[]> {
-        const queryParams = new URLSearchParams();
-        if (params) {
-            Object.entries(params).forEach(([key, value]) => {
-                if (value !== undefined) {
-                    if (key === 'user') {
-                        // Use users[] format for user filtering
-                        queryParams.append('users[]', value.toString());
-                    } else if (key === 'status') {
-                        // Use status[] format for status filtering as per API docs
-                        if (Array.isArray(value)) {
-                            value.forEach(status => queryParams.append('status[]', status));
-                        } else {
-                            queryParams.append('status[]', value.toString());
-                        }
-                    } else {
-                        queryParams.append(key, value.toString());
-                    }
-                }
-            });
-        }
-
-        const queryString = queryParams.toString();
-        const url = `/api/tasks${queryString ? `?${queryString}` : ''}`;
-        return this.request<KimaiTask[]>(url);
+        const status = params?.status === undefined ? [] : [params.status].flat();
+        const query = params ? { ...params, status } : null;
+        return this.invoke<KimaiTask[]>('kimai_list_tasks', { query });
     }
 
     async getTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}`);
+        return this.invoke<KimaiTask>('kimai_get_task', { id });
     }
 
     async createTask(task: Partial<KimaiTask>): Promise<KimaiTask> {
-        return this.request<KimaiTask>('/api/tasks', {
-            method: 'POST',
-            body: JSON.stringify(task)
-        });
+        return this.invoke<KimaiTask>('kimai_create_task', { task });
     }
 
     async updateTask(id: number, task: Partial<KimaiTask>): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}`, {
-            method: 'PATCH',
-            body: JSON.stringify(task)
-        });
+        return this.invoke<KimaiTask>('kimai_update_task', { id, task });
     }
 
     async deleteTask(id: number): Promise<void> {
-        return this.request<void>(`/api/tasks/${id}`, {
-            method: 'DELETE'
-        });
+        return this.invoke<void>('kimai_delete_task', { id });
     }
 
     // Task Time Tracking
     async startTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}/start`, {
-            method: 'PATCH'
-        });
+        return this.taskAction(id, 'start');
     }
 
     async stopTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}/stop`, {
-            method: 'PATCH'
-        });
+        return this.taskAction(id, 'stop');
     }
 
     async closeTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}/close`, {
-            method: 'PATCH'
-        });
+        return this.taskAction(id, 'close');
     }
 
     async reopenTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}/reopen`, {
-            method: 'PATCH'
-        });
+        return this.taskAction(id, 'reopen');
     }
 
     async assignTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}/assign`, {
-            method: 'PATCH'
-        });
+        return this.taskAction(id, 'assign');
     }
 
     async unassignTask(id: number): Promise<KimaiTask> {
-        return this.request<KimaiTask>(`/api/tasks/${id}/unassign`, {
-            method: 'PATCH'
-        });
+        return this.taskAction(id, 'unassign');
     }
 
-    // Core HTTP Request Method
-    private async request<T>(
-        endpoint: string,
-        options: RequestInit = {}
-    ): Promise<T> {
-        const url = `${this.baseUrl}${endpoint}`;
-
-        // Prepare headers
-        const headers: Record<string, string> = {
-            'Content-Type': 'application/json',
-            'Accept': 'application/json'
-        };
-
-        // Add custom headers
-        if (options.headers) {
-            Object.entries(options.headers).forEach(([key, value]) => {
-                if (typeof value === 'string') {
-                    headers[key] = value;
-                }
-            });
-        }
-
-        // Add authentication
-        if (this.authConfig.type === 'api_token' && this.authConfig.apiToken) {
-            headers['Authorization'] = `Bearer ${this.authConfig.apiToken}`;
-        } else if (this.authConfig.type === 'legacy' && this.authConfig.username && this.authConfig.password) {
-            const credentials = btoa(`${this.authConfig.username}:${this.authConfig.password}`);
-            headers['Authorization'] = `Basic ${credentials}`;
-        }
+    private taskAction(id: number, action: KimaiTaskAction): Promise<KimaiTask> {
+        return this.invoke<KimaiTask>('kimai_task_action', { id, action });
+    }
 
-        // Prepare request options
-        const requestOptions: RequestInit = {
-            method: options.method || 'GET',
-            headers,
-            ...options
-        };
+    // Every command but kimai_connect works on the connection of one profile
+    private invoke<T>(command: string, args: Record<string, unknown> = {}): Promise<T> {
+        return call<T>(command, { profileId: this.profile.id, ...args });
+    }
+}
 
-        try {
-            const response = await fetch(url, requestOptions);
-
-            if (!response.ok) {
-                const errorData = await response.json().catch(() => ({}));
-                throw new KimaiApiError(
-                    response.status,
-                    errorData.message || `HTTP ${response.status}: ${response.statusText}`,
-                    errorData
-                );
-            }
-
-            // Handle empty responses
-            if (response.status === 204 || response.headers.get('content-length') === '0') {
-                return {} as T;
-            }
-
-            const data = await response.json();
-            return data;
-        } catch (error) {
-            if (error instanceof KimaiApiError) {
-                throw error;
-            }
-
-            // Network or other errors
-            throw new KimaiApiError(
-                0,
-                error instanceof Error ? error.message : 'Network error',
-                { originalError: error }
-            );
-        }
+async function call<T>(command: string, args: Record<string, unknown>): Promise<T> {
+    try {
+        return await invoke<T>(command, args);
+    } catch (error) {
+        throw KimaiApiError.from(error);
     }
 }
 
 // Custom Error Class
-export class KimaiApiError extends Error implements KimaiApiError {
+export class KimaiApiError extends Error {
     constructor(
-        public code: number,
+        public kind: KimaiErrorKind | 'unknown',
         message: string,
-        public details?: any
+        public fieldErrors: Record<string, string[]> | null = null,
+        public fingerprint?: string,
+        public saved?: unknown
     ) {
         super(message);
         this.name = 'KimaiApiError';
     }
+
+    // Wraps the `{ kind, message, ... }` payload the Rust commands reject with
+    static from(error: unknown): KimaiApiError {
+        if (error instanceof KimaiApiError) return error;
+        if (error && typeof error === 'object' && 'kind' in error && 'message' in error) {
+            const payload = error as KimaiCommandError;
+            return new KimaiApiError(
+                payload.kind,
+                payload.message,
+                payload.field_errors ?? null,
+                payload.fingerprint,
+                payload.saved
+            );
+        }
+        return new KimaiApiError('unknown', error instanceof Error ? error.message : String(error));
+    }
 }
 
 // Utility Functions
-export function createKimaiClient(authConfig: KimaiAuthConfig): KimaiApiClient {
-    return new KimaiApiClient(authConfig);
+export function createKimaiClient(profile: KimaiProfile): KimaiApiClient {
+    return new KimaiApiClient(profile);
+}
+
+// Certificate of the profile's server, as checked with the profile's SSL settings.
+// Works without a connection, so unsaved profiles can be checked too.
+export function inspectCertificate(profile: KimaiProfile): Promise<KimaiPeerCertificate> {
+    return call<KimaiPeerCertificate>('kimai_inspect_certificate', { profile });
 }
 
+// A stored credential (secretRef) counts; Rust reads it when connecting
 export function validateAuthConfig(authConfig: KimaiAuthConfig): boolean {
     if (!authConfig.baseUrl) return false;
 
     if (authConfig.type === 'api_token') {
-        return !!authConfig.apiToken;
+        return !!(authConfig.apiToken || authConfig.secretRef);
     } else if (authConfig.type === 'legacy') {
-        return !!(authConfig.username && authConfig.password);
+        return !!(authConfig.username && (authConfig.password || authConfig.secretRef));
     }
 
     return false;
-} 
\ No newline at end of file
+}
+
+// The entity a partial save stored before one of its custom fields failed
+export function partiallySaved<T>(error: unknown): T | null {
+    if (error instanceof KimaiApiError && error.kind === 'partial_save') {
+        return (error.saved as T) ?? null;
+    }
+    return null;
+}
@@ -0,0 +1,11 @@
+// Time inputs
+// Helpers for the "HH:mm" values of time inputs
+
+// The latest past moment at `time` today, or yesterday after midnight
+export function lastAt(time: string): Date {
+    const [hours, minutes] = time.split(':').map(Number);
+    const at = new Date();
+    at.setHours(hours, minutes, 0, 0);
+    if (at > new Date()) at.setDate(at.getDate() - 1);
+    return at;
+}